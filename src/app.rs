@@ -1,9 +1,14 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{watch, RwLock};
 
 use crate::cli::Args;
-use crate::playback::{list_audio_devices, default_device_index, AudioDevice};
+use crate::playback::{find_device, list_audio_devices, AudioDevice, OutputBackend, TransitionStyle};
+use crate::prefs::Preferences;
+
+/// Volume change per key press
+const VOLUME_STEP: f32 = 0.05;
 
 /// Station metadata
 #[derive(Debug, Clone)]
@@ -35,6 +40,143 @@ impl Default for StationInfo {
     }
 }
 
+/// Alternative tempo interpretation from BPM detection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoCandidate {
+    /// Candidate tempo
+    pub bpm: f32,
+    /// Autocorrelation strength weighted by the perceptual tempo prior
+    pub score: f32,
+}
+
+/// Major or minor mode of a musical key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// Musical key (tonic pitch class and mode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MusicalKey {
+    /// Tonic pitch class (0 = C, 1 = C#/Db, ... 11 = B)
+    pub tonic: u8,
+    pub mode: Mode,
+}
+
+/// Pitch class names, using the spelling most common in each key
+const PITCH_CLASS_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+impl MusicalKey {
+    pub fn new(tonic: u8, mode: Mode) -> Self {
+        Self { tonic: tonic % 12, mode }
+    }
+
+    /// Short key name, e.g. "C" or "F#m"
+    pub fn name(&self) -> String {
+        let tonic = PITCH_CLASS_NAMES[self.tonic as usize];
+        match self.mode {
+            Mode::Major => tonic.to_string(),
+            Mode::Minor => format!("{}m", tonic),
+        }
+    }
+
+    /// Position on the Camelot wheel (1-12) and its letter (A = minor, B = major)
+    pub fn camelot(&self) -> (u8, char) {
+        // Minor keys share a number with their relative major (a minor third up)
+        let (major_tonic, letter) = match self.mode {
+            Mode::Major => (self.tonic, 'B'),
+            Mode::Minor => ((self.tonic + 3) % 12, 'A'),
+        };
+        // Each step round the wheel is a fifth; C major is 8B
+        ((major_tonic * 7 + 7) % 12 + 1, letter)
+    }
+
+    /// Same mode, shifted by a number of semitones
+    pub fn transposed(&self, semitones: i8) -> MusicalKey {
+        MusicalKey::new((self.tonic as i32 + semitones as i32).rem_euclid(12) as u8, self.mode)
+    }
+
+    /// Smallest shift (-5..=6 semitones) that lands this key on `target`'s Camelot number
+    ///
+    /// Major and minor keys are matched through their relatives, so A minor
+    /// needs no shift to sit with C major.
+    pub fn semitones_to(&self, target: &MusicalKey) -> i8 {
+        let relative_major = |key: &MusicalKey| match key.mode {
+            Mode::Major => key.tonic as i32,
+            Mode::Minor => (key.tonic as i32 + 3) % 12,
+        };
+        let shift = (relative_major(target) - relative_major(self)).rem_euclid(12);
+        if shift > 6 {
+            (shift - 12) as i8
+        } else {
+            shift as i8
+        }
+    }
+
+    /// How well a clip in `other` follows this key (1.0 = same key, 0.0 = clash)
+    ///
+    /// Follows the Camelot wheel: the same number (relative major/minor) and
+    /// neighbouring numbers with the same letter mix well.
+    pub fn compatibility(&self, other: &MusicalKey) -> f32 {
+        let (a_number, a_letter) = self.camelot();
+        let (b_number, b_letter) = other.camelot();
+        let diff = (a_number as i32 - b_number as i32).rem_euclid(12);
+        let steps = diff.min(12 - diff);
+
+        match (steps, a_letter == b_letter) {
+            (0, true) => 1.0,
+            (0, false) => 0.9,
+            (1, true) => 0.8,
+            (1, false) => 0.5,
+            (2, true) => 0.4,
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::str::FromStr for MusicalKey {
+    type Err = String;
+
+    /// Parse a key name such as "C", "F#", "Bb", "Am" or "C#min"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.trim().chars();
+        let letter = chars.next().ok_or("empty key name")?;
+        let natural = match letter.to_ascii_uppercase() {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return Err(format!("invalid key '{}'", s)),
+        };
+
+        let rest = chars.as_str();
+        let (tonic, suffix) = match rest.chars().next() {
+            Some('#') => (natural + 1, &rest[1..]),
+            Some('b') => (natural + 11, &rest[1..]),
+            _ => (natural, rest),
+        };
+
+        let mode = match suffix.to_ascii_lowercase().as_str() {
+            "" | "maj" | "major" => Mode::Major,
+            "m" | "min" | "minor" => Mode::Minor,
+            _ => return Err(format!("invalid key '{}'", s)),
+        };
+
+        Ok(MusicalKey::new(tonic, mode))
+    }
+}
+
+impl std::fmt::Display for MusicalKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (number, letter) = self.camelot();
+        write!(f, "{} ({}{})", self.name(), number, letter)
+    }
+}
+
 /// Loop metadata after quantization
 #[derive(Debug, Clone)]
 pub struct LoopInfo {
@@ -48,9 +190,21 @@ pub struct LoopInfo {
     pub time_stretched: bool,
     /// Number of bars in the loop
     pub bars: u8,
-    /// Beats per bar
-    #[allow(dead_code)]
+    /// Beats per bar (fixed or detected)
     pub beats_per_bar: u8,
+    /// Tracked beat positions in frames from the loop start (first beat is a downbeat)
+    #[allow(dead_code)]
+    pub beat_positions: Vec<usize>,
+    /// Ranked tempo candidates from detection (best first)
+    pub tempo_candidates: Vec<TempoCandidate>,
+    /// Musical key as heard, after any pitch shift (None if no clear tonal centre)
+    pub key: Option<MusicalKey>,
+    /// Pitch shift applied to reach the target key (semitones)
+    pub transpose_semitones: i8,
+    /// Integrated loudness before normalization (LUFS, None if below the gate)
+    pub loudness_lufs: Option<f32>,
+    /// Gain applied by loudness normalization (dB)
+    pub gain_db: f32,
     /// Duration in samples (frames)
     pub duration_samples: usize,
     /// Sample rate
@@ -63,6 +217,41 @@ impl LoopInfo {
     pub fn duration_secs(&self) -> f32 {
         self.duration_samples as f32 / self.sample_rate as f32
     }
+
+    /// One bar of 4/4 at `bpm` with no analysis results, for tests
+    #[cfg(test)]
+    pub fn for_test(bpm: f32, frames: usize) -> Self {
+        Self {
+            bpm,
+            source_bpm: bpm,
+            bpm_confidence: 1.0,
+            time_stretched: false,
+            bars: 1,
+            beats_per_bar: 4,
+            beat_positions: Vec::new(),
+            tempo_candidates: Vec::new(),
+            key: None,
+            transpose_semitones: 0,
+            loudness_lufs: None,
+            gain_db: 0.0,
+            duration_samples: frames,
+            sample_rate: crate::audio::SAMPLE_RATE,
+        }
+    }
+
+    /// Time signature label, e.g. "3/4" or "6/4"
+    pub fn time_signature(&self) -> String {
+        time_signature(self.beats_per_bar)
+    }
+
+    /// Tempo candidates other than the detected source tempo
+    pub fn alternative_tempos(&self) -> impl Iterator<Item = f32> + '_ {
+        let source_bpm = self.source_bpm;
+        self.tempo_candidates
+            .iter()
+            .map(|c| c.bpm)
+            .filter(move |bpm| (bpm / source_bpm - 1.0).abs() > 0.03)
+    }
 }
 
 /// BPM mode selection
@@ -81,12 +270,77 @@ impl BpmMode {
     }
 }
 
+/// Meter (beats per bar) selection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeterMode {
+    /// Estimate beats per bar from accent patterns
+    Auto,
+    /// Use fixed beats per bar
+    Fixed(u8),
+}
+
+impl std::fmt::Display for MeterMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeterMode::Auto => write!(f, "Auto"),
+            MeterMode::Fixed(beats_per_bar) => write!(f, "{}", time_signature(*beats_per_bar)),
+        }
+    }
+}
+
+/// Time signature label for a number of beats per bar
+///
+/// Beats are counted at the tracked tempo, so six beats are 6/4: the meter
+/// detector has no notion of compound meters.
+pub fn time_signature(beats_per_bar: u8) -> String {
+    format!("{}/4", beats_per_bar)
+}
+
+/// Key selection for pitch shifting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyMode {
+    /// Keep every clip in its original key
+    Original,
+    /// Transpose every clip to this key
+    Fixed(MusicalKey),
+    /// Transpose every clip to the key of the first clip with a detected key
+    LockToFirst,
+}
+
+impl std::str::FromStr for KeyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "first" | "lock" => Ok(KeyMode::LockToFirst),
+            "original" | "off" => Ok(KeyMode::Original),
+            _ => s.parse().map(KeyMode::Fixed),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyMode::Original => write!(f, "Original"),
+            KeyMode::Fixed(key) => write!(f, "{}", key),
+            KeyMode::LockToFirst => write!(f, "Lock to first"),
+        }
+    }
+}
+
 /// User-configurable settings
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub bpm_mode: BpmMode,
     pub bars: u8,
-    pub beats_per_bar: u8,
+    pub meter: MeterMode,
+    pub key_mode: KeyMode,
+    pub transition: TransitionStyle,
+    /// Playback volume (0.0 to 1.0, saved between sessions)
+    pub volume: f32,
+    /// Output muted (volume is kept for unmuting)
+    pub muted: bool,
     pub listen_seconds: u32,
     #[allow(dead_code)]
     pub clip_seconds: u32,
@@ -96,14 +350,19 @@ pub struct Settings {
     pub min_rms: f32,
     #[allow(dead_code)]
     pub max_silence: f32,
-    /// Selected audio output device index
-    pub audio_device_index: usize,
+    /// Audio output device chosen by name (exact or substring, default device if `None`)
+    pub audio_device: Option<String>,
+    /// Name of the device actually playing (the default one while the chosen one is missing)
+    pub active_audio_device: String,
     /// Cached list of available audio devices
     pub audio_devices: Vec<AudioDevice>,
+    /// Output backend (devices can only be cycled on a device output)
+    pub output: OutputBackend,
 }
 
 impl Settings {
-    pub fn from_args(args: &Args) -> Self {
+    /// Settings from the command line, with volume and mute from saved preferences
+    pub fn from_args(args: &Args, prefs: Preferences) -> Self {
         let bpm_mode = args
             .bpm
             .map(BpmMode::Fixed)
@@ -113,19 +372,28 @@ impl Settings {
             });
 
         let audio_devices = list_audio_devices();
-        let audio_device_index = default_device_index();
+        let audio_device = match &args.output {
+            OutputBackend::NamedDevice(name) => Some(name.clone()),
+            _ => args.device.clone(),
+        };
 
         Self {
             bpm_mode,
             bars: args.bars,
-            beats_per_bar: args.beats_per_bar(),
+            meter: args.meter_mode(),
+            key_mode: args.key.unwrap_or(KeyMode::Original),
+            transition: args.transition,
+            volume: prefs.volume.clamp(0.0, 1.0),
+            muted: prefs.muted,
             listen_seconds: args.listen_seconds,
             clip_seconds: args.clip_seconds,
             station_change_seconds: args.station_change_seconds,
             min_rms: args.min_rms,
             max_silence: args.max_silence,
-            audio_device_index,
+            audio_device,
+            active_audio_device: String::new(),
             audio_devices,
+            output: args.output.clone(),
         }
     }
 
@@ -159,35 +427,100 @@ impl Settings {
         };
     }
 
-    /// Cycle to next audio device (refreshes device list first)
+    /// Cycle to the next transition style
+    pub fn cycle_transition(&mut self) {
+        self.transition = self.transition.next();
+    }
+
+    /// Raise the volume by one step (unmutes)
+    pub fn volume_up(&mut self) {
+        self.volume = (self.volume + VOLUME_STEP).min(1.0);
+        self.muted = false;
+    }
+
+    /// Lower the volume by one step (unmutes)
+    pub fn volume_down(&mut self) {
+        self.volume = (self.volume - VOLUME_STEP).max(0.0);
+        self.muted = false;
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    /// Volume to apply to the output, taking mute into account
+    pub fn output_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume
+        }
+    }
+
+    /// Preferences to save from these settings
+    pub fn preferences(&self) -> Preferences {
+        Preferences {
+            volume: self.volume,
+            muted: self.muted,
+        }
+    }
+
+    /// Choose the audio device after the one playing (refreshes device list first)
     pub fn next_audio_device(&mut self) -> bool {
+        if !self.output.is_device() {
+            return false;
+        }
+
         // Refresh device list in case devices changed
         self.audio_devices = list_audio_devices();
-
-        if self.audio_devices.is_empty() {
+        if self.audio_devices.len() < 2 {
             return false;
         }
 
-        // Ensure current index is valid
-        if self.audio_device_index >= self.audio_devices.len() {
-            self.audio_device_index = 0;
-        }
+        let current = self
+            .audio_devices
+            .iter()
+            .position(|device| device.name == self.active_audio_device)
+            .unwrap_or(0);
+        let next = &self.audio_devices[(current + 1) % self.audio_devices.len()];
+        self.audio_device = Some(next.name.clone());
+        true
+    }
 
-        let new_index = (self.audio_device_index + 1) % self.audio_devices.len();
-        if new_index != self.audio_device_index {
-            self.audio_device_index = new_index;
-            true
-        } else {
-            false
-        }
+    /// Device that should be playing: the chosen one while it is plugged in, else the default
+    pub fn preferred_audio_device(&self, default: Option<String>) -> Option<String> {
+        self.audio_device
+            .as_deref()
+            .and_then(|name| find_device(&self.audio_devices, name))
+            .map(|device| device.name.clone())
+            .or(default)
     }
 
     /// Get the current audio device name
     pub fn current_audio_device_name(&self) -> &str {
-        self.audio_devices
-            .get(self.audio_device_index)
-            .map(|d| d.name.as_str())
-            .unwrap_or("Default")
+        if self.active_audio_device.is_empty() {
+            "Default"
+        } else {
+            &self.active_audio_device
+        }
+    }
+
+    /// Where the audio goes, for display
+    pub fn output_name(&self) -> String {
+        match &self.output {
+            OutputBackend::Null => "None (null output)".to_string(),
+            OutputBackend::File(path) => format!("File {}", path.display()),
+            OutputBackend::Device | OutputBackend::NamedDevice(_) => {
+                let name = self.current_audio_device_name();
+                match &self.audio_device {
+                    // The chosen device is unplugged
+                    Some(wanted) if find_device(&self.audio_devices, wanted).is_none() => {
+                        format!("{} (waiting for {})", name, wanted)
+                    }
+                    _ => name.to_string(),
+                }
+            }
+        }
     }
 }
 
@@ -256,11 +589,13 @@ pub struct AppState {
     pub station_history: RwLock<Vec<StationInfo>>,
     /// Shutdown flag
     pub should_quit: Arc<AtomicBool>,
+    /// When playback was paused (None while playing)
+    paused_since: parking_lot::Mutex<Option<Instant>>,
 }
 
 impl AppState {
-    pub fn new(args: &Args) -> Arc<Self> {
-        let settings = Settings::from_args(args);
+    pub fn new(args: &Args, prefs: Preferences) -> Arc<Self> {
+        let settings = Settings::from_args(args, prefs);
         let (playback_tx, playback_rx) = watch::channel(PlaybackState::Idle);
 
         Arc::new(Self {
@@ -269,6 +604,7 @@ impl AppState {
             playback_rx,
             station_history: RwLock::new(Vec::new()),
             should_quit: Arc::new(AtomicBool::new(false)),
+            paused_since: parking_lot::Mutex::new(None),
         })
     }
 
@@ -300,6 +636,26 @@ impl AppState {
         self.should_quit.load(Ordering::SeqCst)
     }
 
+    /// Pause or resume playback, returning whether it is now paused
+    pub fn toggle_pause(&self) -> bool {
+        let mut paused_since = self.paused_since.lock();
+        *paused_since = match *paused_since {
+            Some(_) => None,
+            None => Some(Instant::now()),
+        };
+        paused_since.is_some()
+    }
+
+    /// Check if playback is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused_since.lock().is_some()
+    }
+
+    /// How long playback has been paused (None while playing)
+    pub fn paused_for(&self) -> Option<Duration> {
+        self.paused_since.lock().map(|since| since.elapsed())
+    }
+
     /// Add station to history
     #[allow(dead_code)]
     pub async fn add_to_history(&self, station: StationInfo) {
@@ -0,0 +1,341 @@
+use tracing::{debug, instrument};
+
+use super::onset::OnsetEnvelope;
+
+/// Meters considered by automatic meter detection (beats per bar)
+const METER_CANDIDATES: [u8; 3] = [3, 4, 6];
+
+/// Meter assumed when no candidate shows a clear accent pattern
+const DEFAULT_METER: u8 = 4;
+
+/// Minimum accent contrast (in standard deviations) for a detected meter
+const MIN_METER_CONTRAST: f32 = 0.3;
+
+/// Margin a meter must beat a meter it contains by (6 over 3)
+const COMPOUND_METER_MARGIN: f32 = 1.15;
+
+/// Beat grid produced by the beat tracker
+#[derive(Debug, Clone, Default)]
+pub struct BeatGrid {
+    /// Beat positions in frames
+    pub beats: Vec<usize>,
+    /// Index into `beats` of the first downbeat
+    pub downbeat_phase: usize,
+    /// Beats per bar used for downbeat estimation
+    pub beats_per_bar: u8,
+}
+
+impl BeatGrid {
+    /// Frame positions of every downbeat in the grid
+    pub fn downbeats(&self) -> impl Iterator<Item = usize> + '_ {
+        self.beats
+            .iter()
+            .skip(self.downbeat_phase)
+            .step_by(self.beats_per_bar.max(1) as usize)
+            .copied()
+    }
+}
+
+/// Beat tracker using dynamic programming over an onset-strength envelope
+///
+/// Follows the approach of Ellis (2007): every envelope frame is scored by its
+/// onset strength plus the best score of a predecessor roughly one beat
+/// earlier, penalised by how far that spacing strays from the target period.
+#[derive(Clone)]
+pub struct BeatTracker {
+    /// Weight of the tempo-consistency penalty (higher = stricter grid)
+    tightness: f32,
+}
+
+impl BeatTracker {
+    pub fn new() -> Self {
+        Self { tightness: 100.0 }
+    }
+
+    /// Track beats in an onset envelope, returning a grid in audio frames
+    #[instrument(skip(self, onset_envelope))]
+    pub fn track(&self, onset_envelope: &OnsetEnvelope, bpm: f32, beats_per_bar: u8) -> BeatGrid {
+        let envelope = &onset_envelope.values;
+        let period = 60.0 / bpm * onset_envelope.rate;
+
+        if envelope.len() < 2 || period < 1.0 {
+            return BeatGrid {
+                beats: Vec::new(),
+                downbeat_phase: 0,
+                beats_per_bar,
+            };
+        }
+
+        let beat_indices = self.dynamic_programming(envelope, period);
+        let downbeat_phase = self.estimate_downbeat_phase(envelope, &beat_indices, beats_per_bar);
+
+        debug!(
+            beat_count = beat_indices.len(),
+            downbeat_phase,
+            "Beat tracking complete"
+        );
+
+        BeatGrid {
+            beats: beat_indices.iter().map(|&i| i * onset_envelope.hop_size).collect(),
+            downbeat_phase,
+            beats_per_bar,
+        }
+    }
+
+    /// Track beats and estimate the meter from accent patterns
+    ///
+    /// Each candidate meter is scored by how strongly its best downbeat phase
+    /// stands out from the remaining beats. Falls back to 4/4 when no
+    /// candidate shows a clear pattern.
+    #[instrument(skip(self, onset_envelope))]
+    pub fn track_with_meter_detection(&self, onset_envelope: &OnsetEnvelope, bpm: f32) -> BeatGrid {
+        let mut grid = self.track(onset_envelope, bpm, DEFAULT_METER);
+        let envelope = &onset_envelope.values;
+        let beat_indices: Vec<usize> = grid
+            .beats
+            .iter()
+            .map(|&frame| frame / onset_envelope.hop_size.max(1))
+            .collect();
+
+        let beats_per_bar = self.estimate_meter(envelope, &beat_indices);
+        if beats_per_bar != grid.beats_per_bar {
+            grid.downbeat_phase = self.estimate_downbeat_phase(envelope, &beat_indices, beats_per_bar);
+            grid.beats_per_bar = beats_per_bar;
+        }
+
+        debug!(beats_per_bar, downbeat_phase = grid.downbeat_phase, "Meter detected");
+
+        grid
+    }
+
+    /// Pick the candidate meter with the clearest downbeat accents
+    fn estimate_meter(&self, envelope: &[f32], beats: &[usize]) -> u8 {
+        let accents: Vec<f32> = beats.iter().map(|&b| self.accent(envelope, b)).collect();
+
+        let scores: Vec<(u8, f32)> = METER_CANDIDATES
+            .iter()
+            .filter(|&&meter| accents.len() >= meter as usize * 2)
+            .map(|&meter| (meter, self.meter_contrast(&accents, meter)))
+            .collect();
+
+        let score_of = |meter: u8| scores.iter().find(|(m, _)| *m == meter).map(|(_, s)| *s);
+
+        let best = scores
+            .iter()
+            .copied()
+            .filter(|&(meter, score)| {
+                // A compound meter only wins if it explains more than the meter it contains
+                METER_CANDIDATES
+                    .iter()
+                    .filter(|&&other| other < meter && meter % other == 0)
+                    .all(|&other| score_of(other).is_none_or(|s| score > s * COMPOUND_METER_MARGIN))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        debug!(?scores, "Meter candidate scores");
+
+        match best {
+            Some((meter, score)) if score >= MIN_METER_CONTRAST => meter,
+            _ => DEFAULT_METER,
+        }
+    }
+
+    /// How far the strongest phase of a meter stands out (in standard deviations)
+    fn meter_contrast(&self, accents: &[f32], meter: u8) -> f32 {
+        let bar = meter as usize;
+        let mean = accents.iter().sum::<f32>() / accents.len().max(1) as f32;
+        let std_dev = (accents.iter().map(|a| (a - mean).powi(2)).sum::<f32>()
+            / accents.len().max(1) as f32)
+            .sqrt();
+        if std_dev < 1e-6 {
+            return 0.0;
+        }
+
+        (0..bar)
+            .map(|phase| {
+                let (mut on, mut on_count, mut off, mut off_count) = (0.0f32, 0, 0.0f32, 0);
+                for (i, &accent) in accents.iter().enumerate() {
+                    if i % bar == phase {
+                        on += accent;
+                        on_count += 1;
+                    } else {
+                        off += accent;
+                        off_count += 1;
+                    }
+                }
+                (on / on_count.max(1) as f32 - off / off_count.max(1) as f32) / std_dev
+            })
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    /// Find the best-scoring beat sequence (indices into the envelope)
+    fn dynamic_programming(&self, envelope: &[f32], period: f32) -> Vec<usize> {
+        let n = envelope.len();
+        let mut score = vec![0.0f32; n];
+        let mut backlink: Vec<Option<usize>> = vec![None; n];
+
+        // Predecessors are searched between half and twice the beat period
+        let min_offset = (period / 2.0).round().max(1.0) as usize;
+        let max_offset = (period * 2.0).round() as usize;
+
+        for t in 0..n {
+            let mut best_prev = None;
+            let mut best_score = f32::NEG_INFINITY;
+
+            if t >= min_offset {
+                let earliest = t.saturating_sub(max_offset);
+                for (p, &prev_score) in score.iter().enumerate().take(t - min_offset + 1).skip(earliest) {
+                    let ratio = (t - p) as f32 / period;
+                    let penalty = self.tightness * ratio.ln().powi(2);
+                    let candidate = prev_score - penalty;
+                    if candidate > best_score {
+                        best_score = candidate;
+                        best_prev = Some(p);
+                    }
+                }
+            }
+
+            if best_prev.is_some() && best_score > 0.0 {
+                score[t] = envelope[t] + best_score;
+                backlink[t] = best_prev;
+            } else {
+                score[t] = envelope[t];
+            }
+        }
+
+        // Start from the best score within the final beat period
+        let tail_start = n.saturating_sub(period.round() as usize + 1);
+        let mut current = (tail_start..n)
+            .max_by(|&a, &b| score[a].total_cmp(&score[b]))
+            .unwrap_or(n - 1);
+
+        let mut beats = vec![current];
+        while let Some(prev) = backlink[current] {
+            beats.push(prev);
+            current = prev;
+        }
+        beats.reverse();
+
+        beats
+    }
+
+    /// Pick which beat (modulo the bar length) carries the strongest accents
+    fn estimate_downbeat_phase(&self, envelope: &[f32], beats: &[usize], beats_per_bar: u8) -> usize {
+        let bar = beats_per_bar.max(1) as usize;
+        if beats.len() < bar * 2 {
+            return 0;
+        }
+
+        let accents: Vec<f32> = beats.iter().map(|&b| self.accent(envelope, b)).collect();
+
+        (0..bar)
+            .map(|phase| {
+                let values: Vec<f32> = accents.iter().skip(phase).step_by(bar).copied().collect();
+                let mean = values.iter().sum::<f32>() / values.len().max(1) as f32;
+                (phase, mean)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(phase, _)| phase)
+            .unwrap_or(0)
+    }
+
+    /// Onset strength in a small window around a beat
+    fn accent(&self, envelope: &[f32], index: usize) -> f32 {
+        let start = index.saturating_sub(2);
+        let end = (index + 3).min(envelope.len());
+        envelope[start..end].iter().fold(0.0f32, |a, &b| a.max(b))
+    }
+}
+
+impl Default for BeatTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Impulse envelope at a fixed beat interval, with accented downbeats
+    fn click_envelope(len: usize, interval: usize, bar: usize, first_downbeat: usize) -> OnsetEnvelope {
+        let mut envelope = vec![0.0f32; len];
+        let mut beat = 0;
+        let mut pos = first_downbeat % interval;
+        let downbeat_offset = first_downbeat / interval;
+        while pos < len {
+            envelope[pos] = if beat % bar == downbeat_offset % bar { 1.0 } else { 0.4 };
+            pos += interval;
+            beat += 1;
+        }
+        OnsetEnvelope {
+            values: envelope,
+            rate: 100.0,
+            hop_size: 1,
+        }
+    }
+
+    #[test]
+    fn test_tracks_regular_beats() {
+        let tracker = BeatTracker::new();
+        // 100 envelope values per second, beats every 50 (120 BPM)
+        let envelope = click_envelope(800, 50, 4, 0);
+
+        let grid = tracker.track(&envelope, 120.0, 4);
+
+        assert!(grid.beats.len() >= 14, "Expected ~16 beats, got {}", grid.beats.len());
+        for pair in grid.beats.windows(2) {
+            let spacing = pair[1] - pair[0];
+            assert!((48..=52).contains(&spacing), "Irregular beat spacing {}", spacing);
+        }
+    }
+
+    #[test]
+    fn test_downbeat_phase() {
+        let tracker = BeatTracker::new();
+        // Accented beat is the third click (index 2)
+        let envelope = click_envelope(1000, 50, 4, 100);
+
+        let grid = tracker.track(&envelope, 120.0, 4);
+        let first_downbeat = grid.downbeats().next().expect("No downbeats");
+
+        assert_eq!(first_downbeat % 200, 100, "Downbeat at {}", first_downbeat);
+    }
+
+    #[test]
+    fn test_detects_meter() {
+        let tracker = BeatTracker::new();
+
+        for bar in [3, 4] {
+            let envelope = click_envelope(1500, 50, bar, 0);
+            let grid = tracker.track_with_meter_detection(&envelope, 120.0);
+            assert_eq!(grid.beats_per_bar as usize, bar, "Wrong meter for {}-beat bars", bar);
+        }
+    }
+
+    #[test]
+    fn test_compound_meter() {
+        let tracker = BeatTracker::new();
+
+        // Strong accent on beat 1, medium on beat 4 of each six-beat bar
+        let mut envelope = click_envelope(1800, 50, 6, 0);
+        for (i, value) in envelope.values.iter_mut().enumerate() {
+            if i % 300 == 150 {
+                *value = 0.7;
+            }
+        }
+
+        let grid = tracker.track_with_meter_detection(&envelope, 120.0);
+        assert_eq!(grid.beats_per_bar, 6);
+        assert_eq!(grid.downbeats().next().map(|b| b % 300), Some(0));
+    }
+
+    #[test]
+    fn test_flat_accents_default_to_four() {
+        let tracker = BeatTracker::new();
+        let envelope = click_envelope(1500, 50, 1, 0);
+
+        let grid = tracker.track_with_meter_detection(&envelope, 120.0);
+        assert_eq!(grid.beats_per_bar, 4);
+    }
+}
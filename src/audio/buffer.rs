@@ -1,4 +1,6 @@
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use crate::app::LoopInfo;
 
@@ -8,6 +10,9 @@ pub const SAMPLE_RATE: u32 = 48_000;
 /// Number of audio channels (stereo)
 pub const CHANNELS: u16 = 2;
 
+/// Source of loop buffer IDs (0 is reserved for "no clip")
+static NEXT_CLIP_ID: AtomicU64 = AtomicU64::new(1);
+
 /// Raw audio buffer before quantization
 #[derive(Debug, Clone)]
 pub struct RawAudioBuffer {
@@ -17,6 +22,8 @@ pub struct RawAudioBuffer {
     pub sample_rate: u32,
     /// Number of channels
     pub channels: u16,
+    /// When the audio was captured (decoded)
+    pub captured_at: SystemTime,
 }
 
 impl RawAudioBuffer {
@@ -25,6 +32,7 @@ impl RawAudioBuffer {
             samples,
             sample_rate,
             channels,
+            captured_at: SystemTime::now(),
         }
     }
 
@@ -55,17 +63,43 @@ impl RawAudioBuffer {
 /// Immutable loop buffer for playback
 #[derive(Debug, Clone)]
 pub struct LoopBuffer {
+    /// Unique clip ID, shared by clones of the same buffer
+    pub id: u64,
     /// Interleaved stereo samples (f32, -1.0 to 1.0)
     pub samples: Arc<[f32]>,
     /// Metadata about the loop
     pub loop_info: LoopInfo,
+    /// Decoded source audio the loop was cut from (kept for re-quantization)
+    pub source: Option<Arc<RawAudioBuffer>>,
 }
 
 impl LoopBuffer {
     pub fn new(samples: Vec<f32>, loop_info: LoopInfo) -> Self {
         Self {
+            id: NEXT_CLIP_ID.fetch_add(1, Ordering::Relaxed),
             samples: samples.into(),
             loop_info,
+            source: None,
+        }
+    }
+
+    /// Attach the decoded source audio
+    pub fn with_source(mut self, source: Arc<RawAudioBuffer>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Release the decoded source audio (the loop can no longer be re-quantized)
+    pub fn without_source(mut self) -> Self {
+        self.source = None;
+        self
+    }
+
+    /// Check whether two loops were cut from the same captured audio
+    pub fn same_source(&self, other: &LoopBuffer) -> bool {
+        match (&self.source, &other.source) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
         }
     }
 
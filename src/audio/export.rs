@@ -0,0 +1,365 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::{info, instrument};
+
+use crate::app::StationInfo;
+use crate::error::AudioError;
+
+use super::buffer::{LoopBuffer, CHANNELS};
+
+/// Sample format of exported WAV files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WavFormat {
+    /// 24-bit integer PCM
+    #[default]
+    Pcm24,
+    /// 32-bit IEEE float (lossless for the internal samples)
+    Float32,
+}
+
+impl WavFormat {
+    fn bytes_per_sample(self) -> u16 {
+        match self {
+            WavFormat::Pcm24 => 3,
+            WavFormat::Float32 => 4,
+        }
+    }
+}
+
+impl std::str::FromStr for WavFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "24" | "pcm24" | "24bit" => Ok(WavFormat::Pcm24),
+            "float" | "f32" | "float32" => Ok(WavFormat::Float32),
+            _ => Err(format!("invalid WAV format '{}' (expected 24 or float)", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for WavFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WavFormat::Pcm24 => write!(f, "24-bit PCM"),
+            WavFormat::Float32 => write!(f, "32-bit float"),
+        }
+    }
+}
+
+/// Sidecar metadata written next to an exported loop
+#[derive(Debug, Serialize)]
+struct ExportSidecar<'a> {
+    station: &'a str,
+    place: &'a str,
+    country: &'a str,
+    latitude: f64,
+    longitude: f64,
+    website: Option<&'a str>,
+    bpm: f32,
+    source_bpm: f32,
+    bars: u8,
+    beats_per_bar: u8,
+    key: Option<String>,
+    loudness_lufs: Option<f32>,
+    /// UTC capture time (ISO 8601)
+    captured_at: String,
+    format: String,
+}
+
+/// Write a loop to `dir` as a tagged WAV plus a JSON sidecar
+///
+/// The WAV carries an `acid` chunk (tempo, beat count and root note), a
+/// `cue ` marker with a label on every bar and a `LIST`/`INFO` chunk, so
+/// DAWs import it as a loop at the right tempo. Returns the WAV path.
+#[instrument(skip(buffer, station), fields(station = %station.name))]
+pub fn export_loop(
+    buffer: &LoopBuffer,
+    station: &StationInfo,
+    dir: &Path,
+    format: WavFormat,
+) -> Result<PathBuf, AudioError> {
+    let captured_at = buffer
+        .source
+        .as_ref()
+        .map_or_else(SystemTime::now, |source| source.captured_at);
+    let timestamp = utc_timestamp(captured_at);
+    let info = &buffer.loop_info;
+
+    std::fs::create_dir_all(dir)?;
+    let stem = format!(
+        "{}-{}-{:.0}bpm",
+        file_stamp(captured_at),
+        file_name_part(&station.name),
+        info.bpm
+    );
+    let wav_path = dir.join(format!("{}.wav", stem));
+
+    let comment = format!(
+        "{}, {} ({:.4}, {:.4}) - source {:.1} BPM",
+        station.place_name, station.country, station.latitude, station.longitude, info.source_bpm
+    );
+    let tags = [
+        (*b"INAM", station.name.as_str()),
+        (*b"ICMT", comment.as_str()),
+        (*b"ICRD", timestamp.as_str()),
+        (*b"ISFT", concat!("tappr ", env!("CARGO_PKG_VERSION"))),
+    ];
+    std::fs::write(&wav_path, encode_wav(buffer, format, &tags))?;
+
+    let sidecar = ExportSidecar {
+        station: &station.name,
+        place: &station.place_name,
+        country: &station.country,
+        latitude: station.latitude,
+        longitude: station.longitude,
+        website: station.website.as_deref(),
+        bpm: info.bpm,
+        source_bpm: info.source_bpm,
+        bars: info.bars,
+        beats_per_bar: info.beats_per_bar,
+        key: info.key.map(|key| key.name()),
+        loudness_lufs: info.loudness_lufs,
+        captured_at: timestamp.clone(),
+        format: format.to_string(),
+    };
+    let json = serde_json::to_string_pretty(&sidecar)
+        .map_err(|e| AudioError::Io(std::io::Error::other(e)))?;
+    std::fs::write(wav_path.with_extension("json"), json)?;
+
+    info!(path = ?wav_path, "Exported loop");
+    Ok(wav_path)
+}
+
+/// Encode a loop as a RIFF/WAVE file with loop metadata
+fn encode_wav(buffer: &LoopBuffer, format: WavFormat, tags: &[([u8; 4], &str)]) -> Vec<u8> {
+    let info = &buffer.loop_info;
+    let frames = buffer.frame_count();
+    let bytes_per_sample = format.bytes_per_sample();
+    let block_align = CHANNELS * bytes_per_sample;
+
+    let mut wave = Vec::new();
+
+    // fmt: PCM is 16 bytes, float adds an empty extension and needs a fact chunk
+    let mut fmt = Vec::with_capacity(18);
+    let format_tag: u16 = match format {
+        WavFormat::Pcm24 => 1,
+        WavFormat::Float32 => 3,
+    };
+    fmt.extend_from_slice(&format_tag.to_le_bytes());
+    fmt.extend_from_slice(&CHANNELS.to_le_bytes());
+    fmt.extend_from_slice(&info.sample_rate.to_le_bytes());
+    fmt.extend_from_slice(&(info.sample_rate * block_align as u32).to_le_bytes());
+    fmt.extend_from_slice(&block_align.to_le_bytes());
+    fmt.extend_from_slice(&(bytes_per_sample * 8).to_le_bytes());
+    if format == WavFormat::Float32 {
+        fmt.extend_from_slice(&0u16.to_le_bytes());
+    }
+    push_chunk(&mut wave, b"fmt ", &fmt);
+    if format == WavFormat::Float32 {
+        push_chunk(&mut wave, b"fact", &(frames as u32).to_le_bytes());
+    }
+
+    // acid: loop (not one-shot) that may be stretched, root note from the key
+    let beats = info.bars as u32 * info.beats_per_bar as u32;
+    let mut acid = Vec::with_capacity(24);
+    let (flags, root_note) = match info.key {
+        Some(key) => (0x02 | 0x04, 60 + key.tonic as u16),
+        None => (0x04, 60),
+    };
+    acid.extend_from_slice(&(flags as u32).to_le_bytes());
+    acid.extend_from_slice(&root_note.to_le_bytes());
+    acid.extend_from_slice(&0x8000u16.to_le_bytes());
+    acid.extend_from_slice(&0f32.to_le_bytes());
+    acid.extend_from_slice(&beats.to_le_bytes());
+    acid.extend_from_slice(&4u16.to_le_bytes());
+    acid.extend_from_slice(&(info.beats_per_bar as u16).to_le_bytes());
+    acid.extend_from_slice(&info.bpm.to_le_bytes());
+    push_chunk(&mut wave, b"acid", &acid);
+
+    // cue: one marker per bar, labelled in an associated data list
+    let bars = info.bars.max(1) as usize;
+    let mut cue = Vec::with_capacity(4 + bars * 24);
+    let mut labels = b"adtl".to_vec();
+    cue.extend_from_slice(&(bars as u32).to_le_bytes());
+    for bar in 0..bars {
+        let id = bar as u32 + 1;
+        let position = (bar as f64 * frames as f64 / bars as f64).round() as u32;
+        cue.extend_from_slice(&id.to_le_bytes());
+        cue.extend_from_slice(&position.to_le_bytes());
+        cue.extend_from_slice(b"data");
+        cue.extend_from_slice(&0u32.to_le_bytes());
+        cue.extend_from_slice(&0u32.to_le_bytes());
+        cue.extend_from_slice(&position.to_le_bytes());
+
+        let mut label = id.to_le_bytes().to_vec();
+        label.extend_from_slice(format!("Bar {}\0", id).as_bytes());
+        push_chunk(&mut labels, b"labl", &label);
+    }
+    push_chunk(&mut wave, b"cue ", &cue);
+    push_chunk(&mut wave, b"LIST", &labels);
+
+    let mut info_list = b"INFO".to_vec();
+    for (id, text) in tags {
+        let mut value = text.as_bytes().to_vec();
+        value.push(0);
+        push_chunk(&mut info_list, id, &value);
+    }
+    push_chunk(&mut wave, b"LIST", &info_list);
+
+    let mut data = Vec::with_capacity(buffer.samples.len() * bytes_per_sample as usize);
+    for &sample in buffer.samples.iter() {
+        match format {
+            WavFormat::Pcm24 => {
+                let value = (sample.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32;
+                data.extend_from_slice(&value.to_le_bytes()[..3]);
+            }
+            WavFormat::Float32 => data.extend_from_slice(&sample.to_le_bytes()),
+        }
+    }
+    push_chunk(&mut wave, b"data", &data);
+
+    let mut riff = Vec::with_capacity(wave.len() + 12);
+    riff.extend_from_slice(b"RIFF");
+    riff.extend_from_slice(&(wave.len() as u32 + 4).to_le_bytes());
+    riff.extend_from_slice(b"WAVE");
+    riff.extend_from_slice(&wave);
+    riff
+}
+
+/// Append a chunk, padded to an even length as RIFF requires
+fn push_chunk(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Station name reduced to something safe in a file name
+fn file_name_part(name: &str) -> String {
+    let part: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let part = part
+        .split('-')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if part.is_empty() {
+        "station".to_string()
+    } else {
+        part
+    }
+}
+
+/// UTC time for file names, e.g. "20240501-123000"
+pub fn file_stamp(time: SystemTime) -> String {
+    utc_timestamp(time).replace([':', '-'], "").replace('T', "-").replace('Z', "")
+}
+
+/// UTC time as ISO 8601, e.g. "2024-05-01T12:30:00Z"
+fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch (proleptic Gregorian calendar)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{LoopInfo, Mode, MusicalKey};
+    use std::time::Duration;
+
+    fn loop_buffer() -> LoopBuffer {
+        let frames = 96_000;
+        LoopBuffer::new(
+            vec![0.5; frames * CHANNELS as usize],
+            LoopInfo {
+                source_bpm: 118.0,
+                time_stretched: true,
+                key: Some(MusicalKey::new(9, Mode::Minor)),
+                ..LoopInfo::for_test(120.0, frames)
+            },
+        )
+    }
+
+    /// Find a top-level chunk body
+    fn chunk<'a>(wav: &'a [u8], id: &[u8; 4]) -> Option<&'a [u8]> {
+        let mut offset = 12;
+        while offset + 8 <= wav.len() {
+            let size = u32::from_le_bytes(wav[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            let body = &wav[offset + 8..offset + 8 + size];
+            if &wav[offset..offset + 4] == id {
+                return Some(body);
+            }
+            offset += 8 + size + size % 2;
+        }
+        None
+    }
+
+    #[test]
+    fn test_wav_layout() {
+        let buffer = loop_buffer();
+        let wav = encode_wav(&buffer, WavFormat::Pcm24, &[(*b"INAM", "Radio")]);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize, wav.len() - 8);
+
+        let fmt = chunk(&wav, b"fmt ").unwrap();
+        assert_eq!(u16::from_le_bytes([fmt[14], fmt[15]]), 24);
+        assert_eq!(chunk(&wav, b"data").unwrap().len(), 96_000 * 2 * 3);
+
+        let acid = chunk(&wav, b"acid").unwrap();
+        assert_eq!(u32::from_le_bytes(acid[12..16].try_into().unwrap()), 4);
+        assert_eq!(f32::from_le_bytes(acid[20..24].try_into().unwrap()), 120.0);
+        assert_eq!(u16::from_le_bytes([acid[4], acid[5]]), 69);
+
+        let cue = chunk(&wav, b"cue ").unwrap();
+        assert_eq!(u32::from_le_bytes(cue[..4].try_into().unwrap()), 1);
+    }
+
+    #[test]
+    fn test_float_has_fact_chunk() {
+        let wav = encode_wav(&loop_buffer(), WavFormat::Float32, &[]);
+        assert_eq!(chunk(&wav, b"fact").unwrap(), 96_000u32.to_le_bytes());
+        let data = chunk(&wav, b"data").unwrap();
+        assert_eq!(f32::from_le_bytes(data[..4].try_into().unwrap()), 0.5);
+    }
+
+    #[test]
+    fn test_names_and_timestamps() {
+        assert_eq!(file_name_part("Radio Nova 98.6 FM!"), "radio-nova-98-6-fm");
+        assert_eq!(file_name_part("???"), "station");
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            utc_timestamp(UNIX_EPOCH + Duration::from_secs(1_709_210_096)),
+            "2024-02-29T12:34:56Z"
+        );
+        assert_eq!(file_stamp(UNIX_EPOCH + Duration::from_secs(1_709_210_096)), "20240229-123456");
+        assert_eq!("float".parse::<WavFormat>(), Ok(WavFormat::Float32));
+    }
+}
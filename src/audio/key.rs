@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use tracing::{debug, instrument};
+
+use crate::app::{Mode, MusicalKey};
+
+use super::spectrum::Stft;
+
+/// STFT frame size for chroma analysis (~85ms at 48kHz, ~12Hz bins)
+const FRAME_SIZE: usize = 4096;
+
+/// Lowest and highest frequencies folded into the chroma vector
+const MIN_FREQUENCY: f32 = 100.0;
+const MAX_FREQUENCY: f32 = 4000.0;
+
+/// Minimum profile correlation for a key to be reported
+const MIN_CORRELATION: f32 = 0.5;
+
+/// Krumhansl-Kessler major key profile (starting at the tonic)
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+
+/// Krumhansl-Kessler minor key profile (starting at the tonic)
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Target key shared by all pipelines in lock-to-first mode
+///
+/// Empty until the first clip with a detected key claims it.
+pub type KeyLock = Arc<Mutex<Option<MusicalKey>>>;
+
+/// Chroma-based key detector
+///
+/// Folds spectral energy into twelve pitch classes and correlates the result
+/// with the major and minor key profiles for every tonic.
+#[derive(Clone)]
+pub struct KeyDetector {
+    stft: Stft,
+    sample_rate: u32,
+}
+
+impl KeyDetector {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            stft: Stft::new(FRAME_SIZE, FRAME_SIZE / 2),
+            sample_rate,
+        }
+    }
+
+    /// Estimate the key of a mono signal
+    ///
+    /// Returns `None` when no key profile fits well (drums, noise, speech).
+    #[instrument(skip(self, mono), fields(samples = mono.len()))]
+    pub fn detect(&self, mono: &[f32]) -> Option<MusicalKey> {
+        let chroma = self.chroma(mono)?;
+
+        let (key, correlation) = (0..12u8)
+            .flat_map(|tonic| {
+                [
+                    (MusicalKey::new(tonic, Mode::Major), correlate(&chroma, &MAJOR_PROFILE, tonic)),
+                    (MusicalKey::new(tonic, Mode::Minor), correlate(&chroma, &MINOR_PROFILE, tonic)),
+                ]
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        debug!(key = %key, correlation, "Key estimate");
+
+        (correlation >= MIN_CORRELATION).then_some(key)
+    }
+
+    /// Average energy per pitch class (index 0 = C)
+    fn chroma(&self, mono: &[f32]) -> Option<[f32; 12]> {
+        let mut chroma = [0.0f32; 12];
+
+        for spectrum in self.stft.magnitudes(mono) {
+            for (bin, &magnitude) in spectrum.iter().enumerate() {
+                let frequency = self.stft.bin_frequency(bin, self.sample_rate);
+                if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency) {
+                    continue;
+                }
+                // MIDI note 69 is A4 (440Hz); pitch class 0 is C
+                let note = (69.0 + 12.0 * (frequency / 440.0).log2()).round() as i32;
+                chroma[note.rem_euclid(12) as usize] += magnitude * magnitude;
+            }
+        }
+
+        let total: f32 = chroma.iter().sum();
+        if total < 1e-6 {
+            return None;
+        }
+        for value in &mut chroma {
+            *value /= total;
+        }
+
+        Some(chroma)
+    }
+}
+
+/// Pearson correlation between a chroma vector and a key profile rotated to `tonic`
+fn correlate(chroma: &[f32; 12], profile: &[f32; 12], tonic: u8) -> f32 {
+    let rotated: Vec<f32> = (0..12)
+        .map(|pitch_class| profile[(pitch_class + 12 - tonic as usize) % 12])
+        .collect();
+
+    let chroma_mean = chroma.iter().sum::<f32>() / 12.0;
+    let profile_mean = rotated.iter().sum::<f32>() / 12.0;
+
+    let mut covariance = 0.0;
+    let mut chroma_var = 0.0;
+    let mut profile_var = 0.0;
+    for (c, p) in chroma.iter().zip(&rotated) {
+        covariance += (c - chroma_mean) * (p - profile_mean);
+        chroma_var += (c - chroma_mean).powi(2);
+        profile_var += (p - profile_mean).powi(2);
+    }
+
+    let denominator = (chroma_var * profile_var).sqrt();
+    if denominator < 1e-12 {
+        0.0
+    } else {
+        covariance / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sum of sine tones at MIDI notes, weighted, for two seconds
+    fn chord(notes: &[(i32, f32)]) -> Vec<f32> {
+        (0..48000 * 2)
+            .map(|i| {
+                let t = i as f64 / 48000.0;
+                notes
+                    .iter()
+                    .map(|&(note, weight)| {
+                        let frequency = 440.0 * 2f64.powf((note - 69) as f64 / 12.0);
+                        (2.0 * std::f64::consts::PI * frequency * t).sin() as f32 * weight
+                    })
+                    .sum::<f32>()
+                    * 0.1
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detects_major_key() {
+        let detector = KeyDetector::new(48000);
+        // C major triad over the rest of the C major scale
+        let mono = chord(&[
+            (60, 1.0), (64, 0.8), (67, 0.9),
+            (62, 0.4), (65, 0.4), (69, 0.4), (71, 0.3),
+        ]);
+
+        assert_eq!(detector.detect(&mono), Some(MusicalKey::new(0, Mode::Major)));
+    }
+
+    #[test]
+    fn test_detects_minor_key() {
+        let detector = KeyDetector::new(48000);
+        // A minor triad over the rest of the A natural minor scale
+        let mono = chord(&[
+            (57, 1.0), (60, 0.8), (64, 0.9),
+            (59, 0.4), (62, 0.4), (65, 0.4), (67, 0.3),
+        ]);
+
+        assert_eq!(detector.detect(&mono), Some(MusicalKey::new(9, Mode::Minor)));
+    }
+
+    #[test]
+    fn test_silence_has_no_key() {
+        let detector = KeyDetector::new(48000);
+        assert_eq!(detector.detect(&vec![0.0; 48000]), None);
+    }
+
+    #[test]
+    fn test_camelot_compatibility() {
+        let a_minor = MusicalKey::new(9, Mode::Minor);
+        let c_major = MusicalKey::new(0, Mode::Major);
+        let g_major = MusicalKey::new(7, Mode::Major);
+        let f_sharp_major = MusicalKey::new(6, Mode::Major);
+
+        assert_eq!(a_minor.camelot(), (8, 'A'));
+        assert_eq!(c_major.camelot(), (8, 'B'));
+        assert_eq!(g_major.camelot(), (9, 'B'));
+        assert!(c_major.compatibility(&a_minor) > c_major.compatibility(&g_major));
+        assert!(c_major.compatibility(&g_major) > c_major.compatibility(&f_sharp_major));
+        assert_eq!(c_major.compatibility(&f_sharp_major), 0.0);
+    }
+
+    #[test]
+    fn test_transpose_to_target() {
+        let c_major: MusicalKey = "C".parse().unwrap();
+        let d_minor: MusicalKey = "Dm".parse().unwrap();
+        let f_sharp_major: MusicalKey = "F#".parse().unwrap();
+
+        assert_eq!(d_minor, MusicalKey::new(2, Mode::Minor));
+        assert_eq!("Bbmaj".parse::<MusicalKey>(), Ok(MusicalKey::new(10, Mode::Major)));
+        assert!("H".parse::<MusicalKey>().is_err());
+
+        // D minor is the relative minor of F major: down five semitones to A minor
+        assert_eq!(d_minor.semitones_to(&c_major), -5);
+        assert_eq!(d_minor.transposed(-5), MusicalKey::new(9, Mode::Minor));
+        assert_eq!(f_sharp_major.semitones_to(&c_major), 6);
+        assert_eq!(c_major.semitones_to(&c_major), 0);
+    }
+}
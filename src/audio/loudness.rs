@@ -0,0 +1,292 @@
+use std::f32::consts::PI;
+
+use tracing::{debug, instrument};
+
+use super::buffer::{CHANNELS, SAMPLE_RATE};
+
+/// Gating block length (400ms) and step (75% overlap), per ITU-R BS.1770
+const BLOCK_SECS: f32 = 0.4;
+const BLOCK_STEP_SECS: f32 = 0.1;
+
+/// Absolute gate (LUFS)
+const ABSOLUTE_GATE: f32 = -70.0;
+
+/// Relative gate below the absolute-gated loudness (LU)
+const RELATIVE_GATE: f32 = -10.0;
+
+/// Oversampling factor and taps per phase for true-peak estimation
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// Largest boost applied to quiet clips (dB), so near-silence is not blown up
+const MAX_GAIN_DB: f32 = 20.0;
+
+/// Second-order IIR section (direct form I)
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+}
+
+impl Biquad {
+    fn process(&self, input: &[f32]) -> Vec<f32> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+        input
+            .iter()
+            .map(|&x| {
+                let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+                x2 = x1;
+                x1 = x;
+                y2 = y1;
+                y1 = y;
+                y
+            })
+            .collect()
+    }
+}
+
+/// K-weighting stage 1: high shelf modelling the head (48kHz coefficients)
+const K_SHELF: Biquad = Biquad {
+    b: [1.535_124_9, -2.691_696_2, 1.198_392_8],
+    a: [-1.690_659_3, 0.732_480_8],
+};
+
+/// K-weighting stage 2: RLB high-pass (48kHz coefficients)
+const K_HIGH_PASS: Biquad = Biquad {
+    b: [1.0, -2.0, 1.0],
+    a: [-1.990_047_5, 0.990_072_3],
+};
+
+/// Result of normalizing a clip
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessMeasurement {
+    /// Integrated loudness before normalization (None if entirely below the gate)
+    pub integrated_lufs: Option<f32>,
+    /// True peak before normalization (dBTP)
+    pub true_peak_db: f32,
+    /// Gain applied (dB)
+    pub gain_db: f32,
+}
+
+/// EBU R128 loudness normalizer with a true-peak ceiling
+#[derive(Clone)]
+pub struct LoudnessNormalizer {
+    /// Target integrated loudness (LUFS)
+    target_lufs: f32,
+    /// Maximum true peak after gain (dBTP)
+    true_peak_ceiling_db: f32,
+    /// Interpolation filter for true-peak estimation, split into phases
+    interpolation_phases: Vec<[f32; TRUE_PEAK_TAPS]>,
+}
+
+impl LoudnessNormalizer {
+    pub fn new(target_lufs: f32, true_peak_ceiling_db: f32) -> Self {
+        Self {
+            target_lufs,
+            true_peak_ceiling_db,
+            interpolation_phases: interpolation_phases(),
+        }
+    }
+
+    /// Measure interleaved stereo audio and scale it to the target loudness
+    ///
+    /// The gain is reduced where needed so the true peak stays under the
+    /// ceiling. Clips that are silent after gating are left untouched.
+    #[instrument(skip(self, samples), fields(samples = samples.len()))]
+    pub fn normalize(&self, samples: &mut [f32]) -> LoudnessMeasurement {
+        let integrated_lufs = self.integrated_loudness(samples);
+        let true_peak_db = amplitude_to_db(self.true_peak(samples));
+
+        let gain_db = match integrated_lufs {
+            Some(lufs) => (self.target_lufs - lufs)
+                .min(self.true_peak_ceiling_db - true_peak_db)
+                .min(MAX_GAIN_DB),
+            None => 0.0,
+        };
+
+        if gain_db.abs() > 0.01 {
+            let gain = db_to_amplitude(gain_db);
+            for sample in samples.iter_mut() {
+                *sample *= gain;
+            }
+        }
+
+        debug!(?integrated_lufs, true_peak_db, gain_db, "Loudness normalized");
+
+        LoudnessMeasurement {
+            integrated_lufs,
+            true_peak_db,
+            gain_db,
+        }
+    }
+
+    /// Gated integrated loudness (LUFS) per ITU-R BS.1770-4
+    pub fn integrated_loudness(&self, samples: &[f32]) -> Option<f32> {
+        let channels = CHANNELS as usize;
+
+        // Mean square of each K-weighted channel over every gating block
+        let block = (BLOCK_SECS * SAMPLE_RATE as f32) as usize;
+        let step = (BLOCK_STEP_SECS * SAMPLE_RATE as f32) as usize;
+        let weighted: Vec<Vec<f32>> = (0..channels)
+            .map(|ch| {
+                let channel: Vec<f32> = samples.iter().skip(ch).step_by(channels).copied().collect();
+                K_HIGH_PASS.process(&K_SHELF.process(&channel))
+            })
+            .collect();
+
+        let frames = weighted.first().map_or(0, Vec::len);
+        if frames < block {
+            return None;
+        }
+
+        let block_powers: Vec<f32> = (0..=(frames - block) / step)
+            .map(|i| {
+                let start = i * step;
+                weighted
+                    .iter()
+                    .map(|channel| channel[start..start + block].iter().map(|s| s * s).sum::<f32>() / block as f32)
+                    .sum()
+            })
+            .collect();
+
+        let gated_mean = |threshold: f32| {
+            let gated: Vec<f32> = block_powers
+                .iter()
+                .copied()
+                .filter(|&power| power_to_lufs(power) > threshold)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f32>() / gated.len() as f32)
+        };
+
+        let absolute = gated_mean(ABSOLUTE_GATE)?;
+        let relative = gated_mean(power_to_lufs(absolute) + RELATIVE_GATE)?;
+
+        Some(power_to_lufs(relative))
+    }
+
+    /// Estimated true peak (linear) using 4x oversampling
+    pub fn true_peak(&self, samples: &[f32]) -> f32 {
+        let channels = CHANNELS as usize;
+        let mut peak = samples.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+
+        for ch in 0..channels {
+            let channel: Vec<f32> = samples.iter().skip(ch).step_by(channels).copied().collect();
+            for end in TRUE_PEAK_TAPS..=channel.len() {
+                let window = &channel[end - TRUE_PEAK_TAPS..end];
+                for phase in &self.interpolation_phases {
+                    let value: f32 = window.iter().zip(phase).map(|(s, c)| s * c).sum();
+                    peak = peak.max(value.abs());
+                }
+            }
+        }
+
+        peak
+    }
+}
+
+/// Windowed-sinc interpolation filter split into one set of taps per phase
+fn interpolation_phases() -> Vec<[f32; TRUE_PEAK_TAPS]> {
+    let length = TRUE_PEAK_TAPS * TRUE_PEAK_OVERSAMPLING;
+    let centre = (length - 1) as f32 / 2.0;
+
+    (0..TRUE_PEAK_OVERSAMPLING)
+        .map(|phase| {
+            let mut taps = [0.0f32; TRUE_PEAK_TAPS];
+            for (i, tap) in taps.iter_mut().enumerate() {
+                let n = i * TRUE_PEAK_OVERSAMPLING + phase;
+                let x = (n as f32 - centre) / TRUE_PEAK_OVERSAMPLING as f32;
+                let sinc = if x.abs() < 1e-6 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                let window = 0.5 * (1.0 - (2.0 * PI * n as f32 / (length - 1) as f32).cos());
+                *tap = sinc * window;
+            }
+            taps
+        })
+        .collect()
+}
+
+/// Loudness of a summed channel power (LUFS)
+fn power_to_lufs(power: f32) -> f32 {
+    -0.691 + 10.0 * power.max(1e-12).log10()
+}
+
+fn amplitude_to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-9).log10()
+}
+
+fn db_to_amplitude(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stereo_sine(frequency: f32, amplitude: f32, secs: f32) -> Vec<f32> {
+        (0..(secs * SAMPLE_RATE as f32) as usize)
+            .flat_map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let value = ((2.0 * std::f64::consts::PI * frequency as f64 * t).sin() * amplitude as f64) as f32;
+                [value, value]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reference_tone_loudness() {
+        let normalizer = LoudnessNormalizer::new(-23.0, -1.0);
+        // A 1kHz stereo sine at -20 dBFS reads about -20 LUFS (K-weighting is ~0 dB at 1kHz,
+        // and two channels add 3 dB to the -3 dB RMS of a sine)
+        let samples = stereo_sine(1000.0, 0.1, 3.0);
+
+        let lufs = normalizer.integrated_loudness(&samples).expect("No loudness");
+        assert!((lufs + 20.0).abs() < 0.3, "Measured {} LUFS", lufs);
+    }
+
+    #[test]
+    fn test_normalizes_to_target() {
+        let normalizer = LoudnessNormalizer::new(-16.0, -1.0);
+        let mut samples = stereo_sine(1000.0, 0.05, 3.0);
+
+        let measurement = normalizer.normalize(&mut samples);
+        let after = normalizer.integrated_loudness(&samples).unwrap();
+
+        assert!(measurement.gain_db > 0.0);
+        assert!((after + 16.0).abs() < 0.3, "Normalized to {} LUFS", after);
+    }
+
+    #[test]
+    fn test_true_peak_ceiling_limits_gain() {
+        let normalizer = LoudnessNormalizer::new(0.0, -1.0);
+        let mut samples = stereo_sine(1000.0, 0.5, 2.0);
+
+        normalizer.normalize(&mut samples);
+
+        let peak_db = amplitude_to_db(normalizer.true_peak(&samples));
+        assert!(peak_db <= -0.9, "True peak {} dBTP exceeds ceiling", peak_db);
+    }
+
+    #[test]
+    fn test_true_peak_finds_intersample_peak() {
+        let normalizer = LoudnessNormalizer::new(-23.0, -1.0);
+        // Sine at fs/4 sampled 45 degrees off its peaks: samples reach 0.707, the wave reaches 1.0
+        let samples: Vec<f32> = (0..4800)
+            .flat_map(|i| {
+                let value = (PI / 2.0 * i as f32 + PI / 4.0).sin();
+                [value, value]
+            })
+            .collect();
+
+        let peak = normalizer.true_peak(&samples);
+        assert!(peak > 0.95, "True peak {} misses the inter-sample peak", peak);
+    }
+
+    #[test]
+    fn test_silence_is_untouched() {
+        let normalizer = LoudnessNormalizer::new(-14.0, -1.0);
+        let mut samples = vec![0.0f32; 48000 * 2];
+
+        let measurement = normalizer.normalize(&mut samples);
+        assert!(measurement.integrated_lufs.is_none());
+        assert_eq!(measurement.gain_db, 0.0);
+    }
+}
@@ -1,16 +1,26 @@
+mod beat;
 mod buffer;
 mod classifier;
 mod decode;
+mod export;
+mod key;
+mod loudness;
+mod onset;
 mod quantize;
+mod spectrum;
 mod stream;
 mod stretch;
 
 pub use buffer::{LoopBuffer, CHANNELS, SAMPLE_RATE};
 pub use classifier::{AudioClassifier, ContentType};
 
+#[allow(unused_imports)]
+pub use buffer::RawAudioBuffer;
 #[allow(unused_imports)]
 pub use classifier::ClassificationResult;
 pub use decode::AudioDecoder;
+pub use export::{export_loop, file_stamp, WavFormat};
+pub use key::KeyLock;
 pub use quantize::Quantizer;
 pub use stream::StreamCapture;
 
@@ -20,7 +30,7 @@ pub use stretch::TimeStretcher;
 
 use tracing::{debug, error, info, instrument, warn};
 
-use crate::app::{BpmMode, StationInfo};
+use crate::app::{BpmMode, KeyMode, MeterMode, StationInfo};
 use crate::error::AudioError;
 
 /// Audio processing pipeline with content classification
@@ -39,6 +49,18 @@ impl AudioPipeline {
         }
     }
 
+    /// Share the lock-to-first target key with other pipelines
+    pub fn with_key_lock(mut self, key_lock: KeyLock) -> Self {
+        self.quantizer = self.quantizer.with_key_lock(key_lock);
+        self
+    }
+
+    /// Normalize loops to a target loudness under a true-peak ceiling
+    pub fn with_loudness(mut self, target_lufs: f32, true_peak_ceiling_db: f32) -> Self {
+        self.quantizer = self.quantizer.with_loudness(target_lufs, true_peak_ceiling_db);
+        self
+    }
+
     /// Quick-start processing for immediate playback (first station only)
     /// Uses shorter capture time and skips time-stretching for fast startup
     /// Still rejects clear silence but allows uncertain content for speed
@@ -46,7 +68,8 @@ impl AudioPipeline {
     pub async fn process_station_quick(
         &self,
         station: &StationInfo,
-        beats_per_bar: u8,
+        meter: MeterMode,
+        key_mode: KeyMode,
     ) -> Result<LoopBuffer, AudioError> {
         const QUICK_LISTEN_SECONDS: u32 = 6;  // Just enough for 4 bars at slow tempo
         const QUICK_BARS: u8 = 4;
@@ -99,7 +122,7 @@ impl AudioPipeline {
         // Run on blocking thread pool to avoid starving async runtime
         let quantizer = self.quantizer.clone();
         let loop_buffer = tokio::task::spawn_blocking(move || {
-            quantizer.quantize(raw_audio, BpmMode::Auto { min: 70.0, max: 170.0 }, QUICK_BARS, beats_per_bar)
+            quantizer.quantize(raw_audio, BpmMode::Auto { min: 70.0, max: 170.0 }, QUICK_BARS, meter, key_mode)
         })
         .await
         .map_err(|e| AudioError::DecodeError(format!("Quantization task failed: {}", e)))??;
@@ -122,7 +145,8 @@ impl AudioPipeline {
         listen_seconds: u32,
         bpm_mode: BpmMode,
         bars: u8,
-        beats_per_bar: u8,
+        meter: MeterMode,
+        key_mode: KeyMode,
     ) -> Result<LoopBuffer, AudioError> {
         let stream_url = station
             .stream_url
@@ -185,7 +209,7 @@ impl AudioPipeline {
         // Run on blocking thread pool to avoid starving async runtime
         let quantizer = self.quantizer.clone();
         let loop_buffer = tokio::task::spawn_blocking(move || {
-            quantizer.quantize(raw_audio, bpm_mode, bars, beats_per_bar)
+            quantizer.quantize(raw_audio, bpm_mode, bars, meter, key_mode)
         })
         .await
         .map_err(|e| AudioError::DecodeError(format!("Quantization task failed: {}", e)))??;
@@ -201,7 +225,7 @@ impl AudioPipeline {
     }
 
     /// Process with retry logic
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::too_many_arguments)]
     #[instrument(skip(self, station), fields(station_name = %station.name))]
     pub async fn process_station_with_retry(
         &self,
@@ -209,14 +233,15 @@ impl AudioPipeline {
         listen_seconds: u32,
         bpm_mode: BpmMode,
         bars: u8,
-        beats_per_bar: u8,
+        meter: MeterMode,
+        key_mode: KeyMode,
         max_retries: u32,
     ) -> Result<LoopBuffer, AudioError> {
         let mut last_error = None;
 
         for attempt in 0..max_retries {
             match self
-                .process_station(station, listen_seconds, bpm_mode, bars, beats_per_bar)
+                .process_station(station, listen_seconds, bpm_mode, bars, meter, key_mode)
                 .await
             {
                 Ok(buffer) => return Ok(buffer),
@@ -0,0 +1,203 @@
+use tracing::{debug, instrument};
+
+use super::buffer::RawAudioBuffer;
+use super::spectrum::Stft;
+
+/// STFT frame size used for spectral flux
+const FRAME_SIZE: usize = 2048;
+
+/// Onset-strength envelope sampled every `hop_size` frames
+#[derive(Debug, Clone, Default)]
+pub struct OnsetEnvelope {
+    /// Onset strength per hop (normalized to unit standard deviation)
+    pub values: Vec<f32>,
+    /// Envelope values per second
+    pub rate: f32,
+    /// Audio frames per envelope value
+    pub hop_size: usize,
+}
+
+impl OnsetEnvelope {
+    /// Envelope value nearest to an audio frame position
+    pub fn at_frame(&self, frame: usize) -> f32 {
+        let index = (frame + self.hop_size / 2) / self.hop_size.max(1);
+        self.values.get(index).copied().unwrap_or(0.0)
+    }
+}
+
+/// Spectral-flux onset detector with adaptive peak-picking
+#[derive(Clone)]
+pub struct OnsetDetector {
+    stft: Stft,
+    hop_size: usize,
+    /// Log compression factor applied to magnitudes before differencing
+    compression: f32,
+    /// Half-width (in hops) of the local-maximum window
+    peak_window: usize,
+    /// Half-width (in hops) of the moving-average threshold window
+    average_window: usize,
+    /// Offset added to the moving average (in standard deviations)
+    threshold_offset: f32,
+    /// Minimum spacing between onsets (in hops)
+    min_gap: usize,
+    /// Minimum onset strength relative to the strongest value in the envelope
+    min_relative_strength: f32,
+}
+
+impl OnsetDetector {
+    pub fn new(hop_size: usize) -> Self {
+        Self {
+            stft: Stft::new(FRAME_SIZE, hop_size),
+            hop_size,
+            compression: 100.0,
+            peak_window: 3,
+            average_window: 10,
+            threshold_offset: 0.5,
+            min_gap: 3,
+            min_relative_strength: 0.1,
+        }
+    }
+
+    /// Compute the spectral-flux onset envelope
+    ///
+    /// Each value is the summed positive change in log-compressed magnitude
+    /// across all frequency bins since the previous hop.
+    #[instrument(skip(self, raw))]
+    pub fn envelope(&self, raw: &RawAudioBuffer) -> OnsetEnvelope {
+        let mono = raw.to_mono();
+        let spectra = self.stft.magnitudes(&mono);
+
+        let compressed: Vec<Vec<f32>> = spectra
+            .iter()
+            .map(|spectrum| {
+                spectrum
+                    .iter()
+                    .map(|m| (1.0 + self.compression * m).ln())
+                    .collect()
+            })
+            .collect();
+
+        let mut values: Vec<f32> = std::iter::once(0.0)
+            .chain(compressed.windows(2).map(|pair| {
+                pair[1]
+                    .iter()
+                    .zip(&pair[0])
+                    .map(|(cur, prev)| (cur - prev).max(0.0))
+                    .sum()
+            }))
+            .collect();
+
+        // Frames whose window runs past the end only see the truncation
+        let complete_frames = mono.len().saturating_sub(FRAME_SIZE / 2) / self.hop_size + 1;
+        for value in values.iter_mut().skip(complete_frames) {
+            *value = 0.0;
+        }
+
+        // Normalize to unit standard deviation so thresholds are scale-independent
+        let mean = values.iter().sum::<f32>() / values.len().max(1) as f32;
+        let std_dev = (values.iter().map(|v| (v - mean).powi(2)).sum::<f32>()
+            / values.len().max(1) as f32)
+            .sqrt();
+        if std_dev > 1e-6 {
+            for value in &mut values {
+                *value /= std_dev;
+            }
+        }
+
+        OnsetEnvelope {
+            values,
+            rate: raw.sample_rate as f32 / self.hop_size as f32,
+            hop_size: self.hop_size,
+        }
+    }
+
+    /// Pick onsets from an envelope, returning audio frame positions
+    ///
+    /// A hop counts as an onset when it is the local maximum of its
+    /// neighbourhood, exceeds the local moving average by a fixed offset and
+    /// is not negligible next to the strongest onset.
+    pub fn pick_peaks(&self, envelope: &OnsetEnvelope) -> Vec<usize> {
+        let values = &envelope.values;
+        let n = values.len();
+        let mut onsets = Vec::new();
+        let mut last_onset: Option<usize> = None;
+        let floor = values.iter().fold(0.0f32, |a, &b| a.max(b)) * self.min_relative_strength;
+
+        for i in 0..n {
+            let peak_start = i.saturating_sub(self.peak_window);
+            let peak_end = (i + self.peak_window + 1).min(n);
+            let is_local_max = values[peak_start..peak_end].iter().all(|&v| v <= values[i]);
+            if !is_local_max || values[i] <= floor {
+                continue;
+            }
+
+            let avg_start = i.saturating_sub(self.average_window);
+            let avg_end = (i + self.average_window + 1).min(n);
+            let average = values[avg_start..avg_end].iter().sum::<f32>() / (avg_end - avg_start) as f32;
+            if values[i] < average + self.threshold_offset {
+                continue;
+            }
+
+            if last_onset.is_some_and(|last| i - last < self.min_gap) {
+                continue;
+            }
+
+            onsets.push(i * self.hop_size);
+            last_onset = Some(i);
+        }
+
+        debug!(onset_count = onsets.len(), "Detected onsets");
+        onsets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mono_buffer(samples: Vec<f32>) -> RawAudioBuffer {
+        RawAudioBuffer::new(samples, 48000, 1)
+    }
+
+    #[test]
+    fn test_click_onsets() {
+        let detector = OnsetDetector::new(512);
+
+        // Noise bursts every 0.5s over 4 seconds
+        let mut samples = vec![0.0f32; 48000 * 4];
+        for beat in 0..8 {
+            let start = beat * 24000;
+            for i in 0..400 {
+                samples[start + i] = if i % 3 == 0 { 0.7 } else { -0.5 };
+            }
+        }
+
+        let envelope = detector.envelope(&mono_buffer(samples));
+        let onsets = detector.pick_peaks(&envelope);
+
+        assert!(onsets.len() >= 7, "Expected ~8 onsets, got {:?}", onsets);
+        for onset in onsets {
+            let offset = onset % 24000;
+            let distance = offset.min(24000 - offset);
+            assert!(distance <= 1024, "Onset at frame {} is not near a click", onset);
+        }
+    }
+
+    #[test]
+    fn test_steady_tone_has_no_onsets() {
+        let detector = OnsetDetector::new(512);
+
+        // Fade in over 10ms, then a steady tone (phase in f64 to avoid jitter)
+        let samples: Vec<f32> = (0..48000 * 2)
+            .map(|i| {
+                let ramp = (i as f64 / 480.0).min(1.0);
+                (ramp * (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 48000.0).sin() * 0.5) as f32
+            })
+            .collect();
+
+        let envelope = detector.envelope(&mono_buffer(samples));
+        let onsets = detector.pick_peaks(&envelope);
+
+        assert!(onsets.len() <= 1, "Steady tone produced onsets {:?}", onsets);
+    }
+}
@@ -1,15 +1,55 @@
+use std::sync::Arc;
+
 use tracing::{debug, info, instrument};
 
-use crate::app::{BpmMode, LoopInfo};
+use crate::app::{BpmMode, KeyMode, LoopInfo, MeterMode, MusicalKey, TempoCandidate};
 use crate::error::AudioError;
 
+use super::beat::{BeatGrid, BeatTracker};
 use super::buffer::{LoopBuffer, RawAudioBuffer, CHANNELS, SAMPLE_RATE};
+use super::key::{KeyDetector, KeyLock};
+use super::loudness::LoudnessNormalizer;
+use super::onset::{OnsetDetector, OnsetEnvelope};
 use super::stretch::TimeStretcher;
 
+/// Default loudness target (LUFS) and true-peak ceiling (dBTP)
+const DEFAULT_TARGET_LUFS: f32 = -14.0;
+const DEFAULT_TRUE_PEAK_CEILING: f32 = -1.0;
+
+/// Hop size (in frames) of the onset-strength envelope used for beat tracking
+const ONSET_HOP_SIZE: usize = 512;
+
+/// Length of the segments compared when scoring seam continuity (~21ms)
+const SEAM_FRAMES: usize = 1024;
+
+/// Maximum distance (frames) a loop start may move to find a better seam (~5ms)
+const SEAM_SEARCH_FRAMES: usize = 240;
+
+/// Length of the tail-into-head crossfade at the loop seam (~10ms)
+const LOOP_CROSSFADE_FRAMES: usize = 480;
+
+/// Weights of the loop-start criteria: rhythmic regularity, energy, seam continuity
+const START_SCORE_WEIGHTS: (f32, f32, f32) = (0.4, 0.3, 0.3);
+
+/// Centre of the perceptual tempo prior (BPM)
+const TEMPO_PRIOR_CENTER: f32 = 120.0;
+
+/// Width of the perceptual tempo prior (octaves)
+const TEMPO_PRIOR_WIDTH: f32 = 0.9;
+
+/// Tempo ratios checked around every autocorrelation peak
+/// (half-time, double-time and 3/2 relationships)
+const TEMPO_RELATIONS: [f32; 5] = [1.0, 0.5, 2.0, 1.5, 2.0 / 3.0];
+
+/// Maximum number of tempo candidates kept per clip
+const MAX_TEMPO_CANDIDATES: usize = 4;
+
 /// BPM estimation result
 struct BpmEstimate {
     bpm: f32,
     confidence: f32,
+    /// Ranked tempo candidates (best first)
+    candidates: Vec<TempoCandidate>,
 }
 
 /// Audio quantizer for beat alignment with time-stretching
@@ -18,6 +58,12 @@ pub struct Quantizer {
     min_bpm: f32,
     max_bpm: f32,
     stretcher: TimeStretcher,
+    onset_detector: OnsetDetector,
+    beat_tracker: BeatTracker,
+    key_detector: KeyDetector,
+    /// Target key in lock-to-first mode (shared between pipelines)
+    key_lock: KeyLock,
+    loudness: LoudnessNormalizer,
 }
 
 impl Quantizer {
@@ -26,22 +72,43 @@ impl Quantizer {
             min_bpm,
             max_bpm,
             stretcher: TimeStretcher::new(),
+            onset_detector: OnsetDetector::new(ONSET_HOP_SIZE),
+            beat_tracker: BeatTracker::new(),
+            key_detector: KeyDetector::new(SAMPLE_RATE),
+            key_lock: KeyLock::default(),
+            loudness: LoudnessNormalizer::new(DEFAULT_TARGET_LUFS, DEFAULT_TRUE_PEAK_CEILING),
         }
     }
 
+    /// Normalize loops to a target loudness under a true-peak ceiling
+    pub fn with_loudness(mut self, target_lufs: f32, true_peak_ceiling_db: f32) -> Self {
+        self.loudness = LoudnessNormalizer::new(target_lufs, true_peak_ceiling_db);
+        self
+    }
+
+    /// Share the lock-to-first target key with other quantizers
+    pub fn with_key_lock(mut self, key_lock: KeyLock) -> Self {
+        self.key_lock = key_lock;
+        self
+    }
+
     /// Quantize raw audio to a loopable buffer with tempo matching
     ///
     /// This method:
     /// 1. Detects the BPM of the source audio
     /// 2. Time-stretches to match the target BPM (if using Fixed mode)
-    /// 3. Extracts a beat-aligned loop segment
+    /// 3. Tracks beats, downbeats and (optionally) the meter in the (stretched) audio
+    /// 4. Scores candidate downbeats and extracts the best loop segment
+    /// 5. Estimates the musical key, transposing towards the target key if requested
+    /// 6. Normalizes the loop to the target loudness
     #[instrument(skip(self, raw))]
     pub fn quantize(
         &self,
         raw: RawAudioBuffer,
         bpm_mode: BpmMode,
         bars: u8,
-        beats_per_bar: u8,
+        meter: MeterMode,
+        key_mode: KeyMode,
     ) -> Result<LoopBuffer, AudioError> {
         debug!(
             input_samples = raw.samples.len(),
@@ -51,6 +118,76 @@ impl Quantizer {
 
         // Step 1: Always detect source BPM first
         let source_estimate = self.detect_bpm(&raw, self.min_bpm, self.max_bpm);
+
+        self.quantize_at(Arc::new(raw), source_estimate, bpm_mode, bars, meter, key_mode)
+    }
+
+    /// Re-quantize a clip as if its source tempo were `factor` times the detected one
+    ///
+    /// Used to fix tempo octave errors after the fact (0.5 = half-time, 2.0 =
+    /// double-time). Requires the clip to still carry its decoded source audio,
+    /// and refuses a factor that takes the tempo outside the configured range.
+    #[instrument(skip(self, buffer))]
+    pub fn requantize(&self, buffer: &LoopBuffer, factor: f32) -> Result<LoopBuffer, AudioError> {
+        let raw = buffer
+            .source
+            .clone()
+            .ok_or_else(|| AudioError::QuantizationFailed("source audio not available".into()))?;
+        let info = &buffer.loop_info;
+        let bpm = info.source_bpm * factor;
+        if !(self.min_bpm..=self.max_bpm).contains(&bpm) {
+            return Err(AudioError::QuantizationFailed(format!(
+                "{:.0} BPM is outside {:.0}-{:.0} BPM",
+                bpm, self.min_bpm, self.max_bpm
+            )));
+        }
+
+        let source_estimate = BpmEstimate {
+            bpm,
+            confidence: info.bpm_confidence,
+            candidates: info.tempo_candidates.clone(),
+        };
+        let bpm_mode = if info.time_stretched {
+            BpmMode::Fixed(info.bpm)
+        } else {
+            BpmMode::Auto {
+                min: self.min_bpm,
+                max: self.max_bpm,
+            }
+        };
+
+        // Keep the clip in the key it was transposed to
+        let key_mode = match info.key {
+            Some(key) if info.transpose_semitones != 0 => KeyMode::Fixed(key),
+            _ => KeyMode::Original,
+        };
+
+        info!(
+            from_bpm = info.source_bpm,
+            to_bpm = source_estimate.bpm,
+            "Re-quantizing clip"
+        );
+
+        self.quantize_at(
+            raw,
+            source_estimate,
+            bpm_mode,
+            info.bars,
+            MeterMode::Fixed(info.beats_per_bar),
+            key_mode,
+        )
+    }
+
+    /// Quantize using an already-estimated source tempo
+    fn quantize_at(
+        &self,
+        raw: Arc<RawAudioBuffer>,
+        source_estimate: BpmEstimate,
+        bpm_mode: BpmMode,
+        bars: u8,
+        meter: MeterMode,
+        key_mode: KeyMode,
+    ) -> Result<LoopBuffer, AudioError> {
         let source_bpm = source_estimate.bpm;
         let detection_confidence = source_estimate.confidence;
 
@@ -60,17 +197,27 @@ impl Quantizer {
             "Detected source BPM"
         );
 
-        // Step 2: Determine target BPM and whether to time-stretch
+        // Step 2: Detect the key and work out the shift towards the target key
+        let source_key = self.key_detector.detect(&raw.to_mono());
+        let transpose_semitones = self.resolve_transpose(source_key, key_mode);
+        let key = source_key.map(|k| k.transposed(transpose_semitones));
+
+        // Step 3: Determine target BPM and whether to time-stretch or transpose
         let (target_bpm, confidence, audio_to_process) = match bpm_mode {
             BpmMode::Fixed(fixed_bpm) => {
                 // Time-stretch to match the fixed target BPM
                 info!(
                     source_bpm,
                     target_bpm = fixed_bpm,
+                    transpose_semitones,
                     "Time-stretching to fixed BPM"
                 );
 
-                let stretched = self.stretcher.stretch_to_bpm(&raw, source_bpm, fixed_bpm);
+                let stretched = self.stretcher.stretch_and_transpose(
+                    &raw,
+                    source_bpm / fixed_bpm,
+                    transpose_semitones as f32,
+                );
 
                 debug!(
                     original_samples = raw.samples.len(),
@@ -78,16 +225,30 @@ impl Quantizer {
                     "Time stretch complete"
                 );
 
-                (fixed_bpm, 1.0, stretched)
+                (fixed_bpm, 1.0, Arc::new(stretched))
+            }
+            BpmMode::Auto { .. } if transpose_semitones != 0 => {
+                // Use detected BPM, only shift the pitch
+                debug!(source_bpm, transpose_semitones, "Using detected BPM (transpose only)");
+                let transposed = self.stretcher.stretch_and_transpose(&raw, 1.0, transpose_semitones as f32);
+                (source_bpm, detection_confidence, Arc::new(transposed))
             }
             BpmMode::Auto { .. } => {
                 // Use detected BPM, no time-stretching needed
                 debug!(source_bpm, "Using detected BPM (no time-stretch)");
-                (source_bpm, detection_confidence, raw)
+                (source_bpm, detection_confidence, Arc::clone(&raw))
             }
         };
 
-        // Step 3: Calculate target loop length in samples (at the target BPM)
+        // Step 4: Track beats (and the meter, if requested) in the audio to loop
+        let envelope = self.onset_detector.envelope(&audio_to_process);
+        let grid = match meter {
+            MeterMode::Fixed(beats_per_bar) => self.beat_tracker.track(&envelope, target_bpm, beats_per_bar),
+            MeterMode::Auto => self.beat_tracker.track_with_meter_detection(&envelope, target_bpm),
+        };
+        let beats_per_bar = grid.beats_per_bar;
+
+        // Step 5: Calculate target loop length in samples (at the target BPM)
         let total_beats = bars as u32 * beats_per_bar as u32;
         let beat_duration_secs = 60.0 / target_bpm;
         let loop_duration_secs = beat_duration_secs * total_beats as f32;
@@ -106,22 +267,44 @@ impl Quantizer {
             return Err(AudioError::AudioTooShort(loop_duration_secs));
         }
 
-        // Step 4: Find best starting point using onset detection
-        let onsets = self.detect_onsets(&audio_to_process);
-        let start_sample = self.find_best_start(&audio_to_process, &onsets, target_samples);
+        // Step 6: Score candidate downbeats as loop start points
+        let onsets = self.onset_detector.pick_peaks(&envelope);
+        let start_frame = self.find_best_start(&audio_to_process, &envelope, &grid, &onsets, target_frames);
+
+        // Step 7: Nudge the loop points to a zero crossing with the best seam
+        let mono = audio_to_process.to_mono();
+        let beat_frame = start_frame;
+        let start_frame = self.refine_loop_start(&mono, start_frame, target_frames);
+        let start_sample = start_frame * CHANNELS as usize;
 
         debug!(
             start_sample,
-            start_frame = start_sample / CHANNELS as usize,
+            start_frame,
+            shift_frames = start_frame as i64 - beat_frame as i64,
+            beats = grid.beats.len(),
             "Selected start point"
         );
 
-        // Step 5: Extract loop segment
+        // Beat positions relative to the loop start (the refined start may sit
+        // a few milliseconds after the tracked downbeat)
+        let beat_positions: Vec<usize> = grid
+            .beats
+            .iter()
+            .filter(|&&beat| beat + SEAM_SEARCH_FRAMES >= start_frame && beat < start_frame + target_frames)
+            .map(|&beat| beat.saturating_sub(start_frame))
+            .collect();
+
+        // Step 8: Extract loop segment
         let end_sample = start_sample + target_samples;
         let mut samples = audio_to_process.samples[start_sample..end_sample].to_vec();
 
-        // Step 6: Apply crossfade at loop boundaries to prevent clicks
-        self.apply_fades(&mut samples);
+        // Step 9: Crossfade the tail into the audio leading up to the head so
+        // the loop repeats without a click or a dip
+        let pre_roll_start = start_sample.saturating_sub(LOOP_CROSSFADE_FRAMES * CHANNELS as usize);
+        self.crossfade_seam(&mut samples, &audio_to_process.samples[pre_roll_start..start_sample]);
+
+        // Step 10: Normalize to the target loudness (EBU R128) under the true-peak ceiling
+        let loudness = self.loudness.normalize(&mut samples);
 
         // Build result
         let time_stretched = matches!(bpm_mode, BpmMode::Fixed(_)) && (source_bpm - target_bpm).abs() > 0.5;
@@ -132,6 +315,12 @@ impl Quantizer {
             time_stretched,
             bars,
             beats_per_bar,
+            beat_positions,
+            tempo_candidates: source_estimate.candidates,
+            key,
+            transpose_semitones,
+            loudness_lufs: loudness.integrated_lufs,
+            gain_db: loudness.gain_db,
             duration_samples: target_frames,
             sample_rate: SAMPLE_RATE,
         };
@@ -140,15 +329,23 @@ impl Quantizer {
             source_bpm,
             target_bpm = loop_info.bpm,
             bars = loop_info.bars,
+            key = ?loop_info.key.map(|k| k.name()),
+            loudness_lufs = ?loudness.integrated_lufs,
+            true_peak_db = loudness.true_peak_db,
+            gain_db = loudness.gain_db,
             duration_secs = loop_duration_secs,
             time_stretched,
             "Quantization complete"
         );
 
-        Ok(LoopBuffer::new(samples, loop_info))
+        Ok(LoopBuffer::new(samples, loop_info).with_source(raw))
     }
 
     /// BPM detection using energy envelope and autocorrelation
+    ///
+    /// Autocorrelation peaks are expanded with their half-time, double-time and
+    /// 3/2 relatives, and every candidate is weighted by a perceptual tempo
+    /// prior so that e.g. 140 BPM is not reported as 70.
     fn detect_bpm(&self, raw: &RawAudioBuffer, min_bpm: f32, max_bpm: f32) -> BpmEstimate {
         // Convert to mono for analysis
         let mono = raw.to_mono();
@@ -169,36 +366,120 @@ impl Quantizer {
             return BpmEstimate {
                 bpm: 120.0,
                 confidence: 0.0,
+                candidates: Vec::new(),
             };
         }
 
-        // Convert BPM range to lag range (in envelope samples)
+        // Remove DC so correlation reflects periodicity rather than loudness
+        let mean = envelope.iter().sum::<f32>() / envelope.len() as f32;
+        let centered: Vec<f32> = envelope.iter().map(|v| v - mean).collect();
+        let energy = self.autocorrelate(&centered, 0).max(1e-12);
+
         let envelope_rate = raw.sample_rate as f32 / hop_size as f32;
-        let min_lag = (60.0 / max_bpm * envelope_rate) as usize;
-        let max_lag = (60.0 / min_bpm * envelope_rate) as usize;
-
-        // Find best correlation lag
-        let mut best_lag = min_lag;
-        let mut best_correlation = f32::NEG_INFINITY;
-
-        for lag in min_lag..=max_lag.min(envelope.len() / 2) {
-            let correlation = self.autocorrelate(&envelope, lag);
-            if correlation > best_correlation {
-                best_correlation = correlation;
-                best_lag = lag;
+
+        // A flat envelope (e.g. a steady tone) has no tempo to speak of
+        let modulation = energy / envelope.len() as f32 / (mean * mean).max(1e-12);
+        let candidates = if modulation > 1e-6 {
+            self.tempo_candidates(&centered, envelope_rate, energy, min_bpm, max_bpm)
+        } else {
+            Vec::new()
+        };
+
+        // Fall back to the strongest lag in range when no periodicity peaks exist
+        let best_bpm = match candidates.first() {
+            Some(best) => best.bpm,
+            None => {
+                let min_lag = ((60.0 / max_bpm * envelope_rate) as usize).max(1);
+                let max_lag = (60.0 / min_bpm * envelope_rate) as usize;
+                let mut best_lag = min_lag;
+                let mut best_correlation = f32::NEG_INFINITY;
+                for lag in min_lag..=max_lag.min(envelope.len() / 2) {
+                    let correlation = self.autocorrelate(&envelope, lag);
+                    if correlation > best_correlation {
+                        best_correlation = correlation;
+                        best_lag = lag;
+                    }
+                }
+                (60.0 / (best_lag as f32 / envelope_rate)).clamp(min_bpm, max_bpm)
             }
+        };
+
+        let lag = 60.0 / best_bpm * envelope_rate;
+        let correlation = self.autocorrelate_at(&centered, lag) / energy;
+
+        BpmEstimate {
+            bpm: best_bpm,
+            // Normalize confidence (correlation can be negative)
+            confidence: ((correlation + 1.0) / 2.0).clamp(0.0, 1.0),
+            candidates,
+        }
+    }
+
+    /// Rank tempo candidates within `[min_bpm, max_bpm]`
+    fn tempo_candidates(
+        &self,
+        envelope: &[f32],
+        envelope_rate: f32,
+        energy: f32,
+        min_bpm: f32,
+        max_bpm: f32,
+    ) -> Vec<TempoCandidate> {
+        // Search peaks an octave beyond the range so related tempos can be found
+        let min_lag = ((60.0 / (max_bpm * 2.0) * envelope_rate) as usize).max(1);
+        let max_lag = ((60.0 / (min_bpm / 2.0) * envelope_rate) as usize).min(envelope.len() / 2);
+
+        if min_lag + 2 > max_lag {
+            return Vec::new();
         }
 
-        // Convert lag back to BPM
-        let bpm = 60.0 / (best_lag as f32 / envelope_rate);
+        let correlations: Vec<f32> = (min_lag..=max_lag)
+            .map(|lag| self.autocorrelate(envelope, lag) / energy)
+            .collect();
 
-        // Normalize confidence (correlation can be negative)
-        let confidence = (best_correlation + 1.0) / 2.0;
+        let mut candidates: Vec<TempoCandidate> = Vec::new();
 
-        BpmEstimate {
-            bpm: bpm.clamp(min_bpm, max_bpm),
-            confidence: confidence.clamp(0.0, 1.0),
+        for i in 1..correlations.len() - 1 {
+            let is_peak = correlations[i] > correlations[i - 1] && correlations[i] >= correlations[i + 1];
+            if !is_peak || correlations[i] <= 0.0 {
+                continue;
+            }
+
+            let peak_bpm = 60.0 / ((min_lag + i) as f32 / envelope_rate);
+
+            for relation in TEMPO_RELATIONS {
+                let bpm = peak_bpm * relation;
+                if bpm < min_bpm || bpm > max_bpm {
+                    continue;
+                }
+
+                let lag = 60.0 / bpm * envelope_rate;
+                let correlation = self.autocorrelate_at(envelope, lag).max(0.0) / energy;
+                let score = correlation * tempo_prior(bpm);
+
+                // Merge with an existing candidate within 3%
+                match candidates.iter_mut().find(|c| (c.bpm / bpm - 1.0).abs() < 0.03) {
+                    Some(existing) if existing.score >= score => {}
+                    Some(existing) => *existing = TempoCandidate { bpm, score },
+                    None => candidates.push(TempoCandidate { bpm, score }),
+                }
+            }
         }
+
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        candidates.truncate(MAX_TEMPO_CANDIDATES);
+        candidates
+    }
+
+    /// Autocorrelation at a fractional lag (linear interpolation)
+    fn autocorrelate_at(&self, signal: &[f32], lag: f32) -> f32 {
+        let lower = lag.floor() as usize;
+        let frac = lag - lower as f32;
+        if lower + 1 >= signal.len() {
+            return 0.0;
+        }
+        let a = self.autocorrelate(signal, lower);
+        let b = self.autocorrelate(signal, lower + 1);
+        a + (b - a) * frac
     }
 
     /// Compute autocorrelation at a specific lag
@@ -217,69 +498,219 @@ impl Quantizer {
         sum / n as f32
     }
 
-    /// Simple onset detection using energy increases
-    fn detect_onsets(&self, raw: &RawAudioBuffer) -> Vec<usize> {
+    /// Find the best loop start frame across the whole capture
+    ///
+    /// Every downbeat that leaves room for the loop is a candidate (onsets are
+    /// used if the beat grid has none). Candidates are scored on rhythmic
+    /// regularity, energy and seam continuity, and the highest score wins.
+    fn find_best_start(
+        &self,
+        raw: &RawAudioBuffer,
+        envelope: &OnsetEnvelope,
+        grid: &BeatGrid,
+        onsets: &[usize],
+        target_frames: usize,
+    ) -> usize {
         let mono = raw.to_mono();
+        let max_start = mono.len().saturating_sub(target_frames);
+
+        let mut candidates: Vec<usize> = grid.downbeats().filter(|&d| d <= max_start).collect();
+        if candidates.is_empty() {
+            candidates = onsets.iter().copied().filter(|&o| o <= max_start).collect();
+        }
+        if candidates.is_empty() {
+            // Fallback: start from beginning
+            return 0;
+        }
+
+        // Prefix sums of squared samples for O(1) window energy
+        let mut energy_prefix = Vec::with_capacity(mono.len() + 1);
+        energy_prefix.push(0.0f64);
+        for sample in &mono {
+            let last = *energy_prefix.last().unwrap_or(&0.0);
+            energy_prefix.push(last + (*sample as f64).powi(2));
+        }
+        let window_rms = |start: usize| {
+            let sum = energy_prefix[start + target_frames] - energy_prefix[start];
+            (sum / target_frames.max(1) as f64).sqrt() as f32
+        };
+
+        let beat_period = median_spacing(&grid.beats);
+        let max_rms = candidates
+            .iter()
+            .map(|&c| window_rms(c))
+            .fold(0.0f32, f32::max)
+            .max(1e-9);
 
-        let hop_size = 512;
-        let threshold = 1.5;
+        let (w_regularity, w_energy, w_seam) = START_SCORE_WEIGHTS;
+        let mut best = (candidates[0], f32::NEG_INFINITY);
 
-        let mut onsets = Vec::new();
-        let mut prev_energy = 0.0f32;
+        for &candidate in &candidates {
+            let regularity = self.rhythmic_regularity(envelope, candidate, target_frames, beat_period);
+            let energy = window_rms(candidate) / max_rms;
+            let seam = self.seam_continuity(&mono, candidate, target_frames);
+            let score = w_regularity * regularity + w_energy * energy + w_seam * seam;
 
-        for (i, chunk) in mono.chunks(hop_size).enumerate() {
-            let energy: f32 = chunk.iter().map(|s| s.abs()).sum::<f32>() / chunk.len() as f32;
+            debug!(candidate, regularity, energy, seam, score, "Scored loop start");
 
-            if energy > prev_energy * threshold && energy > 0.01 {
-                // Convert to stereo sample index
-                onsets.push(i * hop_size * CHANNELS as usize);
+            if score > best.1 {
+                best = (candidate, score);
             }
-            prev_energy = energy.max(0.001); // Avoid division by zero
         }
 
-        debug!(onset_count = onsets.len(), "Detected onsets");
-        onsets
+        debug!(
+            candidates = candidates.len(),
+            start_frame = best.0,
+            score = best.1,
+            "Selected best loop start"
+        );
+
+        best.0
+    }
+
+    /// How strongly onsets line up with the beat grid inside a window (0.0-1.0)
+    fn rhythmic_regularity(
+        &self,
+        envelope: &OnsetEnvelope,
+        start: usize,
+        length: usize,
+        beat_period: Option<usize>,
+    ) -> f32 {
+        let Some(period) = beat_period.filter(|&p| p > 0) else {
+            return 0.5;
+        };
+
+        let hop_size = envelope.hop_size.max(1);
+        let first = start / hop_size;
+        let last = ((start + length) / hop_size).min(envelope.values.len());
+        if last <= first {
+            return 0.0;
+        }
+        let window = &envelope.values[first..last];
+        let window_mean = window.iter().map(|v| v.max(0.0)).sum::<f32>() / window.len() as f32;
+
+        let beats: Vec<f32> = (start..start + length)
+            .step_by(period)
+            .map(|frame| envelope.at_frame(frame).max(0.0))
+            .collect();
+        let on_beat_mean = beats.iter().sum::<f32>() / beats.len().max(1) as f32;
+
+        // Ratio of on-beat to average onset strength, squashed into 0..1
+        let ratio = on_beat_mean / (window_mean + 1e-6);
+        ratio / (1.0 + ratio)
+    }
+
+    /// How well the audio after the loop end matches the loop start (0.0-1.0)
+    ///
+    /// If the original audio continues into something resembling the loop's
+    /// head, wrapping around will sound natural.
+    fn seam_continuity(&self, mono: &[f32], start: usize, length: usize) -> f32 {
+        let end = start + length;
+        let (tail, head) = if end + SEAM_FRAMES <= mono.len() {
+            (&mono[end..end + SEAM_FRAMES], &mono[start..start + SEAM_FRAMES])
+        } else if start >= SEAM_FRAMES {
+            (&mono[end - SEAM_FRAMES..end], &mono[start - SEAM_FRAMES..start])
+        } else {
+            return 0.5;
+        };
+
+        let dot: f32 = tail.iter().zip(head).map(|(a, b)| a * b).sum();
+        let tail_energy: f32 = tail.iter().map(|s| s * s).sum();
+        let head_energy: f32 = head.iter().map(|s| s * s).sum();
+
+        if tail_energy < 1e-9 && head_energy < 1e-9 {
+            return 1.0;
+        }
+
+        let correlation = dot / (tail_energy * head_energy).sqrt().max(1e-9);
+        let level_match = tail_energy.min(head_energy) / tail_energy.max(head_energy).max(1e-9);
+
+        0.5 * correlation.max(0.0) + 0.5 * level_match.sqrt()
     }
 
-    /// Find best starting point aligned to an onset
-    fn find_best_start(&self, raw: &RawAudioBuffer, onsets: &[usize], target_len: usize) -> usize {
-        let max_start = raw.samples.len().saturating_sub(target_len);
+    /// Semitones to shift a clip in `source_key` by under the given key mode
+    fn resolve_transpose(&self, source_key: Option<MusicalKey>, key_mode: KeyMode) -> i8 {
+        let Some(source_key) = source_key else {
+            return 0;
+        };
+
+        let target = match key_mode {
+            KeyMode::Original => return 0,
+            KeyMode::Fixed(target) => target,
+            // The first clip with a detected key sets the target for every later clip
+            KeyMode::LockToFirst => *self.key_lock.lock().get_or_insert(source_key),
+        };
 
-        // Prefer starting near an onset, but not too close to the end
-        for &onset in onsets {
-            if onset < max_start {
-                return onset;
+        source_key.semitones_to(&target)
+    }
+
+    /// Move a loop start to a nearby rising zero crossing with the best seam
+    ///
+    /// The loop length is kept fixed, so the end moves with the start. Among
+    /// zero crossings within a few milliseconds, the one whose following
+    /// audio best matches the loop head wins.
+    fn refine_loop_start(&self, mono: &[f32], start: usize, length: usize) -> usize {
+        let first = start.saturating_sub(SEAM_SEARCH_FRAMES).max(1);
+        let last = (start + SEAM_SEARCH_FRAMES).min(mono.len().saturating_sub(length));
+
+        let mut best = (start, f32::NEG_INFINITY);
+        for candidate in first..=last {
+            let rising = mono[candidate - 1] < 0.0 && mono[candidate] >= 0.0;
+            if !rising {
+                continue;
+            }
+
+            // Small preference for staying close to the tracked downbeat
+            let distance = candidate.abs_diff(start) as f32 / SEAM_SEARCH_FRAMES as f32;
+            let score = self.seam_continuity(mono, candidate, length) - 0.1 * distance;
+
+            if score > best.1 {
+                best = (candidate, score);
             }
         }
 
-        // Fallback: start from beginning
-        0
+        best.0
     }
 
-    /// Apply fade in/out to prevent clicks at loop boundaries
-    fn apply_fades(&self, samples: &mut [f32]) {
-        // ~21ms fade at 48kHz stereo
-        let fade_samples = 2048.min(samples.len() / 4);
-
-        // Fade in (beginning of loop)
-        for i in 0..fade_samples {
-            let gain = i as f32 / fade_samples as f32;
-            // Apply smooth curve (raised cosine)
-            let gain = 0.5 * (1.0 - (std::f32::consts::PI * gain).cos());
-            samples[i] *= gain;
+    /// Crossfade the loop tail into the audio that originally preceded its head
+    ///
+    /// After the fade the last sample leads straight into the first one, the
+    /// same way the captured audio did, so repeats are seamless.
+    fn crossfade_seam(&self, samples: &mut [f32], pre_roll: &[f32]) {
+        let channels = CHANNELS as usize;
+        let fade_frames = (pre_roll.len() / channels).min(samples.len() / channels / 4);
+        if fade_frames == 0 {
+            return;
         }
 
-        // Fade out (end of loop)
-        let len = samples.len();
-        for i in 0..fade_samples {
-            let gain = i as f32 / fade_samples as f32;
-            // Apply smooth curve (raised cosine)
-            let gain = 0.5 * (1.0 - (std::f32::consts::PI * gain).cos());
-            samples[len - 1 - i] *= gain;
+        let tail_start = samples.len() - fade_frames * channels;
+        let pre_roll = &pre_roll[pre_roll.len() - fade_frames * channels..];
+
+        for (i, (sample, lead_in)) in samples[tail_start..].iter_mut().zip(pre_roll).enumerate() {
+            let t = (i / channels) as f32 / fade_frames as f32;
+            // Raised cosine from the loop tail (0.0) to the lead-in (1.0)
+            let gain = 0.5 * (1.0 - (std::f32::consts::PI * t).cos());
+            *sample = *sample * (1.0 - gain) + lead_in * gain;
         }
     }
 }
 
+/// Median spacing between consecutive positions
+fn median_spacing(positions: &[usize]) -> Option<usize> {
+    let mut spacings: Vec<usize> = positions.windows(2).map(|w| w[1] - w[0]).collect();
+    if spacings.is_empty() {
+        return None;
+    }
+    spacings.sort_unstable();
+    Some(spacings[spacings.len() / 2])
+}
+
+/// Perceptual tempo prior: log-Gaussian weighting centred on moderate tempos
+fn tempo_prior(bpm: f32) -> f32 {
+    let octaves = (bpm / TEMPO_PRIOR_CENTER).log2() / TEMPO_PRIOR_WIDTH;
+    (-0.5 * octaves * octaves).exp()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -305,7 +736,7 @@ mod tests {
         let raw = RawAudioBuffer::new(samples, sample_rate, channels);
 
         // Quantize with fixed BPM
-        let result = quantizer.quantize(raw, BpmMode::Fixed(120.0), 2, 4);
+        let result = quantizer.quantize(raw, BpmMode::Fixed(120.0), 2, MeterMode::Fixed(4), KeyMode::Original);
 
         assert!(result.is_ok());
         let loop_buffer = result.unwrap();
@@ -361,4 +792,213 @@ mod tests {
             estimate.confidence
         );
     }
+
+    /// Click track at 120 BPM with every fourth click accented, starting on `first_accent` beat
+    fn accented_click_track(duration_secs: f32, first_accent: usize, beats_per_bar: usize) -> RawAudioBuffer {
+        let sample_rate = 48000;
+        let channels = 2;
+        let num_frames = (duration_secs * sample_rate as f32) as usize;
+        let beat_frames = sample_rate as usize / 2;
+        let click_frames = 200;
+
+        let mut samples = vec![0.0f32; num_frames * channels as usize];
+        let mut beat = 0;
+        let mut frame = 0;
+        while frame + click_frames < num_frames {
+            let level = if beat % beats_per_bar == first_accent { 0.9 } else { 0.3 };
+            for i in 0..click_frames {
+                // Decaying noise-like burst
+                let value = level * (1.0 - i as f32 / click_frames as f32) * if i % 2 == 0 { 1.0 } else { -1.0 };
+                samples[(frame + i) * 2] = value;
+                samples[(frame + i) * 2 + 1] = value;
+            }
+            frame += beat_frames;
+            beat += 1;
+        }
+
+        RawAudioBuffer::new(samples, sample_rate, channels)
+    }
+
+    #[test]
+    fn test_beat_grid_downbeat() {
+        let quantizer = Quantizer::new(60.0, 180.0);
+        let raw = accented_click_track(10.0, 1, 4);
+
+        let envelope = quantizer.onset_detector.envelope(&raw);
+        let grid = quantizer.beat_tracker.track(&envelope, 120.0, 4);
+        let first_downbeat = grid.downbeats().next().expect("No downbeats found");
+
+        // Accented clicks sit at 0.5s + n * 2s (24000 + n * 96000 frames)
+        let offset = (first_downbeat as i64 - 24000).rem_euclid(96000);
+        let distance = offset.min(96000 - offset);
+        assert!(
+            distance <= ONSET_HOP_SIZE as i64,
+            "Downbeat at frame {} is not on an accented click",
+            first_downbeat
+        );
+    }
+
+    #[test]
+    fn test_auto_meter_waltz() {
+        let quantizer = Quantizer::new(60.0, 180.0);
+        let raw = accented_click_track(12.0, 0, 3);
+
+        let loop_buffer = quantizer
+            .quantize(raw, BpmMode::Fixed(120.0), 1, MeterMode::Auto, KeyMode::Original)
+            .expect("Quantization failed");
+
+        assert_eq!(loop_buffer.loop_info.beats_per_bar, 3);
+        // One bar of three beats at 120 BPM
+        assert_eq!(loop_buffer.loop_info.duration_samples, 72000);
+    }
+
+    #[test]
+    fn test_loop_info_beat_positions() {
+        let quantizer = Quantizer::new(60.0, 180.0);
+        let raw = accented_click_track(10.0, 0, 4);
+
+        let loop_buffer = quantizer
+            .quantize(raw, BpmMode::Fixed(120.0), 1, MeterMode::Fixed(4), KeyMode::Original)
+            .expect("Quantization failed");
+        let beats = &loop_buffer.loop_info.beat_positions;
+
+        assert!(!beats.is_empty(), "Expected beat positions");
+        assert!(beats[0] < ONSET_HOP_SIZE, "Loop should start on a beat, first at {}", beats[0]);
+        for pair in beats.windows(2) {
+            let spacing = pair[1] - pair[0];
+            assert!(
+                (23000..=25000).contains(&spacing),
+                "Irregular beat spacing {}",
+                spacing
+            );
+        }
+    }
+
+    /// Stereo click track with clicks every `interval_secs`
+    fn click_track(interval_secs: f32, duration_secs: f32) -> RawAudioBuffer {
+        let sample_rate = 48000;
+        let num_frames = (duration_secs * sample_rate as f32) as usize;
+        let mut samples = vec![0.0f32; num_frames * 2];
+
+        let mut beat = 0;
+        loop {
+            let frame = (beat as f32 * interval_secs * sample_rate as f32) as usize;
+            if frame + 100 >= num_frames {
+                break;
+            }
+            for i in 0..100 {
+                samples[(frame + i) * 2] = 0.8;
+                samples[(frame + i) * 2 + 1] = 0.8;
+            }
+            beat += 1;
+        }
+
+        RawAudioBuffer::new(samples, sample_rate, 2)
+    }
+
+    #[test]
+    fn test_tempo_octave_resolution() {
+        let quantizer = Quantizer::new(70.0, 170.0);
+        let raw = click_track(60.0 / 140.0, 12.0);
+
+        let estimate = quantizer.detect_bpm(&raw, 70.0, 170.0);
+
+        assert!(
+            (estimate.bpm - 140.0).abs() < 5.0,
+            "Expected ~140 BPM, got {} ({:?})",
+            estimate.bpm,
+            estimate.candidates
+        );
+        assert!(
+            estimate.candidates.iter().any(|c| (c.bpm - 70.0).abs() < 4.0),
+            "Expected half-time candidate, got {:?}",
+            estimate.candidates
+        );
+    }
+
+    #[test]
+    fn test_requantize_double_tempo() {
+        let quantizer = Quantizer::new(60.0, 180.0);
+        let raw = click_track(1.0, 12.0);
+
+        let loop_buffer = quantizer
+            .quantize(raw, BpmMode::Auto { min: 60.0, max: 180.0 }, 1, MeterMode::Fixed(4), KeyMode::Original)
+            .expect("Quantization failed");
+        let doubled = quantizer
+            .requantize(&loop_buffer, 2.0)
+            .expect("Re-quantization failed");
+
+        assert!((doubled.loop_info.bpm - loop_buffer.loop_info.bpm * 2.0).abs() < 0.01);
+        assert_eq!(doubled.loop_info.duration_samples, loop_buffer.loop_info.duration_samples / 2);
+        assert!(doubled.same_source(&loop_buffer));
+    }
+
+    #[test]
+    fn test_requantize_stays_in_bpm_range() {
+        let quantizer = Quantizer::new(60.0, 180.0);
+        let raw = click_track(60.0 / 100.0, 12.0);
+
+        let loop_buffer = quantizer
+            .quantize(raw, BpmMode::Auto { min: 60.0, max: 180.0 }, 1, MeterMode::Fixed(4), KeyMode::Original)
+            .expect("Quantization failed");
+        assert!(quantizer.requantize(&loop_buffer, 2.0).is_err());
+        assert!(quantizer.requantize(&loop_buffer, 0.5).is_err());
+    }
+
+    #[test]
+    fn test_best_start_prefers_energetic_window() {
+        let quantizer = Quantizer::new(60.0, 180.0);
+        let mut raw = accented_click_track(10.0, 0, 4);
+
+        // Make the first two seconds much quieter than the rest
+        for sample in &mut raw.samples[..48000 * 2 * 2] {
+            *sample *= 0.05;
+        }
+
+        let envelope = quantizer.onset_detector.envelope(&raw);
+        let grid = BeatGrid {
+            beats: (0..20).map(|beat| beat * 24000).collect(),
+            downbeat_phase: 0,
+            beats_per_bar: 4,
+        };
+        let onsets = quantizer.onset_detector.pick_peaks(&envelope);
+
+        // 4-second loop: downbeats at 0s, 2s, 4s and 6s all fit
+        let start = quantizer.find_best_start(&raw, &envelope, &grid, &onsets, 48000 * 4);
+
+        assert!(start >= 96000, "Expected a start after the quiet intro, got frame {}", start);
+        assert_eq!(start % 96000, 0, "Start should be a downbeat, got frame {}", start);
+    }
+
+    #[test]
+    fn test_loop_seam_is_continuous() {
+        let quantizer = Quantizer::new(60.0, 180.0);
+
+        // Stereo 220Hz tone, 10 seconds (phase in f64 for a clean waveform)
+        let samples: Vec<f32> = (0..48000 * 10)
+            .flat_map(|frame| {
+                let t = frame as f64 / 48000.0;
+                let value = ((2.0 * std::f64::consts::PI * 220.0 * t).sin() * 0.5) as f32;
+                [value, value]
+            })
+            .collect();
+        let raw = RawAudioBuffer::new(samples, 48000, 2);
+
+        let loop_buffer = quantizer
+            .quantize(raw, BpmMode::Fixed(120.0), 1, MeterMode::Fixed(4), KeyMode::Original)
+            .expect("Quantization failed");
+        let samples = &loop_buffer.samples;
+        let len = samples.len();
+
+        // Wrapping from the last frame to the first should look like any other step
+        let max_step = 2.0 * std::f32::consts::PI * 220.0 / 48000.0 * 0.5;
+        let wrap_step = (samples[0] - samples[len - 2]).abs();
+        assert!(wrap_step <= max_step * 1.5, "Seam jump {} exceeds {}", wrap_step, max_step);
+
+        // No dip: the loop edges are as loud as the middle
+        let rms = |slice: &[f32]| (slice.iter().map(|s| s * s).sum::<f32>() / slice.len() as f32).sqrt();
+        let middle = rms(&samples[len / 2..len / 2 + 4096]);
+        assert!(rms(&samples[..4096]) > middle * 0.9, "Loop head is attenuated");
+        assert!(rms(&samples[len - 4096..]) > middle * 0.9, "Loop tail is attenuated");
+    }
 }
@@ -0,0 +1,142 @@
+use std::f32::consts::PI;
+
+/// In-place iterative radix-2 FFT
+///
+/// `re` and `im` must have the same power-of-two length.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert_eq!(n, im.len());
+    debug_assert!(n.is_power_of_two());
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    // Butterflies
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        let (w_im, w_re) = angle.sin_cos();
+        for start in (0..n).step_by(len) {
+            let mut cur_re = 1.0f32;
+            let mut cur_im = 0.0f32;
+            for k in 0..len / 2 {
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cur_re - im[b] * cur_im;
+                let t_im = re[b] * cur_im + im[b] * cur_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                let next_re = cur_re * w_re - cur_im * w_im;
+                cur_im = cur_re * w_im + cur_im * w_re;
+                cur_re = next_re;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Short-time Fourier transform producing magnitude spectra
+#[derive(Clone)]
+pub struct Stft {
+    frame_size: usize,
+    hop_size: usize,
+    window: Vec<f32>,
+}
+
+impl Stft {
+    /// Create an STFT with a Hann window (`frame_size` must be a power of two)
+    pub fn new(frame_size: usize, hop_size: usize) -> Self {
+        let window = (0..frame_size)
+            .map(|i| 0.5 * (1.0 - (2.0 * PI * i as f32 / frame_size as f32).cos()))
+            .collect();
+
+        Self {
+            frame_size,
+            hop_size,
+            window,
+        }
+    }
+
+    /// Number of frequency bins per spectrum
+    pub fn bin_count(&self) -> usize {
+        self.frame_size / 2 + 1
+    }
+
+    /// Centre frequency (Hz) of a bin at the given sample rate
+    pub fn bin_frequency(&self, bin: usize, sample_rate: u32) -> f32 {
+        bin as f32 * sample_rate as f32 / self.frame_size as f32
+    }
+
+    /// Magnitude spectra of a mono signal
+    ///
+    /// Frames are centred, so spectrum `i` describes the signal around sample
+    /// `i * hop_size`.
+    pub fn magnitudes(&self, mono: &[f32]) -> Vec<Vec<f32>> {
+        let half = self.frame_size / 2;
+        let frame_count = mono.len().div_ceil(self.hop_size);
+        let mut spectra = Vec::with_capacity(frame_count);
+
+        let mut re = vec![0.0f32; self.frame_size];
+        let mut im = vec![0.0f32; self.frame_size];
+
+        for frame in 0..frame_count {
+            let centre = frame * self.hop_size;
+            for (i, (r, w)) in re.iter_mut().zip(&self.window).enumerate() {
+                let index = (centre + i).checked_sub(half);
+                *r = index.and_then(|idx| mono.get(idx)).copied().unwrap_or(0.0) * w;
+            }
+            im.iter_mut().for_each(|v| *v = 0.0);
+
+            fft(&mut re, &mut im);
+
+            spectra.push(
+                re.iter()
+                    .zip(&im)
+                    .take(self.bin_count())
+                    .map(|(r, i)| (r * r + i * i).sqrt())
+                    .collect(),
+            );
+        }
+
+        spectra
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fft_sine_peak() {
+        let n = 1024;
+        // 32 cycles over the frame lands exactly on bin 32
+        let mut re: Vec<f32> = (0..n)
+            .map(|i| (2.0 * PI * 32.0 * i as f32 / n as f32).sin())
+            .collect();
+        let mut im = vec![0.0f32; n];
+
+        fft(&mut re, &mut im);
+
+        let magnitudes: Vec<f32> = re.iter().zip(&im).map(|(r, i)| (r * r + i * i).sqrt()).collect();
+        let peak = (0..n / 2)
+            .max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b]))
+            .unwrap();
+
+        assert_eq!(peak, 32);
+        assert!((magnitudes[32] - n as f32 / 2.0).abs() < 1.0);
+    }
+}
@@ -3,7 +3,7 @@ use tracing::{debug, instrument};
 
 use super::buffer::{RawAudioBuffer, CHANNELS, SAMPLE_RATE};
 
-/// Time-stretches audio to match a target BPM while preserving pitch
+/// Time-stretches audio to match a target BPM and optionally shifts its pitch
 #[derive(Clone)]
 pub struct TimeStretcher {
     sample_rate: f32,
@@ -20,6 +20,7 @@ impl TimeStretcher {
     ///
     /// This changes the tempo without affecting pitch.
     /// For example, stretching 90 BPM to 120 BPM makes the audio play faster.
+    #[allow(dead_code)]
     #[instrument(skip(self, input))]
     pub fn stretch_to_bpm(
         &self,
@@ -39,8 +40,22 @@ impl TimeStretcher {
             "Calculating time stretch"
         );
 
-        // If ratio is close to 1.0, skip stretching
-        if (stretch_ratio - 1.0).abs() < 0.01 {
+        self.stretch_and_transpose(input, stretch_ratio, 0.0)
+    }
+
+    /// Time-stretch by a ratio (output length / input length) and transpose
+    /// by a number of semitones in a single pass
+    ///
+    /// Transposition keeps the tempo; stretching keeps the pitch.
+    #[instrument(skip(self, input))]
+    pub fn stretch_and_transpose(
+        &self,
+        input: &RawAudioBuffer,
+        stretch_ratio: f32,
+        semitones: f32,
+    ) -> RawAudioBuffer {
+        // If ratio is close to 1.0 and there is nothing to transpose, skip processing
+        if (stretch_ratio - 1.0).abs() < 0.01 && semitones.abs() < 0.01 {
             debug!("Stretch ratio near 1.0, skipping time stretch");
             return RawAudioBuffer::new(
                 input.samples.clone(),
@@ -72,6 +87,10 @@ impl TimeStretcher {
         // Create and configure stretcher for stereo audio
         let mut stretcher = Stretch::new();
         stretcher.preset_default(CHANNELS as i32, self.sample_rate);
+        if semitones.abs() >= 0.01 {
+            debug!(semitones, "Transposing audio");
+            stretcher.set_transpose_semitones(semitones, None);
+        }
 
         // Prepare input/output buffers
         let input_channels: Vec<Vec<f32>> = vec![left, right];
@@ -166,4 +185,29 @@ mod tests {
         let output = stretcher.stretch_to_bpm(&input, 120.0, 120.0);
         assert_eq!(output.samples.len(), input.samples.len());
     }
+
+    #[test]
+    fn test_transpose_octave_up() {
+        let stretcher = TimeStretcher::new();
+
+        // One second of stereo 220Hz
+        let samples: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .flat_map(|i| {
+                let value = (2.0 * std::f64::consts::PI * 220.0 * i as f64 / SAMPLE_RATE as f64).sin() as f32 * 0.5;
+                [value, value]
+            })
+            .collect();
+        let input = RawAudioBuffer::new(samples, SAMPLE_RATE, CHANNELS);
+
+        let output = stretcher.stretch_and_transpose(&input, 1.0, 12.0);
+        assert_eq!(output.samples.len(), input.samples.len());
+
+        // Count rising zero crossings in the settled middle half of the left channel
+        let left: Vec<f32> = output.samples.iter().step_by(2).copied().collect();
+        let middle = &left[left.len() / 4..left.len() * 3 / 4];
+        let crossings = middle.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        let frequency = crossings as f32 * 2.0;
+
+        assert!((frequency - 440.0).abs() < 30.0, "Transposed frequency {}", frequency);
+    }
 }
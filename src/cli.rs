@@ -1,92 +1,268 @@
-use clap::Parser;
+use std::time::Duration;
+
+use clap::{CommandFactory, Parser, Subcommand};
+
+use crate::app::{KeyMode, MeterMode};
+use crate::audio::{file_stamp, WavFormat};
+use crate::playback::{LimiterSettings, OutputBackend, TransitionStyle};
 
 #[derive(Parser, Debug, Clone)]
 #[command(name = "tappr")]
 #[command(about = "Ride the beat of the world's airwaves")]
 #[command(version)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     // Station selection
     /// Search for stations by query
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub search: Option<String>,
 
     /// Filter by region/country
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub region: Option<String>,
 
     /// Use random station selection (default if no search/region)
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub random: bool,
 
     /// Seed for reproducible random selection
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub seed: Option<u64>,
 
     // Timing
     /// Duration to capture from stream (seconds)
-    #[arg(long, default_value = "12")]
+    #[arg(long, global = true, default_value = "12")]
     pub listen_seconds: u32,
 
     /// Duration of captured clip (seconds)
-    #[arg(long, default_value = "4")]
+    #[arg(long, global = true, default_value = "4")]
     pub clip_seconds: u32,
 
-    /// Duration before changing stations (seconds)
-    #[arg(long, default_value = "12")]
+    /// Time each loop repeats before changing stations (seconds, rounded up to whole loops)
+    #[arg(long, global = true, default_value = "12")]
     pub station_change_seconds: u32,
 
     /// Number of bars per clip (more bars = longer clip)
-    #[arg(long, default_value = "8", value_parser = clap::value_parser!(u8).range(1..=16))]
+    #[arg(long, global = true, default_value = "8", value_parser = clap::value_parser!(u8).range(1..=16))]
     pub bars: u8,
 
-    /// Time signature (beats per bar)
-    #[arg(long, default_value = "4/4")]
+    /// Time signature (e.g. 4/4, 3/4, 6/8) or "auto" to detect beats per bar
+    #[arg(long, global = true, default_value = "4/4")]
     pub meter: String,
 
     // BPM
     /// Fixed BPM (disables auto-detection)
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub bpm: Option<f32>,
 
     /// Minimum BPM for auto-detection
-    #[arg(long, default_value = "70")]
+    #[arg(long, global = true, default_value = "70")]
     pub bpm_min: f32,
 
     /// Maximum BPM for auto-detection
-    #[arg(long, default_value = "170")]
+    #[arg(long, global = true, default_value = "170")]
     pub bpm_max: f32,
 
+    // Loudness
+    /// Integrated loudness every loop is normalized to (LUFS)
+    #[arg(long, global = true, default_value = "-14", allow_hyphen_values = true)]
+    pub target_lufs: f32,
+
+    /// True-peak ceiling for normalized loops (dBTP)
+    #[arg(long, global = true, default_value = "-1", allow_hyphen_values = true)]
+    pub true_peak_ceiling: f32,
+
+    /// Master limiter ceiling (dBFS)
+    #[arg(long, global = true, default_value = "-0.3", allow_hyphen_values = true)]
+    pub limiter_ceiling: f32,
+
+    /// Master limiter release time (milliseconds)
+    #[arg(long, global = true, default_value = "150")]
+    pub limiter_release_ms: f32,
+
+    // Key
+    /// Transpose every clip to a key (e.g. C, Am, F#m) or "first" to lock to the first clip's key
+    #[arg(long, global = true)]
+    pub key: Option<KeyMode>,
+
+    // Transitions
+    /// Transition between clips: cut, crossfade[:bars], echo, filter, backspin or tuning
+    #[arg(long, global = true, default_value = "crossfade")]
+    pub transition: TransitionStyle,
+
+    // Sequencing
+    /// Hold finished clips in a small pool and play the most key-compatible one next
+    #[arg(long, global = true)]
+    pub harmonic: bool,
+
+    // Output
+    /// Where audio goes: device[:<name>], null (discard in real time) or file:<path> (WAV, or ffmpeg by extension)
+    #[arg(long, default_value = "device")]
+    pub output: OutputBackend,
+
+    /// Audio output device by name (or part of it); playback falls back to the default device while it is unplugged
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+
+    /// Headphone cue device by name (or part of it) for previewing the next clip
+    #[arg(long, value_name = "NAME")]
+    pub cue_device: Option<String>,
+
+    // Export
+    /// Directory the current loop is exported to (default: <music dir>/tappr)
+    #[arg(long)]
+    pub export_dir: Option<std::path::PathBuf>,
+
+    /// Sample format of exported loops: 24 (24-bit PCM) or float (32-bit float)
+    #[arg(long, default_value = "24")]
+    pub export_format: WavFormat,
+
+    /// Record the session mix from the start (.wav, or any format ffmpeg can write)
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<std::path::PathBuf>,
+
     // Heuristics
     /// Minimum RMS threshold for audio
-    #[arg(long, default_value = "0.01")]
+    #[arg(long, global = true, default_value = "0.01")]
     pub min_rms: f32,
 
     /// Maximum silence duration (seconds)
-    #[arg(long, default_value = "2.0")]
+    #[arg(long, global = true, default_value = "2.0")]
     pub max_silence: f32,
 
     /// Rate limit between API requests (ms)
-    #[arg(long, default_value = "500")]
+    #[arg(long, global = true, default_value = "500")]
     pub rate_limit_ms: u64,
 
     // Debug
     /// Custom cache directory
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub cache_dir: Option<std::path::PathBuf>,
 
     /// Enable verbose logging
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub verbose: bool,
 }
 
+/// Modes other than the interactive TUI
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Render a mix to a file without an audio device (faster than real time when the network allows)
+    Render(RenderArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct RenderArgs {
+    /// Length of the mix (e.g. 90s, 10m, 1h30m; plain numbers are seconds)
+    #[arg(long, value_parser = parse_duration)]
+    pub duration: Duration,
+
+    /// Output file (.wav, or any format ffmpeg can write)
+    #[arg(long)]
+    pub out: std::path::PathBuf,
+
+    /// Don't print progress to the terminal
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+/// Parse a duration such as "90", "90s", "10m" or "1h30m"
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{}' (expected e.g. 90s, 10m or 1h30m)", text);
+
+    let mut secs = 0u64;
+    let mut digits = String::new();
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+        secs += value * unit;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        secs += digits.parse::<u64>().map_err(|_| invalid())?;
+    }
+
+    if secs == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(secs))
+}
+
 impl Args {
-    /// Parse meter string (e.g., "4/4") into beats per bar
-    pub fn beats_per_bar(&self) -> u8 {
-        self.meter
+    /// Parse the command line, rejecting options that contradict each other
+    pub fn parse_checked() -> Self {
+        let args = Self::parse();
+        if let Err(e) = args.check_conflicts() {
+            e.exit();
+        }
+        args
+    }
+
+    /// `--output device:<name>` and `--device` both name the output device
+    fn check_conflicts(&self) -> Result<(), clap::Error> {
+        if matches!(self.output, OutputBackend::NamedDevice(_)) && self.device.is_some() {
+            return Err(Self::command().error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--device cannot be combined with --output device:<name> (use one of them)",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse meter string (e.g., "4/4" or "auto") into a meter mode
+    pub fn meter_mode(&self) -> MeterMode {
+        if self.meter.eq_ignore_ascii_case("auto") {
+            return MeterMode::Auto;
+        }
+
+        let beats_per_bar = self
+            .meter
             .split('/')
             .next()
             .and_then(|s| s.parse().ok())
-            .unwrap_or(4)
+            .filter(|&n| n > 0)
+            .unwrap_or(4);
+        MeterMode::Fixed(beats_per_bar)
+    }
+
+    /// Master limiter settings
+    pub fn limiter_settings(&self) -> LimiterSettings {
+        LimiterSettings {
+            ceiling_db: self.limiter_ceiling,
+            release_ms: self.limiter_release_ms,
+        }
+    }
+
+    /// Path of a recording started from the TUI (same format as --record, WAV by default)
+    pub fn session_recording_path(&self, started: std::time::SystemTime) -> std::path::PathBuf {
+        let extension = self
+            .record
+            .as_ref()
+            .and_then(|path| path.extension())
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("wav");
+        self.export_dir()
+            .join(format!("tappr-session-{}.{}", file_stamp(started), extension))
+    }
+
+    /// Directory exported loops are written to
+    pub fn export_dir(&self) -> std::path::PathBuf {
+        self.export_dir.clone().unwrap_or_else(|| {
+            dirs::audio_dir()
+                .unwrap_or_else(|| std::path::PathBuf::from("."))
+                .join("tappr")
+        })
     }
 
     /// Check if using default random selection
@@ -94,3 +270,41 @@ impl Args {
         self.random || (self.search.is_none() && self.region.is_none())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2m30s"), Ok(Duration::from_secs(150)));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("10x").is_err());
+    }
+
+    #[test]
+    fn test_device_conflicts_with_named_output() {
+        let args = Args::try_parse_from(["tappr", "--output", "device:USB", "--device", "Speakers"]).unwrap();
+        assert!(args.check_conflicts().is_err());
+        let args = Args::try_parse_from(["tappr", "--output", "device", "--device", "Speakers"]).unwrap();
+        assert!(args.check_conflicts().is_ok());
+    }
+
+    #[test]
+    fn test_options_after_render_subcommand() {
+        let args = Args::try_parse_from([
+            "tappr", "render", "--duration", "10m", "--out", "mix.wav", "--transition", "cut", "--bars", "4", "-q",
+        ])
+        .unwrap();
+        assert_eq!(args.transition, TransitionStyle::Cut);
+        assert_eq!(args.bars, 4);
+        let Some(Command::Render(render)) = args.command else {
+            panic!("render subcommand not parsed");
+        };
+        assert!(render.quiet);
+    }
+}
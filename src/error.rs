@@ -102,6 +102,9 @@ pub enum PlaybackError {
 
     #[error("Playback failed: {0}")]
     PlaybackFailed(String),
+
+    #[error("Recording failed: {0}")]
+    Recording(String),
 }
 
 /// TUI errors
@@ -3,6 +3,7 @@ mod audio;
 mod cli;
 mod error;
 mod playback;
+mod prefs;
 mod radio;
 mod tasks;
 mod tui;
@@ -12,18 +13,20 @@ use std::io;
 use std::panic;
 use std::sync::Arc;
 
-use clap::Parser;
+use tokio::sync::mpsc;
 use crossterm::execute;
 use crossterm::terminal::{disable_raw_mode, LeaveAlternateScreen};
-use tracing::{error, info, Level};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::fmt::writer::MakeWriterExt;
 use tracing_subscriber::EnvFilter;
 
-use crate::app::{AppState, BpmMode};
-use crate::cli::Args;
-use crate::error::Result;
-use crate::playback::PlaybackEngine;
-use crate::tasks::{Channels, Producer, ProducerConfig, ProducerEvent};
+use crate::app::{AppState, BpmMode, KeyMode};
+use crate::audio::{export_loop, LoopBuffer, Quantizer};
+use crate::cli::{Args, Command, RenderArgs};
+use crate::error::{PlaybackError, Result};
+use crate::playback::{default_device_name, find_device, list_audio_devices, OfflineRenderer, PlaybackEngine, Recorder};
+use crate::prefs::Preferences;
+use crate::tasks::{Channels, Producer, ProducerCommand, ProducerConfig, ProducerEvent};
 use crate::tui::TuiApp;
 
 /// Restore terminal state (used for panic hook and cleanup)
@@ -41,15 +44,15 @@ async fn main() -> Result<()> {
         default_hook(info);
     }));
 
-    let args = Args::parse();
+    let args = Args::parse_checked();
 
     // Initialize tracing (to file if TUI is enabled)
     init_tracing(args.verbose);
 
     info!("tappr v{} starting", env!("CARGO_PKG_VERSION"));
 
-    // Create shared application state
-    let state = AppState::new(&args);
+    // Create shared application state (volume and mute saved by the last session)
+    let state = AppState::new(&args, Preferences::load());
 
     // Set up graceful shutdown
     let shutdown_state = Arc::clone(&state);
@@ -62,8 +65,11 @@ async fn main() -> Result<()> {
         shutdown_state.quit();
     });
 
-    // Run the application
-    let result = run(state, args).await;
+    // Run the application (or render a mix offline)
+    let result = match args.command.clone() {
+        Some(Command::Render(render)) => render_mix(state, args, render).await,
+        None => run(state, args).await,
+    };
 
     // Always restore terminal on exit
     restore_terminal();
@@ -108,58 +114,82 @@ async fn run(state: Arc<AppState>, args: Args) -> Result<()> {
         "Starting session"
     );
 
-    // Initialize playback engine with default audio device
+    // Initialize playback engine on the chosen output (default audio device)
     let initial_device = {
         let settings = state.settings.read().await;
-        settings.audio_device_index
+        settings.preferred_audio_device(default_device_name())
+    };
+    let mut playback = PlaybackEngine::with_output(&args.output, initial_device.as_deref(), args.limiter_settings())?;
+    state.settings.write().await.active_audio_device = playback.device_name().to_string();
+    playback.set_dwell(args.station_change_seconds as f32);
+
+    // Headphone cue: a second engine previewing the next queued clip on a loop
+    // (opened strictly, falling back to the default device would preview on the main speakers)
+    let mut cue = match &args.cue_device {
+        Some(device) => match PlaybackEngine::with_device_strict(device, args.limiter_settings()) {
+            Ok(cue) if cue.device_name() == playback.device_name() => {
+                warn!(device = %cue.device_name(), "Cue device is the main output, previewing is disabled");
+                None
+            }
+            Ok(cue) => {
+                cue.toggle_hold();
+                info!(device = %cue.device_name(), "Cue output ready");
+                Some(cue)
+            }
+            Err(e) => {
+                warn!(%device, error = %e, "Cue device unavailable, previewing is disabled");
+                None
+            }
+        },
+        None => None,
     };
-    let mut playback = PlaybackEngine::with_device(Some(initial_device))?;
+    let mut cued_clip: Option<u64> = None;
 
     // Set up channels for task communication
     let channels = Channels::new();
     let (cmd_tx, cmd_rx, event_tx, mut event_rx) = channels.split();
 
-    // Configure producer
-    let bpm_mode = args
-        .bpm
-        .map(BpmMode::Fixed)
-        .unwrap_or(BpmMode::Auto {
-            min: args.bpm_min,
-            max: args.bpm_max,
-        });
+    // Re-quantization of the current clip runs off the main loop and reports back as an event
+    let requantize_tx = event_tx.clone();
+    let quantizer = Quantizer::new(args.bpm_min, args.bpm_max)
+        .with_loudness(args.target_lufs, args.true_peak_ceiling);
 
-    let producer_config = ProducerConfig {
-        search: args.search.clone(),
-        region: args.region.clone(),
-        listen_seconds: args.listen_seconds,
-        station_change_seconds: args.station_change_seconds,
-        bars: args.bars,
-        beats_per_bar: args.beats_per_bar(),
-        bpm_mode,
-    };
+    // Loop exports write files off the main loop too
+    let export_tx = event_tx.clone();
+
+    // Re-enumerate output devices to follow devices being plugged in and out
+    if args.output.is_device() {
+        spawn_device_scan(event_tx.clone());
+    }
 
     // Start producer task with parallel workers
-    let producer = Producer::with_params(
-        producer_config,
-        Arc::clone(&state),
-        cmd_rx,
-        event_tx,
-        args.rate_limit_ms,
-        args.cache_dir.clone(),
-        args.bpm_min,
-        args.bpm_max,
-    );
-    tokio::spawn(async move {
-        producer.run().await;
-    });
+    spawn_producer(&args, Arc::clone(&state), cmd_rx, event_tx);
 
     // Initialize TUI
-    let mut tui = TuiApp::new(Arc::clone(&state), cmd_tx)?;
+    let mut tui = TuiApp::new(Arc::clone(&state), cmd_tx.clone())?;
+    tui.set_limiter_meter(playback.limiter_meter());
 
     info!("TUI started - press 'q' to quit");
 
-    // Track sink length to detect clip transitions (ground truth from audio playback)
-    let mut last_sink_len: usize = 0;
+    // Session recording taps the master output; chapters follow the playing clip
+    let mut recorder: Option<Recorder> = None;
+    let mut recorded_clip: Option<u64> = None;
+    // Harmonic sequencing follows the key of the clip actually playing
+    let mut keyed_clip: Option<u64> = None;
+    if let Some(path) = &args.record {
+        match Recorder::start(path) {
+            Ok(started) => {
+                playback.connect_recorder(&started);
+                recorder = Some(started);
+            }
+            Err(e) => tui.set_error(format!("Recording failed: {}", e)),
+        }
+    }
+
+    let mut applied_transition = None;
+
+    // Volume is saved whenever it changes
+    let mut saved_prefs = state.settings.read().await.preferences();
 
     // Main event loop
     loop {
@@ -169,16 +199,6 @@ async fn run(state: Arc<AppState>, args: Args) -> Result<()> {
             break;
         }
 
-        // TUI sync: detect clip transitions by monitoring sink length
-        // This is the ground truth - the audio sink knows when clips actually finish
-        let current_sink_len = playback.queue_len();
-        let sink_transitioned = current_sink_len < last_sink_len;
-
-        if sink_transitioned && tui.queue_len() > 0 {
-            // Audio sink transitioned to next clip, advance TUI to match
-            tui.advance_queue();
-        }
-
         // Process producer events (may add more clips to the sink)
         while let Ok(event) = event_rx.try_recv() {
             match event {
@@ -187,44 +207,179 @@ async fn run(state: Arc<AppState>, args: Args) -> Result<()> {
                 }
                 ProducerEvent::LoopReady(buffer, station) => {
                     let loop_info = buffer.loop_info.clone();
+                    let clip_id = buffer.id;
 
                     // Append to queue for gapless playback
                     // First clip uses play(), subsequent clips use append()
                     if playback.is_finished() {
-                        // This clip will play immediately
                         playback.play(buffer);
-                        tui.set_now_playing(station, loop_info);
                     } else {
-                        // This clip is queued for later
                         playback.append(buffer);
-                        tui.add_to_queue(station, loop_info);
                     }
+                    // Shown as now playing once the engine reports it playing
+                    tui.add_to_queue(station, loop_info, clip_id);
                 }
                 ProducerEvent::Error(msg) => {
                     tui.set_error(msg);
                 }
                 ProducerEvent::SkipCurrent => {
                     info!("Skipping current station");
-                    // Skip current clip in playback - TUI will advance on next
-                    // loop iteration when it detects the sink length decrease
+                    // Skip current clip in playback - TUI will advance once the
+                    // reported playback position moves to the next clip
                     playback.skip_one();
                 }
-                ProducerEvent::AudioDeviceChanged(device_index) => {
-                    info!(device_index, "Switching audio device");
-                    // Stop current playback
-                    playback.stop();
-                    // Recreate playback engine with new device
-                    match PlaybackEngine::with_device(Some(device_index)) {
-                        Ok(new_playback) => {
-                            playback = new_playback;
-                            info!("Audio device switched successfully");
+                ProducerEvent::Requantize(factor) => {
+                    let Some(current) = playback.current_buffer() else {
+                        continue;
+                    };
+                    info!(factor, bpm = current.loop_info.bpm, "Re-quantizing current clip");
+
+                    let quantizer = quantizer.clone();
+                    let requantize_tx = requantize_tx.clone();
+                    tokio::spawn(async move {
+                        let result = tokio::task::spawn_blocking(move || {
+                            quantizer.requantize(&current, factor)
+                        })
+                        .await;
+
+                        let event = match result {
+                            Ok(Ok(buffer)) => ProducerEvent::Requantized(buffer),
+                            Ok(Err(e)) => ProducerEvent::Error(format!("Re-quantize failed: {}", e)),
+                            Err(e) => ProducerEvent::Error(format!("Re-quantize task failed: {}", e)),
+                        };
+                        let _ = requantize_tx.send(event).await;
+                    });
+                }
+                ProducerEvent::Requantized(buffer) => {
+                    let loop_info = buffer.loop_info.clone();
+                    let clip_id = buffer.id;
+                    if playback.replace_current(buffer) {
+                        tui.replace_now_playing(loop_info, clip_id);
+                    }
+                }
+                ProducerEvent::ToggleHold => {
+                    let held = playback.toggle_hold();
+                    info!(held, "Toggled loop hold");
+                }
+                ProducerEvent::Replay(clip_id) => {
+                    if playback.replay(clip_id) {
+                        tui.requeue_from_history(clip_id);
+                    } else {
+                        tui.set_error("Clip is no longer in the history".to_string());
+                    }
+                }
+                ProducerEvent::Export => {
+                    let Some(current) = playback.current_buffer() else {
+                        tui.set_error("Nothing to export".to_string());
+                        continue;
+                    };
+                    let Some(station) = tui.station_for_clip(current.id) else {
+                        tui.set_error("Nothing to export (station of the playing clip unknown)".to_string());
+                        continue;
+                    };
+                    let dir = args.export_dir();
+                    let format = args.export_format;
+                    info!(station = %station.name, dir = ?dir, %format, "Exporting current loop");
+
+                    let export_tx = export_tx.clone();
+                    tokio::spawn(async move {
+                        let result = tokio::task::spawn_blocking(move || {
+                            export_loop(&current, &station, &dir, format)
+                        })
+                        .await;
+
+                        let event = match result {
+                            Ok(Ok(path)) => ProducerEvent::Exported(path),
+                            Ok(Err(e)) => ProducerEvent::Error(format!("Export failed: {}", e)),
+                            Err(e) => ProducerEvent::Error(format!("Export task failed: {}", e)),
+                        };
+                        let _ = export_tx.send(event).await;
+                    });
+                }
+                ProducerEvent::Exported(path) => {
+                    tui.set_notice(format!("Exported {}", path.display()));
+                }
+                ProducerEvent::ToggleRecord => {
+                    if let Some(finished) = recorder.take() {
+                        playback.disconnect_recorder();
+                        tui.set_recording(None);
+                        match finished.finish() {
+                            Ok(path) => tui.set_notice(format!("Recorded {}", path.display())),
+                            Err(e) => tui.set_error(format!("Recording failed: {}", e)),
                         }
-                        Err(e) => {
-                            error!(error = %e, "Failed to switch audio device");
-                            tui.set_error(format!("Device switch failed: {}", e));
+                    } else {
+                        let path = args.session_recording_path(std::time::SystemTime::now());
+                        match Recorder::start(&path) {
+                            Ok(started) => {
+                                playback.connect_recorder(&started);
+                                recorded_clip = None;
+                                tui.set_notice(format!("Recording to {}", path.display()));
+                                recorder = Some(started);
+                            }
+                            Err(e) => tui.set_error(format!("Recording failed: {}", e)),
                         }
                     }
                 }
+                ProducerEvent::ToggleCue => {
+                    let Some(cue) = cue.as_mut() else {
+                        tui.set_error("No cue device (start with --cue-device <name>)".to_string());
+                        continue;
+                    };
+                    if cued_clip.take().is_some() {
+                        cue.stop();
+                    } else if let Some(next) = playback.next_queued() {
+                        info!(clip_id = next.id, "Previewing next clip on cue device");
+                        cued_clip = Some(next.id);
+                        preview(cue, next);
+                    } else {
+                        tui.set_error("Nothing queued to preview".to_string());
+                    }
+                    tui.set_cued(cued_clip);
+                }
+                ProducerEvent::DropCued => {
+                    let (Some(cue), Some(clip_id)) = (cue.as_mut(), cued_clip.take()) else {
+                        continue;
+                    };
+                    cue.stop();
+                    if playback.remove_queued(clip_id) {
+                        info!(clip_id, "Dropped cued clip from the queue");
+                        tui.remove_from_queue(clip_id);
+                    }
+                    tui.set_cued(None);
+                }
+                ProducerEvent::RemoveQueued(clip_id) => {
+                    if playback.remove_queued(clip_id) {
+                        info!(clip_id, "Removed clip from the queue");
+                        tui.remove_from_queue(clip_id);
+                        recue(cue.as_mut(), &playback, &mut cued_clip, &mut tui);
+                    } else {
+                        tui.set_error("Clip is already playing".to_string());
+                    }
+                }
+                ProducerEvent::MoveQueued(clip_id, offset) => {
+                    if playback.move_queued(clip_id, offset) {
+                        tui.reorder_queue(&playback.queued_ids());
+                        recue(cue.as_mut(), &playback, &mut cued_clip, &mut tui);
+                    }
+                }
+                ProducerEvent::PinQueued(clip_id) => {
+                    if playback.pin_queued(clip_id) {
+                        info!(clip_id, "Pinned clip to play next");
+                        tui.reorder_queue(&playback.queued_ids());
+                        recue(cue.as_mut(), &playback, &mut cued_clip, &mut tui);
+                    } else {
+                        tui.set_error("Clip is already playing".to_string());
+                    }
+                }
+                ProducerEvent::AudioDeviceChanged(device) => {
+                    info!(%device, "Audio device chosen");
+                    let default = tokio::task::block_in_place(default_device_name);
+                    follow_audio_device(&mut playback, &state, &mut tui, default).await;
+                }
+                ProducerEvent::DevicesScanned { devices, default } => {
+                    state.settings.write().await.audio_devices = devices;
+                    follow_audio_device(&mut playback, &state, &mut tui, default).await;
+                }
                 ProducerEvent::Shutdown => {
                     info!("Producer shutdown");
                     break;
@@ -232,11 +387,64 @@ async fn run(state: Arc<AppState>, args: Args) -> Result<()> {
             }
         }
 
-        // Update sink length tracking after event processing
-        last_sink_len = playback.queue_len();
+        // Follow what the mixer is actually playing
+        let position = playback.position();
+        tui.sync_position(position, || playback.queued_ids());
+
+        // The preview ends once the clip leaves the queue for the main output
+        if let Some(clip_id) = cued_clip.filter(|&id| !playback.is_queued(id)) {
+            info!(clip_id, "Cued clip is playing, stopping preview");
+            if let Some(cue) = cue.as_mut() {
+                cue.stop();
+            }
+            cued_clip = None;
+            tui.set_cued(None);
+        }
+
+        if let Some(clip) = position.clip.filter(|clip| keyed_clip != Some(clip.id)) {
+            keyed_clip = Some(clip.id);
+            let key = playback.current_buffer().and_then(|buffer| buffer.loop_info.key);
+            let _ = cmd_tx.try_send(ProducerCommand::NowPlayingKey(key));
+        }
+
+        // Mark station changes in the recording where the clip started
+        if let Some(recorder) = recorder.as_mut() {
+            if let Some(clip) = position.clip.filter(|clip| recorded_clip != Some(clip.id)) {
+                recorded_clip = Some(clip.id);
+                if let Some(station) = tui.station_for_clip(clip.id) {
+                    let into_clip = position.frame.saturating_sub(clip.start);
+                    recorder.add_chapter(&station, recorder.frames().saturating_sub(into_clip));
+                }
+            }
+            tui.set_recording(Some(recorder.elapsed()));
+        }
 
         // Draw TUI
         let settings = state.settings.read().await.clone();
+        // The mixer shares its lock with the audio thread, so only touch it on a change
+        if applied_transition != Some(settings.transition) {
+            playback.set_transition(settings.transition);
+            applied_transition = Some(settings.transition);
+        }
+
+        // Apply pause and volume (also picked up by a newly switched device)
+        let paused = state.is_paused();
+        if paused != playback.is_paused() {
+            if paused {
+                playback.pause();
+            } else {
+                playback.resume();
+            }
+        }
+        let volume = settings.output_volume();
+        if playback.volume() != volume {
+            playback.set_volume(volume);
+        }
+        let prefs = settings.preferences();
+        if prefs != saved_prefs {
+            prefs.save();
+            saved_prefs = prefs;
+        }
         tui.draw(&settings)?;
 
         // Small delay to prevent busy loop
@@ -244,8 +452,251 @@ async fn run(state: Arc<AppState>, args: Args) -> Result<()> {
     }
 
     // Clean shutdown
+    playback.disconnect_recorder();
+    if let Some(recorder) = recorder.take() {
+        match recorder.finish() {
+            Ok(path) => info!(path = ?path, "Session recording saved"),
+            Err(e) => error!(error = %e, "Failed to finish session recording"),
+        }
+    }
     playback.stop();
     tui.cleanup();
 
     Ok(())
 }
+
+/// Loop a clip on the cue engine, on a bar grid of its own
+fn preview(cue: &mut PlaybackEngine, clip: LoopBuffer) {
+    cue.stop();
+    cue.clear_tempo();
+    cue.play(clip);
+}
+
+/// Keep a running preview on the next queued clip after the queue was edited
+fn recue(cue: Option<&mut PlaybackEngine>, playback: &PlaybackEngine, cued_clip: &mut Option<u64>, tui: &mut TuiApp) {
+    let (Some(cue), Some(cued)) = (cue, *cued_clip) else {
+        return;
+    };
+    match playback.next_queued() {
+        Some(next) if next.id == cued => return,
+        Some(next) => {
+            info!(clip_id = next.id, "Next clip changed, previewing it on cue device");
+            *cued_clip = Some(next.id);
+            preview(cue, next);
+        }
+        None => {
+            cue.stop();
+            *cued_clip = None;
+        }
+    }
+    tui.set_cued(*cued_clip);
+}
+
+/// Output devices are re-enumerated this often right after a change
+const DEVICE_SCAN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+/// The interval doubles while the device list stays the same, up to this
+const DEVICE_SCAN_MAX_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Report the output devices (and the default one) to the main loop when they change
+///
+/// Enumerating devices is slow, so scans back off while nothing is plugged in
+/// or out.
+fn spawn_device_scan(event_tx: mpsc::Sender<ProducerEvent>) {
+    tokio::spawn(async move {
+        let mut interval = DEVICE_SCAN_INTERVAL;
+        let mut last = None;
+        loop {
+            tokio::time::sleep(interval).await;
+            let scan = tokio::task::spawn_blocking(|| (list_audio_devices(), default_device_name())).await;
+            let Ok(scan) = scan else {
+                continue;
+            };
+            if last.as_ref() == Some(&scan) {
+                interval = (interval * 2).min(DEVICE_SCAN_MAX_INTERVAL);
+                continue;
+            }
+            interval = DEVICE_SCAN_INTERVAL;
+            last = Some(scan.clone());
+
+            let (devices, default) = scan;
+            if event_tx.send(ProducerEvent::DevicesScanned { devices, default }).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Move playback to the chosen device if it is plugged in, else to the default device
+///
+/// Called whenever the choice or the device list changes, so playback falls
+/// back when the chosen device disappears and returns once it reappears.
+/// Opening a device blocks, so settings are not locked while it happens.
+async fn follow_audio_device(
+    playback: &mut PlaybackEngine,
+    state: &AppState,
+    tui: &mut TuiApp,
+    default: Option<String>,
+) {
+    let (target, unavailable) = {
+        let settings = state.settings.read().await;
+        if !settings.output.is_device() {
+            return;
+        }
+        let Some(target) = settings.preferred_audio_device(default) else {
+            return;
+        };
+        let unavailable = settings
+            .audio_device
+            .clone()
+            .filter(|wanted| find_device(&settings.audio_devices, wanted).is_none());
+        (target, unavailable)
+    };
+    if target == playback.device_name() {
+        return;
+    }
+
+    match tokio::task::block_in_place(|| playback.switch_device(Some(&target))) {
+        Ok(()) => {
+            state.settings.write().await.active_audio_device = playback.device_name().to_string();
+            match unavailable {
+                Some(wanted) => tui.set_notice(format!("{} unavailable, playing on {}", wanted, target)),
+                None => tui.set_notice(format!("Playing on {}", target)),
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to switch audio device");
+            tui.set_error(format!("Device switch failed: {}", e));
+        }
+    }
+}
+
+/// An offline render gives up once it has waited this long for the next clip
+const RENDER_STALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// Render a mix to a file without an audio device
+///
+/// Runs the producer and the same mixer and limiter as live playback, but
+/// pulls samples as fast as they can be computed. Rendering only waits when
+/// the producer hasn't delivered the next clip yet.
+async fn render_mix(state: Arc<AppState>, args: Args, render: RenderArgs) -> Result<()> {
+    let total_frames = (render.duration.as_secs_f64() * audio::SAMPLE_RATE as f64) as u64;
+    info!(out = ?render.out, secs = render.duration.as_secs(), "Starting offline render");
+
+    let mut renderer = OfflineRenderer::create(&render.out, args.limiter_settings())?;
+    renderer.set_transition(args.transition);
+    renderer.set_dwell(args.station_change_seconds as f32);
+
+    let (cmd_tx, cmd_rx, event_tx, mut event_rx) = Channels::new().split();
+    spawn_producer(&args, Arc::clone(&state), cmd_rx, event_tx);
+
+    let mut producer_done = false;
+    let mut stalled = false;
+    let mut stations = 0;
+    let mut last_clip = std::time::Instant::now();
+    let mut last_progress = None;
+    while renderer.frames() < total_frames && !state.is_quitting() {
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                ProducerEvent::LoopReady(buffer, station) => {
+                    info!(station = %station.name, bpm = buffer.loop_info.bpm, "Queued clip for render");
+                    stations += 1;
+                    last_clip = std::time::Instant::now();
+                    renderer.append(buffer);
+                }
+                ProducerEvent::Error(msg) => warn!(error = %msg, "Producer error"),
+                ProducerEvent::Shutdown => producer_done = true,
+                _ => {}
+            }
+        }
+
+        if renderer.ready() || producer_done {
+            tokio::task::block_in_place(|| renderer.render(total_frames))?;
+        } else if last_clip.elapsed() >= RENDER_STALL_TIMEOUT {
+            warn!(stations, "No clip from the producer, stopping the render");
+            stalled = true;
+            break;
+        } else {
+            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        }
+
+        let secs = renderer.frames() / audio::SAMPLE_RATE as u64;
+        if !render.quiet && last_progress != Some(secs) {
+            last_progress = Some(secs);
+            eprint!(
+                "\rRendering {}  {}:{:02} / {}:{:02}",
+                render.out.display(),
+                secs / 60,
+                secs % 60,
+                render.duration.as_secs() / 60,
+                render.duration.as_secs() % 60
+            );
+        }
+    }
+    if !render.quiet {
+        eprintln!();
+    }
+
+    let _ = cmd_tx.send(ProducerCommand::Quit).await;
+    state.quit();
+    let secs = renderer.frames() / audio::SAMPLE_RATE as u64;
+    let path = tokio::task::block_in_place(|| renderer.finish())?;
+    if stalled {
+        return Err(PlaybackError::PlaybackFailed(format!(
+            "no clip arrived for {} s, stopped after {}:{:02} (partial mix in {})",
+            RENDER_STALL_TIMEOUT.as_secs(),
+            secs / 60,
+            secs % 60,
+            path.display()
+        ))
+        .into());
+    }
+    if !render.quiet {
+        println!("Wrote {} ({} clips)", path.display(), stations);
+    }
+    Ok(())
+}
+
+/// Start the producer task with parallel workers
+fn spawn_producer(
+    args: &Args,
+    state: Arc<AppState>,
+    cmd_rx: mpsc::Receiver<ProducerCommand>,
+    event_tx: mpsc::Sender<ProducerEvent>,
+) {
+    // Configure producer
+    let bpm_mode = args
+        .bpm
+        .map(BpmMode::Fixed)
+        .unwrap_or(BpmMode::Auto {
+            min: args.bpm_min,
+            max: args.bpm_max,
+        });
+
+    let producer_config = ProducerConfig {
+        search: args.search.clone(),
+        region: args.region.clone(),
+        listen_seconds: args.listen_seconds,
+        station_change_seconds: args.station_change_seconds,
+        bars: args.bars,
+        meter: args.meter_mode(),
+        bpm_mode,
+        key_mode: args.key.unwrap_or(KeyMode::Original),
+        target_lufs: args.target_lufs,
+        true_peak_ceiling: args.true_peak_ceiling,
+        harmonic: args.harmonic,
+    };
+
+    let producer = Producer::with_params(
+        producer_config,
+        state,
+        cmd_rx,
+        event_tx,
+        args.rate_limit_ms,
+        args.cache_dir.clone(),
+        args.bpm_min,
+        args.bpm_max,
+    );
+    tokio::spawn(async move {
+        producer.run().await;
+    });
+}
@@ -0,0 +1,164 @@
+use crate::audio::SAMPLE_RATE;
+
+/// Tempo difference still treated as the session tempo (rounding of detected BPMs)
+const TEMPO_TOLERANCE_BPM: f64 = 0.01;
+
+/// Session tempo and grid of the master clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionTempo {
+    pub bpm: f64,
+    pub beats_per_bar: u8,
+    /// Frame of the first downbeat
+    origin: u64,
+}
+
+impl SessionTempo {
+    /// Whether a clip at this tempo and meter fits the grid
+    pub fn matches(&self, bpm: f64, beats_per_bar: u8) -> bool {
+        self.beats_per_bar == beats_per_bar && (self.bpm - bpm).abs() < TEMPO_TOLERANCE_BPM
+    }
+
+    fn frames_per_beat(&self) -> f64 {
+        60.0 / self.bpm * SAMPLE_RATE as f64
+    }
+
+    fn frames_per_bar(&self) -> f64 {
+        self.frames_per_beat() * self.beats_per_bar as f64
+    }
+
+    /// Frame of bar `index`, computed from the origin so rounding never accumulates
+    fn bar_frame(&self, index: i64) -> u64 {
+        (self.origin as i64 + (index as f64 * self.frames_per_bar()).round() as i64).max(0) as u64
+    }
+
+    /// Bar number (fractional) at a frame
+    fn bars_at(&self, frame: u64) -> f64 {
+        (frame as f64 - self.origin as f64) / self.frames_per_bar()
+    }
+}
+
+/// Sample-accurate master transport
+///
+/// Counts output frames and lays a bar grid over them at the session tempo,
+/// so clip starts can be scheduled on exact bar boundaries no matter how
+/// long the clips before them were.
+#[derive(Debug, Clone, Default)]
+pub struct MasterClock {
+    /// Output frames rendered so far
+    frame: u64,
+    /// Tempo grid, set when the first clip starts (or explicitly)
+    tempo: Option<SessionTempo>,
+}
+
+impl MasterClock {
+    /// Current frame
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Move on by one frame
+    pub fn tick(&mut self) {
+        self.frame += 1;
+    }
+
+    pub fn tempo(&self) -> Option<SessionTempo> {
+        self.tempo
+    }
+
+    /// Set the session tempo with a downbeat at the current frame
+    pub fn set_tempo(&mut self, bpm: f64, beats_per_bar: u8) {
+        if bpm > 0.0 && beats_per_bar > 0 {
+            self.tempo = Some(SessionTempo {
+                bpm,
+                beats_per_bar,
+                origin: self.frame,
+            });
+        }
+    }
+
+    /// Forget the session tempo (the next clip to start sets a new one)
+    pub fn clear_tempo(&mut self) {
+        self.tempo = None;
+    }
+
+    /// Whether the current frame is a downbeat (always true without a tempo)
+    pub fn on_bar(&self) -> bool {
+        self.tempo.is_none_or(|tempo| {
+            let bar = tempo.bars_at(self.frame).round() as i64;
+            tempo.bar_frame(bar) == self.frame
+        })
+    }
+
+    /// Bar boundary nearest to `frame`, but after `after`
+    pub fn nearest_bar(&self, frame: u64, after: u64) -> u64 {
+        let Some(tempo) = self.tempo else {
+            return frame.max(after + 1);
+        };
+
+        let mut bar = tempo.bars_at(frame).round() as i64;
+        while tempo.bar_frame(bar) <= after {
+            bar += 1;
+        }
+        tempo.bar_frame(bar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advance(clock: &mut MasterClock, frames: u64) {
+        for _ in 0..frames {
+            clock.tick();
+        }
+    }
+
+    #[test]
+    fn test_bar_grid_does_not_drift() {
+        // 123 BPM: 93658.5 frames per bar, so each bar rounds differently
+        let mut clock = MasterClock::default();
+        clock.set_tempo(123.0, 4);
+
+        let bar = 4.0 * 60.0 / 123.0 * SAMPLE_RATE as f64;
+        for n in [1u64, 10, 100, 1000] {
+            let expected = (n as f64 * bar).round() as u64;
+            assert_eq!(clock.nearest_bar(expected + 1_000, 0), expected);
+        }
+    }
+
+    #[test]
+    fn test_nearest_bar_is_after_minimum() {
+        let mut clock = MasterClock::default();
+        advance(&mut clock, 100);
+        clock.set_tempo(120.0, 4);
+
+        // Bars are 96000 frames long, starting at frame 100
+        assert_eq!(clock.nearest_bar(96_000, 0), 96_100);
+        assert_eq!(clock.nearest_bar(130_000, 0), 96_100);
+        assert_eq!(clock.nearest_bar(150_000, 0), 192_100);
+        assert_eq!(clock.nearest_bar(96_000, 100_000), 192_100);
+    }
+
+    #[test]
+    fn test_downbeat() {
+        let mut clock = MasterClock::default();
+        assert!(clock.on_bar());
+
+        clock.set_tempo(120.0, 3);
+        assert!(clock.on_bar());
+        advance(&mut clock, 24_000 * 4);
+        assert!(!clock.on_bar());
+        advance(&mut clock, 24_000 * 2);
+        assert!(clock.on_bar());
+    }
+
+    #[test]
+    fn test_tempo_matches() {
+        let mut clock = MasterClock::default();
+        clock.set_tempo(120.0, 4);
+        let tempo = clock.tempo().unwrap();
+        assert!(tempo.matches(120.0, 4));
+        assert!(!tempo.matches(120.0, 3));
+        assert!(!tempo.matches(126.0, 4));
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use rodio::cpal::traits::{DeviceTrait, HostTrait};
 use rodio::{OutputStream, OutputStreamHandle, Sink};
 use tracing::{debug, info, instrument, warn};
@@ -5,10 +7,15 @@ use tracing::{debug, info, instrument, warn};
 use crate::audio::LoopBuffer;
 use crate::error::PlaybackError;
 
-use super::source::OneShotSource;
+use super::limiter::{Limiter, LimiterMeter, LimiterSettings};
+use super::mixer::{deck_mixer, MixerHandle};
+use super::output::{OutputBackend, RealtimeOutput};
+use super::position::PositionSnapshot;
+use super::recorder::{RecordTap, Recorder, Tap};
+use super::transition::TransitionStyle;
 
 /// Audio device information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AudioDevice {
     pub name: String,
     #[allow(dead_code)]
@@ -42,36 +49,43 @@ pub fn list_audio_devices() -> Vec<AudioDevice> {
     devices
 }
 
-/// Get the default device index
-pub fn default_device_index() -> usize {
-    let host = rodio::cpal::default_host();
-    if let Some(default) = host.default_output_device() {
-        if let Ok(default_name) = default.name() {
-            if let Ok(devices) = host.output_devices() {
-                for (index, device) in devices.enumerate() {
-                    if let Ok(name) = device.name() {
-                        if name == default_name {
-                            return index;
-                        }
-                    }
-                }
-            }
-        }
-    }
-    0
+/// Find an output device by name: an exact (case-insensitive) match, else
+/// the first device whose name contains `name`
+pub fn find_device<'a>(devices: &'a [AudioDevice], name: &str) -> Option<&'a AudioDevice> {
+    let name = name.to_lowercase();
+    devices
+        .iter()
+        .find(|device| device.name.to_lowercase() == name)
+        .or_else(|| devices.iter().find(|device| device.name.to_lowercase().contains(&name)))
+}
+
+/// Get the default device name
+pub fn default_device_name() -> Option<String> {
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.name().ok())
 }
 
 /// Playback engine managing audio output
+///
+/// Clips are appended to a two-deck mixer whose output runs through the
+/// master limiter into a single sink: mixer -> limiter -> tap -> sink -> device.
+/// The tap hands the final stream to a session recorder while one is connected.
 pub struct PlaybackEngine {
-    /// Keep the stream alive (dropping it stops audio)
-    _stream: OutputStream,
-    /// Handle for creating sinks
-    _stream_handle: OutputStreamHandle,
-    /// Audio sink for playback control
+    /// Keep the output alive (dropping it stops audio)
+    output: EngineOutput,
+    /// Audio sink playing the master chain (pause and volume control)
     sink: Sink,
-    /// Current device index
-    #[allow(dead_code)]
-    device_index: usize,
+    /// Control side of the deck mixer
+    mixer: MixerHandle,
+    /// Gain reduction meter of the master limiter
+    limiter_meter: Arc<LimiterMeter>,
+    /// Recording slot on the master output
+    record_tap: RecordTap,
+    /// Master limiter settings (reapplied when the device changes)
+    limiter: LimiterSettings,
+    /// Name of the device playing (empty for null and file outputs)
+    device_name: String,
 }
 
 impl PlaybackEngine {
@@ -79,66 +93,123 @@ impl PlaybackEngine {
     #[allow(dead_code)]
     #[instrument]
     pub fn new() -> Result<Self, PlaybackError> {
-        Self::with_device(None)
+        Self::with_device(None, LimiterSettings::default())
+    }
+
+    /// Create a new playback engine on a device (by name, or the default one)
+    #[instrument]
+    pub fn with_device(device: Option<&str>, limiter: LimiterSettings) -> Result<Self, PlaybackError> {
+        Self::with_output(&OutputBackend::Device, device, limiter)
     }
 
-    /// Create a new playback engine with a specific device
+    /// Create a new playback engine on exactly the named device
+    ///
+    /// Unlike [`Self::with_device`] this fails when the device is missing or
+    /// can't be opened instead of playing on the default device.
     #[instrument]
-    pub fn with_device(device_index: Option<usize>) -> Result<Self, PlaybackError> {
-        info!("Initializing audio output");
-
-        let host = rodio::cpal::default_host();
-
-        let (stream, stream_handle, actual_index) = if let Some(index) = device_index {
-            // Try to get the specific device
-            if let Ok(mut devices) = host.output_devices() {
-                if let Some(device) = devices.nth(index) {
-                    let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
-                    info!(device = %name, index, "Using selected audio device");
-
-                    match OutputStream::try_from_device(&device) {
-                        Ok((stream, handle)) => (stream, handle, index),
-                        Err(e) => {
-                            warn!(error = %e, "Failed to open selected device, using default");
-                            let (stream, handle) = OutputStream::try_default()
-                                .map_err(|e| PlaybackError::Device(format!("Failed to open audio device: {}", e)))?;
-                            (stream, handle, default_device_index())
-                        }
-                    }
-                } else {
-                    warn!(index, "Device index out of range, using default");
-                    let (stream, handle) = OutputStream::try_default()
-                        .map_err(|e| PlaybackError::Device(format!("Failed to open audio device: {}", e)))?;
-                    (stream, handle, default_device_index())
-                }
-            } else {
-                let (stream, handle) = OutputStream::try_default()
-                    .map_err(|e| PlaybackError::Device(format!("Failed to open audio device: {}", e)))?;
-                (stream, handle, default_device_index())
+    pub fn with_device_strict(device: &str, limiter: LimiterSettings) -> Result<Self, PlaybackError> {
+        info!(%device, "Initializing audio output");
+        let (output, sink, device_name) = open_device_strict(device)?;
+        Ok(Self::from_output(output, sink, device_name, limiter))
+    }
+
+    /// Create a new playback engine on an output backend
+    ///
+    /// `device` names the device for [`OutputBackend::Device`] (see
+    /// [`find_device`]); the other backends ignore it.
+    #[instrument]
+    pub fn with_output(
+        backend: &OutputBackend,
+        device: Option<&str>,
+        limiter: LimiterSettings,
+    ) -> Result<Self, PlaybackError> {
+        info!(%backend, "Initializing audio output");
+
+        let (output, sink, device_name) = match backend {
+            OutputBackend::Device => open_device(device)?,
+            OutputBackend::NamedDevice(name) => open_device(Some(name))?,
+            OutputBackend::Null => {
+                let (sink, queue) = Sink::new_idle();
+                let output = RealtimeOutput::null(queue)?;
+                (EngineOutput::Realtime { _output: output }, sink, String::new())
+            }
+            OutputBackend::File(path) => {
+                let (sink, queue) = Sink::new_idle();
+                let output = RealtimeOutput::file(queue, path)?;
+                (EngineOutput::Realtime { _output: output }, sink, String::new())
             }
-        } else {
-            let (stream, handle) = OutputStream::try_default()
-                .map_err(|e| PlaybackError::Device(format!("Failed to open audio device: {}", e)))?;
-            (stream, handle, default_device_index())
         };
+        Ok(Self::from_output(output, sink, device_name, limiter))
+    }
 
-        let sink = Sink::try_new(&stream_handle)
-            .map_err(|e| PlaybackError::Device(format!("Failed to create audio sink: {}", e)))?;
+    /// Build the master chain on an opened output
+    fn from_output(output: EngineOutput, sink: Sink, device_name: String, limiter: LimiterSettings) -> Self {
+        // Master chain: the mixer keeps the limiter fed with silence when idle
+        let (mixer, mixer_output) = deck_mixer();
+        let limiter_meter = Arc::new(LimiterMeter::default());
+        let record_tap = RecordTap::default();
+        sink.append(Tap::new(
+            Limiter::new(mixer_output, limiter, Arc::clone(&limiter_meter)),
+            record_tap.clone(),
+        ));
+
+        debug!(ceiling_db = limiter.ceiling_db, release_ms = limiter.release_ms, "Audio output initialized");
+
+        Self {
+            output,
+            sink,
+            mixer,
+            limiter_meter,
+            record_tap,
+            limiter,
+            device_name,
+        }
+    }
 
-        debug!("Audio output initialized");
+    /// Move playback to another device (by name, or the default one)
+    ///
+    /// The mixer carries over, so the playing clip, the queue and the
+    /// history continue on the new device, as do volume, pause and any
+    /// recording.
+    #[instrument(skip(self))]
+    pub fn switch_device(&mut self, device: Option<&str>) -> Result<(), PlaybackError> {
+        let (output, sink, device_name) = open_device(device)?;
+        sink.set_volume(self.sink.volume());
+        if self.sink.is_paused() {
+            sink.pause();
+        }
 
-        Ok(Self {
-            _stream: stream,
-            _stream_handle: stream_handle,
-            sink,
-            device_index: actual_index,
-        })
+        // Stop the old stream before the new one starts pulling from the shared mixer
+        drop(std::mem::replace(&mut self.sink, sink));
+        drop(std::mem::replace(&mut self.output, output));
+        self.sink.append(Tap::new(
+            Limiter::new(self.mixer.source(), self.limiter, Arc::clone(&self.limiter_meter)),
+            self.record_tap.clone(),
+        ));
+
+        info!(from = %self.device_name, to = %device_name, "Switched audio device");
+        self.device_name = device_name;
+        Ok(())
     }
 
-    /// Get current device index
-    #[allow(dead_code)]
-    pub fn device_index(&self) -> usize {
-        self.device_index
+    /// Name of the device playing
+    pub fn device_name(&self) -> &str {
+        &self.device_name
+    }
+
+    /// Gain reduction meter of the master limiter
+    pub fn limiter_meter(&self) -> Arc<LimiterMeter> {
+        Arc::clone(&self.limiter_meter)
+    }
+
+    /// Start sending the master output to a recorder
+    pub fn connect_recorder(&self, recorder: &Recorder) {
+        self.record_tap.connect(recorder);
+    }
+
+    /// Stop sending the master output to the recorder
+    pub fn disconnect_recorder(&self) {
+        self.record_tap.disconnect();
     }
 
     /// Start playing an audio buffer (plays once, no looping)
@@ -156,24 +227,20 @@ impl PlaybackEngine {
             sample_count,
             max_sample,
             rms,
+            loudness_lufs = ?buffer.loop_info.loudness_lufs,
+            gain_db = buffer.loop_info.gain_db,
             "Starting playback"
         );
 
-        // Create the one-shot source
-        let source = OneShotSource::new(buffer);
-
         // Clear any existing playback
-        self.sink.clear();
-
-        // Start the new source
-        self.sink.append(source);
+        self.mixer.clear();
 
-        // Ensure sink is playing (clear() may pause it)
-        self.sink.play();
+        // Start the new source (a paused sink stays paused until resumed)
+        self.mixer.append(buffer);
 
         // Log sink state
         info!(
-            sink_empty = self.sink.empty(),
+            queued = self.mixer.len(),
             sink_paused = self.sink.is_paused(),
             sink_volume = self.sink.volume(),
             "Playback started"
@@ -190,60 +257,154 @@ impl PlaybackEngine {
             "Queueing next clip"
         );
 
-        let source = OneShotSource::new(buffer);
-        self.sink.append(source);
+        self.mixer.append(buffer);
+    }
 
-        // Ensure sink is playing
-        self.sink.play();
+    /// Get the buffer that is currently playing
+    pub fn current_buffer(&self) -> Option<LoopBuffer> {
+        self.mixer.current_buffer()
+    }
+
+    /// Replace the currently playing clip, keeping the rest of the queue
+    ///
+    /// The replacement must have been cut from the same source audio as the
+    /// current clip; returns false (and changes nothing) if playback has
+    /// moved on in the meantime.
+    #[instrument(skip(self, buffer))]
+    pub fn replace_current(&mut self, buffer: LoopBuffer) -> bool {
+        match self.mixer.current_buffer() {
+            Some(current) if current.same_source(&buffer) => {}
+            _ => {
+                warn!("Current clip changed, dropping replacement");
+                return false;
+            }
+        }
+
+        info!(bpm = buffer.loop_info.bpm, "Replacing current clip");
+        self.mixer.replace_current(buffer);
+
+        true
     }
 
     /// Check if audio is currently playing
     #[allow(dead_code)]
     pub fn is_playing(&self) -> bool {
-        !self.sink.empty() && !self.sink.is_paused()
+        !self.is_finished() && !self.sink.is_paused()
     }
 
-    /// Check if playback has finished (sink is empty)
+    /// Check if playback has finished (no clip left in the queue)
     pub fn is_finished(&self) -> bool {
-        self.sink.empty()
+        self.queue_len() == 0
     }
 
-    /// Get the number of sources queued in the sink
+    /// Get the number of clips queued (including the one playing)
     #[allow(dead_code)]
     pub fn queue_len(&self) -> usize {
-        self.sink.len()
+        self.mixer.len()
     }
 
     /// Pause playback
-    #[allow(dead_code)]
     pub fn pause(&self) {
         debug!("Pausing playback");
         self.sink.pause();
     }
 
     /// Resume playback
-    #[allow(dead_code)]
     pub fn resume(&self) {
         debug!("Resuming playback");
         self.sink.play();
     }
 
+    /// Check if playback is paused
+    pub fn is_paused(&self) -> bool {
+        self.sink.is_paused()
+    }
+
+    /// Loop each following clip until the station dwell time has passed
+    pub fn set_dwell(&self, secs: f32) {
+        self.mixer.set_dwell(secs);
+    }
+
+    /// Clock frame and clip actually playing, as last rendered by the mixer
+    pub fn position(&self) -> PositionSnapshot {
+        self.mixer.position()
+    }
+
+    /// Hold or release the current loop, returning whether it is now held
+    pub fn toggle_hold(&self) -> bool {
+        self.mixer.toggle_hold()
+    }
+
+    /// Set the transition used between clips
+    pub fn set_transition(&self, style: TransitionStyle) {
+        self.mixer.set_transition(style);
+    }
+
+    /// Play a clip from the history again, ahead of the queue
+    ///
+    /// Returns false if the clip is no longer in the history.
+    #[instrument(skip(self))]
+    pub fn replay(&mut self, clip_id: u64) -> bool {
+        info!("Replaying clip from history");
+        self.mixer.replay(clip_id)
+    }
+
+    /// Next clip waiting in the queue (not yet on a deck)
+    pub fn next_queued(&self) -> Option<LoopBuffer> {
+        self.mixer.queued().into_iter().next()
+    }
+
+    /// Whether a clip is still waiting in the queue
+    pub fn is_queued(&self, clip_id: u64) -> bool {
+        self.mixer.is_queued(clip_id)
+    }
+
+    /// IDs of the clips waiting in the queue, next first
+    pub fn queued_ids(&self) -> Vec<u64> {
+        self.mixer.queued().iter().map(|buffer| buffer.id).collect()
+    }
+
+    /// Move a queued clip `offset` places later (negative: earlier)
+    ///
+    /// Returns false if it is no longer queued.
+    pub fn move_queued(&self, clip_id: u64, offset: isize) -> bool {
+        self.mixer.move_queued(clip_id, offset)
+    }
+
+    /// Make a queued clip the next one to play
+    ///
+    /// A transition already under way still lands on its incoming clip.
+    pub fn pin_queued(&self, clip_id: u64) -> bool {
+        self.mixer.move_queued(clip_id, isize::MIN)
+    }
+
+    /// Drop a clip from the queue before it plays
+    ///
+    /// Returns false if it is no longer queued.
+    pub fn remove_queued(&self, clip_id: u64) -> bool {
+        self.mixer.remove(clip_id)
+    }
+
     /// Skip to the next queued source
     #[instrument(skip(self))]
-    pub fn skip_one(&self) {
+    pub fn skip_one(&mut self) {
         info!("Skipping to next clip");
-        self.sink.skip_one();
+        self.mixer.skip();
     }
 
     /// Stop playback
     #[instrument(skip(self))]
     pub fn stop(&mut self) {
         info!("Stopping playback");
-        self.sink.clear();
+        self.mixer.clear();
+    }
+
+    /// Forget the session bar grid, so the next clip starts without waiting for a downbeat
+    pub fn clear_tempo(&self) {
+        self.mixer.clear_tempo();
     }
 
     /// Set playback volume (0.0 to 1.0)
-    #[allow(dead_code)]
     pub fn set_volume(&self, volume: f32) {
         let volume = volume.clamp(0.0, 1.0);
         debug!(volume, "Setting volume");
@@ -251,15 +412,109 @@ impl PlaybackEngine {
     }
 
     /// Get current volume
-    #[allow(dead_code)]
     pub fn volume(&self) -> f32 {
         self.sink.volume()
     }
 }
 
+/// Whatever drives the sink
+enum EngineOutput {
+    /// Audio device stream and the handle the sink was created from
+    Device {
+        _stream: OutputStream,
+        _handle: OutputStreamHandle,
+    },
+    /// Null or file output pulled by a background thread
+    Realtime { _output: RealtimeOutput },
+}
+
+/// Open an audio device by name (the default one if `device` is `None`,
+/// matches nothing or can't be opened), returning its full name
+fn open_device(device: Option<&str>) -> Result<(EngineOutput, Sink, String), PlaybackError> {
+    let opened = device.and_then(|name| {
+        open_named_stream(name)
+            .inspect_err(|e| warn!(error = %e, "Selected device unavailable, using default"))
+            .ok()
+    });
+
+    let (stream, stream_handle, name) = match opened {
+        Some(opened) => opened,
+        None => {
+            let (stream, handle) = OutputStream::try_default()
+                .map_err(|e| PlaybackError::Device(format!("Failed to open audio device: {}", e)))?;
+            (stream, handle, default_device_name().unwrap_or_else(|| "Default".to_string()))
+        }
+    };
+    device_output(stream, stream_handle, name)
+}
+
+/// Open exactly the device matching `name`, failing instead of falling back
+fn open_device_strict(name: &str) -> Result<(EngineOutput, Sink, String), PlaybackError> {
+    let (stream, stream_handle, name) = open_named_stream(name)?;
+    device_output(stream, stream_handle, name)
+}
+
+/// Open the stream of the device matching `name` (see [`find_device`])
+fn open_named_stream(name: &str) -> Result<(OutputStream, OutputStreamHandle, String), PlaybackError> {
+    let name = find_device(&list_audio_devices(), name)
+        .map(|device| device.name.clone())
+        .ok_or_else(|| PlaybackError::Device(format!("No output device matches {}", name)))?;
+    let device = rodio::cpal::default_host()
+        .output_devices()
+        .ok()
+        .and_then(|mut devices| devices.find(|device| device.name().is_ok_and(|device_name| device_name == name)))
+        .ok_or_else(|| PlaybackError::Device(format!("Output device {} disappeared", name)))?;
+    info!(device = %name, "Using selected audio device");
+
+    let (stream, handle) = OutputStream::try_from_device(&device)
+        .map_err(|e| PlaybackError::Device(format!("Failed to open {}: {}", name, e)))?;
+    Ok((stream, handle, name))
+}
+
+/// Sink on an opened device stream
+fn device_output(
+    stream: OutputStream,
+    stream_handle: OutputStreamHandle,
+    name: String,
+) -> Result<(EngineOutput, Sink, String), PlaybackError> {
+    let sink = Sink::try_new(&stream_handle)
+        .map_err(|e| PlaybackError::Device(format!("Failed to create audio sink: {}", e)))?;
+
+    Ok((
+        EngineOutput::Device {
+            _stream: stream,
+            _handle: stream_handle,
+        },
+        sink,
+        name,
+    ))
+}
+
 impl Drop for PlaybackEngine {
     fn drop(&mut self) {
         debug!("Dropping playback engine");
         self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_device_prefers_exact_name() {
+        let devices: Vec<AudioDevice> = ["USB Audio Device (hw:2)", "USB Audio", "Built-in Output"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| AudioDevice {
+                name: name.to_string(),
+                index,
+            })
+            .collect();
+
+        assert_eq!(find_device(&devices, "usb audio").map(|d| d.index), Some(1));
+        assert_eq!(find_device(&devices, "hw:2").map(|d| d.index), Some(0));
+        assert_eq!(find_device(&devices, "built-in").map(|d| d.index), Some(2));
+        assert!(find_device(&devices, "HDMI").is_none());
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rodio::Source;
+
+/// Look-ahead time of the limiter (also its latency)
+const LOOKAHEAD_SECS: f32 = 0.005;
+
+/// Limiter configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimiterSettings {
+    /// Output ceiling (dBFS)
+    pub ceiling_db: f32,
+    /// Time for gain reduction to recover (milliseconds)
+    pub release_ms: f32,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            ceiling_db: -0.3,
+            release_ms: 150.0,
+        }
+    }
+}
+
+/// Gain reduction meter shared between the audio thread and the TUI
+///
+/// The limiter records the deepest reduction it applies; readers take (and
+/// reset) the maximum since their last read.
+#[derive(Debug, Default)]
+pub struct LimiterMeter {
+    /// Peak gain reduction in dB (positive), stored as f32 bits
+    reduction_db: AtomicU32,
+}
+
+impl LimiterMeter {
+    /// Record a gain reduction (dB, positive)
+    fn record(&self, reduction_db: f32) {
+        // Bit patterns of non-negative floats sort like the floats themselves
+        self.reduction_db
+            .fetch_max(reduction_db.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Take the peak gain reduction (dB) since the last call
+    pub fn take(&self) -> f32 {
+        f32::from_bits(self.reduction_db.swap(0, Ordering::Relaxed))
+    }
+}
+
+/// Look-ahead brickwall limiter
+///
+/// The required gain of each frame is held for the look-ahead window,
+/// released exponentially and then smoothed with a moving average of the
+/// same length. Because the audio is delayed by the window length, the gain
+/// has fully ramped down by the time a peak reaches the output, so no sample
+/// exceeds the ceiling and the attack stays click-free.
+pub struct Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    input: S,
+    channels: usize,
+    sample_rate: u32,
+    /// Linear ceiling
+    ceiling: f32,
+    /// Per-frame release smoothing coefficient
+    release_coeff: f32,
+    /// Look-ahead window in frames
+    lookahead: usize,
+    /// Frame index of the next input frame
+    frame_index: usize,
+    /// Sliding minimum of required gains: (frame index, gain), increasing gain
+    required: VecDeque<(usize, f32)>,
+    /// Held gain after release smoothing
+    released: f32,
+    /// Moving-average window of released gains and its sum
+    smoothing: VecDeque<f32>,
+    smoothing_sum: f64,
+    /// Delayed interleaved input samples
+    delay: VecDeque<f32>,
+    /// Processed samples waiting to be returned
+    output: VecDeque<f32>,
+    /// Frames of silence still to feed once the input has ended
+    flush_frames: usize,
+    input_done: bool,
+    meter: Arc<LimiterMeter>,
+}
+
+impl<S> Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, settings: LimiterSettings, meter: Arc<LimiterMeter>) -> Self {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate();
+        let lookahead = ((LOOKAHEAD_SECS * sample_rate as f32) as usize).max(1);
+        let release_frames = (settings.release_ms / 1000.0 * sample_rate as f32).max(1.0);
+
+        Self {
+            input,
+            channels,
+            sample_rate,
+            ceiling: 10f32.powf(settings.ceiling_db / 20.0),
+            release_coeff: (-1.0 / release_frames).exp(),
+            lookahead,
+            frame_index: 0,
+            required: VecDeque::new(),
+            released: 1.0,
+            smoothing: std::iter::repeat_n(1.0, lookahead).collect(),
+            smoothing_sum: lookahead as f64,
+            delay: std::iter::repeat_n(0.0, (lookahead - 1) * channels).collect(),
+            output: VecDeque::new(),
+            flush_frames: lookahead - 1,
+            input_done: false,
+            meter,
+        }
+    }
+
+    /// Run one frame through the limiter, queueing one delayed output frame
+    fn process_frame(&mut self, frame: &[f32]) {
+        let peak = frame.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+        let required = if peak > self.ceiling { self.ceiling / peak } else { 1.0 };
+
+        // Minimum required gain over the look-ahead window
+        while self.required.back().is_some_and(|&(_, gain)| gain >= required) {
+            self.required.pop_back();
+        }
+        self.required.push_back((self.frame_index, required));
+        while self
+            .required
+            .front()
+            .is_some_and(|&(index, _)| index + self.lookahead <= self.frame_index)
+        {
+            self.required.pop_front();
+        }
+        let held = self.required.front().map_or(1.0, |&(_, gain)| gain);
+        self.frame_index += 1;
+
+        // Instant attack, exponential release
+        self.released = if held < self.released {
+            held
+        } else {
+            held + (self.released - held) * self.release_coeff
+        };
+
+        // Moving average spreads the attack over the look-ahead window
+        self.smoothing.push_back(self.released);
+        self.smoothing_sum += self.released as f64;
+        if let Some(old) = self.smoothing.pop_front() {
+            self.smoothing_sum -= old as f64;
+        }
+        let gain = (self.smoothing_sum / self.lookahead as f64).min(1.0) as f32;
+
+        if gain < 1.0 {
+            self.meter.record(-20.0 * gain.max(1e-6).log10());
+        }
+
+        self.delay.extend(frame.iter().copied());
+        for _ in 0..self.channels {
+            let sample = self.delay.pop_front().unwrap_or(0.0) * gain;
+            self.output.push_back(sample.clamp(-self.ceiling, self.ceiling));
+        }
+    }
+
+    /// Pull the next input frame (or silence while flushing after the input ends)
+    fn fill(&mut self) -> bool {
+        let mut frame = Vec::with_capacity(self.channels);
+
+        if !self.input_done {
+            for _ in 0..self.channels {
+                match self.input.next() {
+                    Some(sample) => frame.push(sample),
+                    None => break,
+                }
+            }
+            if frame.is_empty() {
+                self.input_done = true;
+            }
+        }
+
+        if self.input_done {
+            if self.flush_frames == 0 {
+                return false;
+            }
+            self.flush_frames -= 1;
+        }
+
+        frame.resize(self.channels, 0.0);
+        self.process_frame(&frame);
+        true
+    }
+}
+
+impl<S> Iterator for Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.output.is_empty() && !self.fill() {
+            return None;
+        }
+        self.output.pop_front()
+    }
+}
+
+impl<S> Source for Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn limit(samples: Vec<f32>, settings: LimiterSettings) -> (Vec<f32>, Arc<LimiterMeter>) {
+        let meter = Arc::new(LimiterMeter::default());
+        let input = SamplesBuffer::new(2, 48000, samples);
+        let output = Limiter::new(input, settings, Arc::clone(&meter)).collect();
+        (output, meter)
+    }
+
+    #[test]
+    fn test_output_never_exceeds_ceiling() {
+        // Loud sine with sudden spikes well over full scale
+        let mut samples: Vec<f32> = (0..48000)
+            .flat_map(|i| {
+                let value = (2.0 * std::f32::consts::PI * 100.0 * i as f32 / 48000.0).sin() * 0.8;
+                [value, value]
+            })
+            .collect();
+        for spike in (1000..samples.len()).step_by(7919) {
+            samples[spike] = 1.8;
+        }
+
+        let settings = LimiterSettings {
+            ceiling_db: -1.0,
+            release_ms: 50.0,
+        };
+        let (output, meter) = limit(samples.clone(), settings);
+        let ceiling = 10f32.powf(-1.0 / 20.0);
+
+        // Input plus the flushed look-ahead delay
+        let delay = ((LOOKAHEAD_SECS * 48000.0) as usize - 1) * 2;
+        assert_eq!(output.len(), samples.len() + delay);
+        let peak = output.iter().fold(0.0f32, |a, &b| a.max(b.abs()));
+        assert!(peak <= ceiling + 1e-6, "Peak {} over ceiling {}", peak, ceiling);
+        assert!(meter.take() > 6.0, "Meter did not register the spikes");
+        assert_eq!(meter.take(), 0.0);
+    }
+
+    #[test]
+    fn test_quiet_audio_passes_unchanged() {
+        let samples: Vec<f32> = (0..4800).map(|i| (i as f32 * 0.01).sin() * 0.5).collect();
+        let (output, meter) = limit(samples.clone(), LimiterSettings::default());
+
+        // Delayed by the look-ahead, otherwise identical
+        let delay = ((LOOKAHEAD_SECS * 48000.0) as usize - 1) * 2;
+        for (out, input) in output[delay..].iter().zip(&samples) {
+            assert!((out - input).abs() < 1e-6);
+        }
+        assert_eq!(meter.take(), 0.0);
+    }
+}
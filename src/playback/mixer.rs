@@ -0,0 +1,908 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use rodio::Source;
+
+use crate::audio::{LoopBuffer, CHANNELS, SAMPLE_RATE};
+
+use super::clock::MasterClock;
+use super::position::{ClipPosition, PlaybackPosition, PositionSnapshot};
+use super::source::OneShotSource;
+use super::transition::{lead_frames, Transition, TransitionStyle};
+
+/// Length of the quick crossfade used when skipping a clip
+const SKIP_FADE_SECS: f32 = 0.05;
+
+/// Number of played clips kept for replaying
+pub const HISTORY_CLIPS: usize = 10;
+
+/// Frames rendered each time the mixer locks its shared state
+const BLOCK_FRAMES: usize = 256;
+
+/// Interleaved frame width
+const FRAME: usize = CHANNELS as usize;
+
+/// One deck: a clip looped until its slot on the bar grid ends
+struct Deck {
+    buffer: LoopBuffer,
+    source: OneShotSource,
+    /// Frames of looping the clip was loaded for (whole passes covering the dwell time)
+    planned_frames: usize,
+    /// Master clock frames where the deck started and where it hands over,
+    /// set once it starts playing
+    span: Option<(u64, u64)>,
+}
+
+impl Deck {
+    /// Load a clip, looping it for at least `dwell_frames`
+    fn new(buffer: LoopBuffer, dwell_frames: usize) -> Self {
+        let frames = buffer.frame_count().max(1);
+        let passes = dwell_frames.div_ceil(frames).max(1);
+        Self {
+            source: OneShotSource::seamless(buffer.clone()),
+            buffer,
+            planned_frames: passes * frames,
+            span: None,
+        }
+    }
+
+    /// Start playing now, handing over on the bar boundary nearest the planned end
+    fn start(&mut self, clock: &MasterClock) {
+        let start = clock.frame();
+        let end = clock.nearest_bar(start + self.planned_frames as u64, start);
+        self.span = Some((start, end));
+    }
+
+    /// Keep looping for another pass (snapped back onto the bar grid)
+    fn extend(&mut self, clock: &MasterClock) {
+        if let Some((start, end)) = self.span {
+            let target = end + self.buffer.frame_count() as u64;
+            self.span = Some((start, clock.nearest_bar(target, end)));
+        }
+    }
+
+    /// Playhead within the current pass, in frames
+    fn position(&self) -> usize {
+        self.buffer.frame_count() - self.source.remaining_frames()
+    }
+
+    /// Frames left until the deck hands over
+    fn remaining_frames(&self, now: u64) -> usize {
+        self.span
+            .map_or(self.planned_frames, |(_, end)| end.saturating_sub(now) as usize)
+    }
+
+    fn is_finished(&self, now: u64) -> bool {
+        self.span.is_some_and(|(_, end)| now >= end)
+    }
+
+    /// Write the next frame into `out`, looping the clip
+    fn read_frame(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = match self.source.next() {
+                Some(value) => value,
+                // The seam is already crossfaded, so the loop restarts without a fade
+                None => {
+                    self.source = OneShotSource::seamless(self.buffer.clone());
+                    self.source.next().unwrap_or(0.0)
+                }
+            };
+        }
+    }
+}
+
+/// State shared between the mixer source and its handle
+#[derive(Default)]
+struct MixerState {
+    /// Clips waiting for a deck
+    queue: VecDeque<LoopBuffer>,
+    /// Deck playing the current clip
+    current: Option<Deck>,
+    /// Deck taking over from the current one during a transition
+    incoming: Option<Deck>,
+    /// Transition in progress between the two decks
+    transition: Option<Transition>,
+    /// Style used for transitions between clips
+    style: TransitionStyle,
+    /// Minimum time each clip loops for (station dwell time)
+    dwell_frames: usize,
+    /// The current clip was skipped and is on its way out
+    skipping: bool,
+    /// A clip was replayed during a transition: skip to it once that finishes
+    replay_pending: bool,
+    /// Keep looping the current clip instead of moving on
+    held: bool,
+    /// Sample-accurate transport the clip starts are scheduled on
+    clock: MasterClock,
+    /// Position published for the main loop and TUI
+    position: Arc<PlaybackPosition>,
+    /// Clips that have played, oldest first
+    history: VecDeque<LoopBuffer>,
+}
+
+impl MixerState {
+    /// Keep a deck's clip in the history once it has played
+    ///
+    /// Only the loop is kept: the decoded capture it was cut from is by far
+    /// the larger part and is only needed to re-quantize the playing clip.
+    fn retire(&mut self, deck: Deck) {
+        // Never started, or queued to play again
+        if deck.span.is_none() || self.queue.iter().any(|buffer| buffer.id == deck.buffer.id) {
+            return;
+        }
+        self.history.retain(|buffer| buffer.id != deck.buffer.id);
+        self.history.push_back(deck.buffer.without_source());
+        if self.history.len() > HISTORY_CLIPS {
+            self.history.pop_front();
+        }
+    }
+
+    /// Quickly crossfade from the current clip to the next one
+    fn skip(&mut self) {
+        if self.skipping || self.current.is_none() {
+            return;
+        }
+
+        if self.transition.is_none() {
+            let length = (SKIP_FADE_SECS * SAMPLE_RATE as f32) as usize;
+            self.start_transition(Transition::crossfade(length));
+        }
+        self.skipping = true;
+    }
+
+    /// Skip from the clip that just came in to the replayed clip at the
+    /// front of the queue, queueing the skipped clip again right behind it
+    fn skip_to_replayed(&mut self) {
+        let Some(skipped) = self.current.as_ref().map(|deck| deck.buffer.clone()) else {
+            return;
+        };
+        self.skip();
+        self.queue.push_front(skipped);
+    }
+
+    /// Hand over from the current deck to the next clip
+    fn start_transition(&mut self, transition: Transition) {
+        if self.incoming.is_none() {
+            let dwell_frames = self.dwell_frames;
+            self.incoming = self.queue.pop_front().map(|next| Deck::new(next, dwell_frames));
+        }
+        self.transition = Some(transition);
+    }
+
+    /// Start the transition so the next clip lands on the current deck's end
+    fn schedule_transition(&mut self) {
+        if self.transition.is_some() || self.held {
+            return;
+        }
+        let (Some(current), Some(next)) = (&self.current, self.queue.front()) else {
+            return;
+        };
+
+        let remaining = current.remaining_frames(self.clock.frame());
+        let lead = lead_frames(self.style, &current.buffer, next);
+        if remaining <= lead {
+            let transition = Transition::new(self.style, &current.buffer, current.position(), Some(next), remaining);
+            self.start_transition(transition);
+        }
+    }
+
+    /// Start a deck on the clock
+    ///
+    /// A clip starts on its own downbeat, so the session grid is re-derived
+    /// from it when there is none yet, when its tempo or meter differ (the
+    /// BPM setting changed), or when it starts between bars of the old grid.
+    fn start_deck(clock: &mut MasterClock, deck: &mut Deck) {
+        if deck.span.is_some() {
+            return;
+        }
+        let info = &deck.buffer.loop_info;
+        let (bpm, beats_per_bar) = (info.bpm as f64, info.beats_per_bar);
+        let on_grid = clock.on_bar() && clock.tempo().is_some_and(|tempo| tempo.matches(bpm, beats_per_bar));
+        if !on_grid {
+            clock.set_tempo(bpm, beats_per_bar);
+        }
+        deck.start(clock);
+    }
+
+    /// Render one interleaved frame
+    fn render_frame(&mut self, out: &mut [f32]) {
+        let now = self.clock.frame();
+
+        if self.held && self.transition.is_none() {
+            if let Some(deck) = self.current.as_mut().filter(|deck| deck.remaining_frames(now) <= 1) {
+                deck.extend(&self.clock);
+            }
+        }
+        self.schedule_transition();
+
+        if self.transition.is_none() {
+            if self.current.as_ref().is_some_and(|deck| deck.is_finished(now)) {
+                if let Some(deck) = self.current.take() {
+                    self.retire(deck);
+                }
+            }
+            // A clip starting from silence waits for the next downbeat
+            if self.current.is_none() && self.clock.on_bar() {
+                let dwell_frames = self.dwell_frames;
+                self.current = self.queue.pop_front().map(|next| Deck::new(next, dwell_frames));
+            }
+        }
+
+        let mut outgoing = [0.0; FRAME];
+        if let Some(deck) = &mut self.current {
+            Self::start_deck(&mut self.clock, deck);
+            if !deck.is_finished(now) {
+                deck.read_frame(&mut outgoing);
+            }
+        }
+
+        match &mut self.transition {
+            Some(transition) => {
+                let mut incoming = [0.0; FRAME];
+                if transition.incoming_started() {
+                    if let Some(deck) = &mut self.incoming {
+                        Self::start_deck(&mut self.clock, deck);
+                        deck.read_frame(&mut incoming);
+                    }
+                }
+                transition.mix_frame(&outgoing, &incoming, out);
+
+                if transition.is_done() {
+                    self.transition = None;
+                    self.skipping = false;
+                    if let Some(deck) = std::mem::replace(&mut self.current, self.incoming.take()) {
+                        self.retire(deck);
+                    }
+                    if std::mem::take(&mut self.replay_pending) {
+                        self.skip_to_replayed();
+                    }
+                }
+            }
+            None => out.copy_from_slice(&outgoing),
+        }
+
+        self.clock.tick();
+    }
+
+    /// The clip the listener is hearing as "now playing"
+    fn playing(&self) -> Option<&Deck> {
+        if self.skipping {
+            self.incoming.as_ref()
+        } else {
+            self.current.as_ref()
+        }
+    }
+
+    /// Publish the clock frame and the playing clip's span
+    fn publish_position(&self) {
+        let clip = self.playing().and_then(|deck| {
+            deck.span.map(|(start, end)| ClipPosition {
+                id: deck.buffer.id,
+                start,
+                end,
+                held: self.held,
+            })
+        });
+        self.position.publish(self.clock.frame(), clip);
+    }
+}
+
+/// Control side of a [`DeckMixer`]
+#[derive(Clone)]
+pub struct MixerHandle {
+    state: Arc<Mutex<MixerState>>,
+    /// Read without locking the mixer state
+    position: Arc<PlaybackPosition>,
+}
+
+impl MixerHandle {
+    /// Queue a clip after the ones already waiting
+    pub fn append(&self, buffer: LoopBuffer) {
+        self.state.lock().queue.push_back(buffer);
+    }
+
+    /// Drop every deck and queued clip
+    ///
+    /// The next clip starts right away at its own tempo.
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.queue.clear();
+        state.current = None;
+        state.incoming = None;
+        state.transition = None;
+        state.skipping = false;
+        state.replay_pending = false;
+    }
+
+    /// Forget the bar grid, so the next clip starts right away on a grid of its own
+    pub fn clear_tempo(&self) {
+        self.state.lock().clock.clear_tempo();
+    }
+
+    /// Set the style of the following transitions
+    pub fn set_transition(&self, style: TransitionStyle) {
+        self.state.lock().style = style;
+    }
+
+    /// Loop each clip a whole number of times until at least `secs` have passed
+    ///
+    /// Applies to clips loaded from now on.
+    pub fn set_dwell(&self, secs: f32) {
+        self.state.lock().dwell_frames = (secs.max(0.0) * SAMPLE_RATE as f32) as usize;
+    }
+
+    /// Hold or release the current loop, returning whether it is now held
+    ///
+    /// While held the playing clip repeats sample-accurately and the queue
+    /// does not advance; a transition already under way still completes and
+    /// the incoming clip is the one held.
+    pub fn toggle_hold(&self) -> bool {
+        let mut state = self.state.lock();
+        state.held = !state.held;
+        state.held
+    }
+
+    /// Quickly crossfade from the current clip to the next one
+    ///
+    /// With nothing queued the current clip simply fades out. A transition
+    /// already under way is left to finish.
+    pub fn skip(&self) {
+        self.state.lock().skip();
+    }
+
+    /// Play a clip from the history again, crossfading to it right away
+    ///
+    /// Returns false if the clip is no longer in the history. A transition
+    /// already under way finishes first, then its incoming clip is skipped
+    /// and goes back to the queue, after the replayed one.
+    pub fn replay(&self, id: u64) -> bool {
+        let mut state = self.state.lock();
+        let Some(index) = state.history.iter().position(|buffer| buffer.id == id) else {
+            return false;
+        };
+        let Some(buffer) = state.history.remove(index) else {
+            return false;
+        };
+
+        state.queue.push_front(buffer);
+        if state.transition.is_some() {
+            state.replay_pending = true;
+        } else {
+            state.skip();
+        }
+        true
+    }
+
+    /// Clips that have played, oldest first
+    #[cfg(test)]
+    pub fn history(&self) -> Vec<LoopBuffer> {
+        self.state.lock().history.iter().cloned().collect()
+    }
+
+    /// Clips waiting for a deck, next first
+    pub fn queued(&self) -> Vec<LoopBuffer> {
+        self.state.lock().queue.iter().cloned().collect()
+    }
+
+    /// Whether a clip is still waiting for a deck
+    pub fn is_queued(&self, id: u64) -> bool {
+        self.state.lock().queue.iter().any(|buffer| buffer.id == id)
+    }
+
+    /// Take a clip out of the queue, returning false if it isn't waiting any more
+    pub fn remove(&self, id: u64) -> bool {
+        let mut state = self.state.lock();
+        let Some(index) = state.queue.iter().position(|buffer| buffer.id == id) else {
+            return false;
+        };
+        state.queue.remove(index);
+        true
+    }
+
+    /// Move a queued clip `offset` places later (negative: earlier)
+    ///
+    /// Returns false if it isn't waiting any more.
+    pub fn move_queued(&self, id: u64, offset: isize) -> bool {
+        let mut state = self.state.lock();
+        let Some(index) = state.queue.iter().position(|buffer| buffer.id == id) else {
+            return false;
+        };
+        let target = index.saturating_add_signed(offset).min(state.queue.len() - 1);
+        if let Some(buffer) = state.queue.remove(index) {
+            state.queue.insert(target, buffer);
+        }
+        true
+    }
+
+    /// A new source rendering this mixer, replacing the one playing
+    ///
+    /// Only one source may be pulled at a time: they share the decks and
+    /// the clock.
+    pub fn source(&self) -> DeckMixer {
+        DeckMixer::new(Arc::clone(&self.state))
+    }
+
+    /// Number of clips not yet finished (including the one playing)
+    pub fn len(&self) -> usize {
+        let state = self.state.lock();
+        let current = usize::from(state.current.is_some() && !state.skipping);
+        state.queue.len() + current + usize::from(state.incoming.is_some())
+    }
+
+    /// The clip currently playing
+    pub fn current_buffer(&self) -> Option<LoopBuffer> {
+        self.state.lock().playing().map(|deck| deck.buffer.clone())
+    }
+
+    /// Latest published playback position
+    pub fn position(&self) -> PositionSnapshot {
+        self.position.snapshot()
+    }
+
+    /// Restart the current deck with a different cut of the same clip
+    ///
+    /// Any transition in progress is cancelled and the next clip goes back
+    /// to the front of the queue.
+    pub fn replace_current(&self, buffer: LoopBuffer) {
+        let mut state = self.state.lock();
+        if state.skipping {
+            state.current = state.incoming.take();
+            state.skipping = false;
+        } else if let Some(incoming) = state.incoming.take() {
+            state.queue.push_front(incoming.buffer);
+        }
+        state.transition = None;
+        state.current = Some(Deck::new(buffer, state.dwell_frames));
+    }
+}
+
+/// Two-deck mixer source
+///
+/// Plays queued clips back to back, handing over from one deck to the other
+/// with the selected transition, timed in beats of the outgoing clip so the
+/// next clip lands on the downbeat. Outputs silence while nothing is queued
+/// and never ends.
+pub struct DeckMixer {
+    state: Arc<Mutex<MixerState>>,
+    /// Rendered interleaved samples
+    block: Vec<f32>,
+    /// Next sample of `block` to return
+    position: usize,
+}
+
+/// Create a mixer source and the handle controlling it
+pub fn deck_mixer() -> (MixerHandle, DeckMixer) {
+    let position = Arc::new(PlaybackPosition::default());
+    let state = Arc::new(Mutex::new(MixerState {
+        position: Arc::clone(&position),
+        ..MixerState::default()
+    }));
+    let mixer = DeckMixer::new(Arc::clone(&state));
+    (MixerHandle { state, position }, mixer)
+}
+
+impl DeckMixer {
+    fn new(state: Arc<Mutex<MixerState>>) -> Self {
+        Self {
+            state,
+            block: vec![0.0; BLOCK_FRAMES * CHANNELS as usize],
+            position: BLOCK_FRAMES * CHANNELS as usize,
+        }
+    }
+
+    fn render_block(&mut self) {
+        let mut state = self.state.lock();
+        for frame in self.block.chunks_mut(CHANNELS as usize) {
+            state.render_frame(frame);
+        }
+        state.publish_position();
+        self.position = 0;
+    }
+}
+
+impl Iterator for DeckMixer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.block.len() {
+            self.render_block();
+        }
+        let sample = self.block[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for DeckMixer {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::LoopInfo;
+    use crate::audio::RawAudioBuffer;
+
+    /// Clip at 120 BPM in 2/4 (one bar per second) with constant left/right levels
+    fn clip_frames(frames: usize, left: f32, right: f32) -> LoopBuffer {
+        let samples = (0..frames).flat_map(|_| [left, right]).collect();
+        LoopBuffer::new(
+            samples,
+            LoopInfo {
+                bars: (frames / SAMPLE_RATE as usize).max(1) as u8,
+                beats_per_bar: 2,
+                ..LoopInfo::for_test(120.0, frames)
+            },
+        )
+    }
+
+    /// One bar clip
+    fn clip(left: f32, right: f32) -> LoopBuffer {
+        clip_frames(SAMPLE_RATE as usize, left, right)
+    }
+
+    /// Two bar clip, long enough for a one bar crossfade
+    fn long_clip(left: f32, right: f32) -> LoopBuffer {
+        clip_frames(2 * SAMPLE_RATE as usize, left, right)
+    }
+
+    fn render(mixer: &mut DeckMixer, frames: usize) -> Vec<[f32; 2]> {
+        (0..frames)
+            .map(|_| [mixer.next().unwrap(), mixer.next().unwrap()])
+            .collect()
+    }
+
+    #[test]
+    fn test_next_clip_starts_on_last_bar() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.append(clip_frames(96_000, 0.5, 0.5));
+        handle.append(clip_frames(96_000, 0.25, 0.25));
+
+        let output = render(&mut mixer, 160_000);
+        // One bar at 120 BPM, starting on the downbeat of the last bar
+        let overlap = 48_000;
+
+        assert!((output[overlap - 1][0] - 0.5).abs() < 1e-6);
+        assert!((output[overlap][0] - 0.5).abs() < 1e-3, "Incoming clip starts silent");
+        let midpoint = (0.5 + 0.25) * std::f32::consts::FRAC_1_SQRT_2;
+        assert!((output[overlap + overlap / 2][0] - midpoint).abs() < 1e-3);
+        assert!((output[96_000][0] - 0.25).abs() < 1e-6);
+
+        // Total length is both clips minus the overlap, then silence
+        assert!((output[144_000 - 1][0] - 0.25).abs() < 1e-6);
+        assert_eq!(output[144_000], [0.0, 0.0]);
+        assert_eq!(handle.len(), 0);
+    }
+
+    #[test]
+    fn test_short_clips_keep_bar_grid() {
+        let (handle, mut mixer) = deck_mixer();
+        // Two bar crossfades between one bar clips: half a bar of overlap
+        // would start each clip mid-bar, so they hand over on the bar line
+        handle.set_transition(TransitionStyle::Crossfade(2));
+        handle.append(clip(0.5, 0.5));
+        handle.append(clip(0.25, 0.25));
+        handle.append(clip(0.125, 0.125));
+
+        let output = render(&mut mixer, 150_000);
+        assert_eq!(output[47_999], [0.5, 0.5]);
+        assert_eq!(output[48_000], [0.25, 0.25]);
+        assert_eq!(output[95_999], [0.25, 0.25]);
+        assert_eq!(output[96_000], [0.125, 0.125]);
+        assert_eq!(output[144_000], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_meter_change_rederives_bar_grid() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        handle.append(clip(0.5, 0.5));
+        // One bar of 3/4: 72000 frames, one and a half bars of the 2/4 grid
+        let samples = (0..72_000).flat_map(|_| [0.25, 0.25]).collect();
+        handle.append(LoopBuffer::new(
+            samples,
+            LoopInfo {
+                beats_per_bar: 3,
+                ..LoopInfo::for_test(120.0, 72_000)
+            },
+        ));
+        handle.append(clip(0.125, 0.125));
+
+        // On the old grid the 3/4 bar would be cut or looped to 96000 frames
+        let output = render(&mut mixer, 150_000);
+        assert_eq!(output[48_000], [0.25, 0.25]);
+        assert_eq!(output[119_999], [0.25, 0.25]);
+        assert_eq!(output[120_000], [0.125, 0.125]);
+    }
+
+    #[test]
+    fn test_clip_ends_snap_to_bar_grid() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        handle.append(clip(0.5, 0.5));
+        // 1.6 bars long: loops, then hands over on the nearest downbeat
+        handle.append(clip_frames(76_800, 0.25, 0.25));
+        handle.append(clip(0.125, 0.125));
+
+        let output = render(&mut mixer, 200_000);
+        assert_eq!(output[48_000], [0.25, 0.25]);
+        assert_eq!(output[48_000 + 76_800], [0.25, 0.25]);
+        assert_eq!(output[143_999], [0.25, 0.25]);
+        assert_eq!(output[144_000], [0.125, 0.125]);
+    }
+
+    #[test]
+    fn test_start_from_silence_waits_for_downbeat() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.append(clip(0.5, 0.5));
+        render(&mut mixer, 60_000);
+
+        // Queued mid-bar after the first clip ended
+        handle.append(clip(0.25, 0.25));
+        let output = render(&mut mixer, 40_000);
+        let downbeat = 96_000 - 60_000;
+        assert_eq!(output[downbeat - 1], [0.0, 0.0]);
+        assert_eq!(output[downbeat], [0.25, 0.25]);
+    }
+
+    #[test]
+    fn test_clear_keeps_bar_grid() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.append(clip(0.5, 0.5));
+        render(&mut mixer, 60_000);
+
+        // Clearing the decks doesn't move the downbeat
+        handle.clear();
+        handle.append(clip(0.25, 0.25));
+        let output = render(&mut mixer, 40_000);
+        let downbeat = 96_000 - 60_000;
+        assert_eq!(output[downbeat - 1], [0.0, 0.0]);
+        assert_eq!(output[downbeat], [0.25, 0.25]);
+
+        // Forgetting the tempo starts the next clip with the next block
+        handle.clear();
+        handle.clear_tempo();
+        handle.append(clip(0.125, 0.125));
+        let output = render(&mut mixer, 2 * BLOCK_FRAMES);
+        assert_eq!(output[2 * BLOCK_FRAMES - 1], [0.125, 0.125]);
+    }
+
+    #[test]
+    fn test_crossfade_keeps_equal_power() {
+        // Outgoing clip on the left channel, incoming on the right
+        let (handle, mut mixer) = deck_mixer();
+        handle.append(long_clip(1.0, 0.0));
+        handle.append(long_clip(0.0, 1.0));
+
+        let output = render(&mut mixer, 96_000);
+        for [left, right] in &output[48_000..96_000] {
+            let power = left * left + right * right;
+            assert!((power - 1.0).abs() < 1e-3, "Power dipped to {}", power);
+        }
+    }
+
+    #[test]
+    fn test_cut_lands_on_downbeat() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        handle.append(clip(0.5, 0.5));
+        handle.append(clip(0.25, 0.25));
+
+        let output = render(&mut mixer, 100_000);
+        assert_eq!(output[47_999], [0.5, 0.5]);
+        assert_eq!(output[48_000], [0.25, 0.25]);
+        assert_eq!(output[95_999], [0.25, 0.25]);
+        assert_eq!(output[96_000], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_loop_repeats_until_dwell_time() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        handle.set_dwell(2.5);
+        handle.append(clip(0.5, 0.5));
+        handle.append(clip(0.25, 0.25));
+
+        // 2.5s of a 1s loop rounds up to three whole passes
+        render(&mut mixer, 1);
+        let info = handle.current_buffer().unwrap().loop_info;
+        let progress = handle.position().loop_progress(&info).unwrap();
+        assert_eq!((progress.pass, progress.passes), (1, 3));
+        assert!((progress.remaining_secs - 3.0).abs() < 0.01);
+
+        render(&mut mixer, 100_000);
+        assert_eq!(handle.position().loop_progress(&info).unwrap().pass, 3);
+
+        let output = render(&mut mixer, 50_000);
+        // Next clip lands right after the third pass
+        assert_eq!(output[144_000 - 100_001 - 1], [0.5, 0.5]);
+        assert_eq!(output[144_000 - 100_001], [0.25, 0.25]);
+    }
+
+    #[test]
+    fn test_hold_keeps_looping_current_clip() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        handle.append(clip(0.5, 0.5));
+        handle.append(clip(0.25, 0.25));
+        assert!(handle.toggle_hold());
+
+        // Well past the end of the first clip, it is still playing
+        let output = render(&mut mixer, 150_000);
+        assert!(output.iter().all(|frame| *frame == [0.5, 0.5]));
+        let info = handle.current_buffer().unwrap().loop_info;
+        let progress = handle.position().loop_progress(&info).unwrap();
+        assert!(progress.held);
+        assert_eq!(progress.pass, 4);
+        assert_eq!(handle.len(), 2);
+
+        // Released, the current pass finishes before moving on
+        assert!(!handle.toggle_hold());
+        let output = render(&mut mixer, 50_000);
+        let switch = 4 * 48_000 - 150_000;
+        assert_eq!(output[switch - 1], [0.5, 0.5]);
+        assert_eq!(output[switch], [0.25, 0.25]);
+    }
+
+    #[test]
+    fn test_len_counts_unfinished_clips() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.append(long_clip(0.5, 0.5));
+        handle.append(long_clip(0.25, 0.25));
+        assert_eq!(handle.len(), 2);
+
+        // During the overlap both clips are still playing
+        render(&mut mixer, 60_000);
+        assert_eq!(handle.len(), 2);
+
+        render(&mut mixer, 40_000);
+        assert_eq!(handle.len(), 1);
+        assert!((handle.current_buffer().unwrap().samples[0] - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_skip_crossfades_to_next_clip() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.append(clip(0.5, 0.5));
+        handle.append(clip(0.25, 0.25));
+        render(&mut mixer, 1_000);
+
+        handle.skip();
+        assert_eq!(handle.len(), 1);
+        assert!((handle.current_buffer().unwrap().samples[0] - 0.25).abs() < 1e-6);
+
+        // Skip fade plus a block of latency
+        let output = render(&mut mixer, 3_000);
+        assert!((output[2_999][0] - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_removed_clip_is_skipped() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        handle.append(clip(0.5, 0.5));
+        let dropped = clip(0.25, 0.25);
+        let dropped_id = dropped.id;
+        handle.append(dropped);
+        handle.append(clip(0.125, 0.125));
+        render(&mut mixer, 100);
+
+        assert!(handle.is_queued(dropped_id));
+        assert!(handle.remove(dropped_id));
+        assert!(!handle.remove(dropped_id));
+        assert_eq!(handle.queued().len(), 1);
+
+        let output = render(&mut mixer, 48_000);
+        assert!((output[47_999][0] - 0.125).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_move_queued_reorders_queue() {
+        let (handle, _mixer) = deck_mixer();
+        let clips: Vec<LoopBuffer> = (0..4).map(|i| clip(i as f32 / 4.0, 0.0)).collect();
+        let ids: Vec<u64> = clips.iter().map(|buffer| buffer.id).collect();
+        clips.into_iter().for_each(|buffer| handle.append(buffer));
+        let order = || handle.queued().iter().map(|buffer| buffer.id).collect::<Vec<_>>();
+
+        assert!(handle.move_queued(ids[1], 1));
+        assert_eq!(order(), vec![ids[0], ids[2], ids[1], ids[3]]);
+        assert!(handle.move_queued(ids[3], isize::MIN));
+        assert_eq!(order(), vec![ids[3], ids[0], ids[2], ids[1]]);
+        assert!(handle.move_queued(ids[0], 10));
+        assert_eq!(order(), vec![ids[3], ids[2], ids[1], ids[0]]);
+        assert!(!handle.move_queued(u64::MAX, 1));
+    }
+
+    #[test]
+    fn test_new_source_continues_playback() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        handle.append(clip(0.5, 0.5));
+        handle.append(clip(0.25, 0.25));
+        render(&mut mixer, 24_000);
+        let before = handle.position();
+
+        // A device switch swaps the source, the decks and clock carry on
+        drop(mixer);
+        let mut mixer = handle.source();
+        let output = render(&mut mixer, 256);
+        assert!((output[255][0] - 0.5).abs() < 1e-6);
+        let after = handle.position();
+        assert_eq!(after.frame, before.frame + 256);
+        assert_eq!(after.clip_id(), before.clip_id());
+        assert_eq!(handle.len(), 2);
+    }
+
+    #[test]
+    fn test_replay_brings_back_played_clip() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        let capture = Arc::new(RawAudioBuffer::new(vec![0.0; 16], SAMPLE_RATE, CHANNELS));
+        let first = clip(0.5, 0.5).with_source(capture);
+        let first_id = first.id;
+        handle.append(first);
+        handle.append(clip(0.25, 0.25));
+        handle.append(clip(0.125, 0.125));
+
+        // Unknown until it has played
+        assert!(!handle.replay(first_id));
+        render(&mut mixer, 60_000);
+        let history = handle.history();
+        assert_eq!(history.iter().map(|buffer| buffer.id).collect::<Vec<_>>(), vec![first_id]);
+        // The decoded capture is released once the clip has played
+        assert!(history[0].source.is_none());
+
+        assert!(handle.replay(first_id));
+        assert!(handle.history().is_empty());
+        let output = render(&mut mixer, 3_000);
+        assert!((output[2_999][0] - 0.5).abs() < 1e-6);
+
+        // The queue carries on where it was
+        assert_eq!(handle.len(), 2);
+    }
+
+    #[test]
+    fn test_replay_lets_running_transition_finish() {
+        let (handle, mut mixer) = deck_mixer();
+        let replayed = long_clip(0.125, 0.125);
+        let replayed_id = replayed.id;
+        let incoming = long_clip(-0.5, -0.5);
+        let incoming_id = incoming.id;
+        handle.append(replayed);
+        handle.append(long_clip(0.5, 0.5));
+        handle.append(incoming);
+
+        // One bar crossfades: the second clip hands over to the third from 96000 to 144000
+        let mut output = render(&mut mixer, 110_000);
+        assert!(handle.replay(replayed_id));
+        output.extend(render(&mut mixer, 40_000));
+
+        // No jump back to the outgoing clip alone
+        let largest_step = output[109_000..]
+            .windows(2)
+            .map(|pair| (pair[1][0] - pair[0][0]).abs())
+            .fold(0.0f32, f32::max);
+        assert!(largest_step < 0.01, "Step of {largest_step}");
+        assert!((output[149_999][0] - 0.125).abs() < 1e-6);
+
+        // The clip that came in plays after the replayed one, instead of joining the history
+        assert_eq!(handle.queued().iter().map(|buffer| buffer.id).collect::<Vec<_>>(), vec![incoming_id]);
+        assert!(handle.history().iter().all(|buffer| buffer.id != incoming_id));
+    }
+}
@@ -1,4 +1,21 @@
+mod clock;
 mod engine;
+mod limiter;
+mod mixer;
+mod offline;
+mod output;
+mod position;
+mod recorder;
 mod source;
+mod transition;
 
-pub use engine::{list_audio_devices, default_device_index, AudioDevice, PlaybackEngine};
+pub use engine::{default_device_name, find_device, list_audio_devices, AudioDevice, PlaybackEngine};
+pub use limiter::{LimiterMeter, LimiterSettings};
+pub use mixer::HISTORY_CLIPS;
+#[cfg(test)]
+pub use mixer::deck_mixer;
+pub use offline::OfflineRenderer;
+pub use output::OutputBackend;
+pub use position::PositionSnapshot;
+pub use recorder::Recorder;
+pub use transition::TransitionStyle;
@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::info;
+
+use crate::audio::{LoopBuffer, CHANNELS, SAMPLE_RATE};
+use crate::error::PlaybackError;
+
+use super::limiter::{Limiter, LimiterMeter, LimiterSettings};
+use super::mixer::{deck_mixer, DeckMixer, MixerHandle};
+use super::recorder::MixWriter;
+use super::transition::TransitionStyle;
+
+/// Frames pulled from the master chain per render call (~100 ms)
+const RENDER_CHUNK_FRAMES: u64 = SAMPLE_RATE as u64 / 10;
+
+/// Offline counterpart of [`super::PlaybackEngine`]
+///
+/// Runs the same chain as live playback (mixer -> limiter) but pulls the
+/// samples into a file as fast as they can be computed instead of handing
+/// them to an audio device.
+pub struct OfflineRenderer {
+    mixer: MixerHandle,
+    output: Limiter<DeckMixer>,
+    writer: Option<MixWriter>,
+    path: PathBuf,
+    /// Frames written so far
+    frames: u64,
+    samples: Vec<f32>,
+    bytes: Vec<u8>,
+}
+
+impl OfflineRenderer {
+    /// Create the output file (WAV, or any format ffmpeg can write)
+    pub fn create(path: &Path, limiter: LimiterSettings) -> Result<Self, PlaybackError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| PlaybackError::Recording(e.to_string()))?;
+        }
+        let writer = MixWriter::create(path)?;
+
+        let (mixer, mixer_output) = deck_mixer();
+        let output = Limiter::new(mixer_output, limiter, Arc::new(LimiterMeter::default()));
+
+        info!(path = ?path, "Offline render started");
+        Ok(Self {
+            mixer,
+            output,
+            writer: Some(writer),
+            path: path.to_path_buf(),
+            frames: 0,
+            samples: Vec::with_capacity(RENDER_CHUNK_FRAMES as usize * CHANNELS as usize),
+            bytes: Vec::new(),
+        })
+    }
+
+    /// Queue a clip after the ones already waiting
+    pub fn append(&self, buffer: LoopBuffer) {
+        self.mixer.append(buffer);
+    }
+
+    pub fn set_transition(&self, style: TransitionStyle) {
+        self.mixer.set_transition(style);
+    }
+
+    /// Loop each clip for at least this long (station dwell time)
+    pub fn set_dwell(&self, secs: f32) {
+        self.mixer.set_dwell(secs);
+    }
+
+    /// A clip is waiting behind the playing one, so the next chunk cannot run dry
+    ///
+    /// Live playback falls silent when the network can't keep up; offline
+    /// rendering waits for the producer instead.
+    pub fn ready(&self) -> bool {
+        self.mixer.len() >= 2
+    }
+
+    /// Frames written so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    #[cfg(test)]
+    pub fn position(&self) -> super::position::PositionSnapshot {
+        self.mixer.position()
+    }
+
+    /// Render up to one chunk, stopping at `until` frames
+    ///
+    /// Returns the number of frames written.
+    pub fn render(&mut self, until: u64) -> Result<u64, PlaybackError> {
+        let frames = until.saturating_sub(self.frames).min(RENDER_CHUNK_FRAMES);
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(0);
+        };
+
+        self.samples.clear();
+        self.samples
+            .extend(self.output.by_ref().take(frames as usize * CHANNELS as usize));
+        writer
+            .write_samples(&self.samples, &mut self.bytes)
+            .map_err(|e| PlaybackError::Recording(e.to_string()))?;
+
+        self.frames += frames;
+        Ok(frames)
+    }
+
+    /// Complete the output file
+    pub fn finish(mut self) -> Result<PathBuf, PlaybackError> {
+        if let Some(writer) = self.writer.take() {
+            writer.finish().map_err(|e| PlaybackError::Recording(e.to_string()))?;
+        }
+        info!(path = ?self.path, secs = self.frames as f64 / SAMPLE_RATE as f64, "Offline render finished");
+        Ok(self.path.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::LoopInfo;
+
+    /// One bar clip at 120 BPM in 2/4 with a constant level
+    fn clip(level: f32) -> LoopBuffer {
+        let frames = SAMPLE_RATE as usize;
+        LoopBuffer::new(
+            vec![level; frames * CHANNELS as usize],
+            LoopInfo {
+                beats_per_bar: 2,
+                ..LoopInfo::for_test(120.0, frames)
+            },
+        )
+    }
+
+    fn read_samples(path: &Path) -> Vec<f32> {
+        let wav = std::fs::read(path).unwrap();
+        wav[58..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_plays_clips_back_to_back_into_wav() {
+        let path = std::env::temp_dir().join(format!("tappr-render-test-{}.wav", std::process::id()));
+        let mut renderer = OfflineRenderer::create(&path, LimiterSettings::default()).unwrap();
+        renderer.set_transition(TransitionStyle::Cut);
+        renderer.set_dwell(2.0);
+        renderer.append(clip(0.25));
+        assert!(!renderer.ready());
+        renderer.append(clip(0.5));
+        assert!(renderer.ready());
+
+        // Two seconds of the first clip, then the second one
+        let total = SAMPLE_RATE as u64 * 3;
+        let mut clips = Vec::new();
+        while renderer.frames() < total {
+            assert!(renderer.render(total).unwrap() > 0);
+            clips.extend(renderer.position().clip_id());
+        }
+        assert_eq!(renderer.frames(), total);
+        clips.dedup();
+        assert_eq!(clips.len(), 2);
+        renderer.finish().unwrap();
+
+        let samples = read_samples(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(samples.len() as u64, total * CHANNELS as u64);
+
+        let level_at = |secs: f32| samples[(secs * SAMPLE_RATE as f32) as usize * CHANNELS as usize];
+        assert!((level_at(1.0) - 0.25).abs() < 1e-3);
+        assert!((level_at(2.5) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_render_applies_master_limiter() {
+        let path = std::env::temp_dir().join(format!("tappr-render-limit-{}.wav", std::process::id()));
+        let mut renderer = OfflineRenderer::create(&path, LimiterSettings::default()).unwrap();
+        renderer.append(clip(1.5));
+
+        let total = SAMPLE_RATE as u64;
+        while renderer.render(total).unwrap() > 0 {}
+        renderer.finish().unwrap();
+
+        let samples = read_samples(&path);
+        std::fs::remove_file(&path).ok();
+        let ceiling = 10f32.powf(LimiterSettings::default().ceiling_db / 20.0);
+        assert!(samples.iter().all(|sample| sample.abs() <= ceiling + 1e-4));
+        assert!(samples.iter().any(|sample| sample.abs() > 0.5));
+    }
+}
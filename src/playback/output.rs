@@ -0,0 +1,215 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use rodio::queue::SourcesQueueOutput;
+use tracing::{info, warn};
+
+use crate::audio::{CHANNELS, SAMPLE_RATE};
+use crate::error::PlaybackError;
+
+use super::recorder::MixWriter;
+
+/// Frames consumed per wake-up of a non-device output (~10 ms)
+const OUTPUT_BLOCK_FRAMES: usize = SAMPLE_RATE as usize / 100;
+
+/// Where the master output goes
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OutputBackend {
+    /// Audio device picked in the TUI (the default device at start)
+    #[default]
+    Device,
+    /// Named audio device (exact name, or the first name containing it)
+    NamedDevice(String),
+    /// Discard samples, consumed in real time
+    Null,
+    /// Write samples to a WAV file (or through ffmpeg), consumed in real time
+    File(PathBuf),
+}
+
+impl OutputBackend {
+    /// Plays through an audio device
+    pub fn is_device(&self) -> bool {
+        matches!(self, Self::Device | Self::NamedDevice(_))
+    }
+}
+
+impl FromStr for OutputBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, target) = match s.split_once(':') {
+            Some((kind, target)) => (kind, Some(target)),
+            None => (s, None),
+        };
+
+        match (kind.to_ascii_lowercase().as_str(), target) {
+            ("null", None) => Ok(Self::Null),
+            ("file", Some(path)) if !path.is_empty() => Ok(Self::File(PathBuf::from(path))),
+            ("device", None) => Ok(Self::Device),
+            ("device", Some(name)) if !name.is_empty() => Ok(Self::NamedDevice(name.to_string())),
+            _ => Err(format!(
+                "invalid output '{}' (expected null, file:<path> or device[:<name>])",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Device => write!(f, "device"),
+            Self::NamedDevice(name) => write!(f, "device:{}", name),
+            Self::Null => write!(f, "null"),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+
+/// Pulls the sink's output at real-time pace for outputs without a device clock
+///
+/// Samples are dropped (null output) or written to a file. Dropping the
+/// output stops the thread and completes the file.
+pub(super) struct RealtimeOutput {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl RealtimeOutput {
+    /// Consume and discard the sink's output
+    pub(super) fn null(source: SourcesQueueOutput<f32>) -> Result<Self, PlaybackError> {
+        Self::spawn(source, None)
+    }
+
+    /// Write the sink's output to a file
+    pub(super) fn file(source: SourcesQueueOutput<f32>, path: &Path) -> Result<Self, PlaybackError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| PlaybackError::Device(e.to_string()))?;
+        }
+        let writer = MixWriter::create(path)?;
+        info!(path = ?path, "Writing output to file");
+        Self::spawn(source, Some(writer))
+    }
+
+    fn spawn(source: SourcesQueueOutput<f32>, writer: Option<MixWriter>) -> Result<Self, PlaybackError> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let thread = std::thread::Builder::new()
+            .name("output".into())
+            .spawn(move || run_output(source, writer, &thread_stop))
+            .map_err(|e| PlaybackError::Device(format!("Failed to start output thread: {}", e)))?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for RealtimeOutput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(Err(e))) => warn!(error = %e, "Output file could not be completed"),
+            Some(Err(_)) => warn!("Output thread panicked"),
+            _ => {}
+        }
+    }
+}
+
+/// Pull blocks on a wall-clock schedule until stopped, then close the writer
+fn run_output(mut source: SourcesQueueOutput<f32>, mut writer: Option<MixWriter>, stop: &AtomicBool) -> io::Result<()> {
+    let started = Instant::now();
+    let mut frames = 0u64;
+    let mut block = Vec::with_capacity(OUTPUT_BLOCK_FRAMES * CHANNELS as usize);
+    let mut bytes = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        block.clear();
+        block.extend(source.by_ref().take(OUTPUT_BLOCK_FRAMES * CHANNELS as usize));
+        if let Some(writer) = writer.as_mut() {
+            writer.write_samples(&block, &mut bytes)?;
+        }
+        frames += OUTPUT_BLOCK_FRAMES as u64;
+
+        // Stay on schedule rather than sleeping a fixed time per block
+        let due = started + Duration::from_secs_f64(frames as f64 / SAMPLE_RATE as f64);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+
+    match writer {
+        Some(writer) => writer.finish(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output_backend() {
+        assert_eq!("null".parse(), Ok(OutputBackend::Null));
+        assert_eq!("device".parse(), Ok(OutputBackend::Device));
+        assert_eq!(
+            "device:USB Audio".parse(),
+            Ok(OutputBackend::NamedDevice("USB Audio".into()))
+        );
+        assert_eq!(
+            "file:/tmp/out.wav".parse(),
+            Ok(OutputBackend::File(PathBuf::from("/tmp/out.wav")))
+        );
+        assert!("file:".parse::<OutputBackend>().is_err());
+        assert!("speakers".parse::<OutputBackend>().is_err());
+        assert_eq!(OutputBackend::NamedDevice("USB".into()).to_string(), "device:USB");
+    }
+
+    #[test]
+    fn test_file_output_runs_engine_without_device() {
+        use crate::app::LoopInfo;
+        use crate::audio::LoopBuffer;
+        use crate::playback::{LimiterSettings, PlaybackEngine};
+
+        let path = std::env::temp_dir().join(format!("tappr-output-test-{}.wav", std::process::id()));
+        let backend = OutputBackend::File(path.clone());
+        let started = Instant::now();
+        let mut engine = PlaybackEngine::with_output(&backend, None, LimiterSettings::default()).unwrap();
+
+        let frames = SAMPLE_RATE as usize;
+        engine.play(LoopBuffer::new(
+            vec![0.25; frames * CHANNELS as usize],
+            LoopInfo {
+                beats_per_bar: 2,
+                ..LoopInfo::for_test(120.0, frames)
+            },
+        ));
+        std::thread::sleep(Duration::from_millis(300));
+        drop(engine);
+        let elapsed = started.elapsed().as_secs_f32();
+
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        let data_bytes = u32::from_le_bytes(wav[54..58].try_into().unwrap()) as usize;
+        assert_eq!(data_bytes, wav.len() - 58);
+        let samples: Vec<f32> = wav[58..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert!(samples.iter().any(|sample| (sample - 0.25).abs() < 1e-3));
+
+        // Paced by the wall clock: never ahead of it by more than a block
+        let secs = samples.len() as f32 / (SAMPLE_RATE * CHANNELS as u32) as f32;
+        let block_secs = OUTPUT_BLOCK_FRAMES as f32 / SAMPLE_RATE as f32;
+        assert!(secs <= elapsed + block_secs, "wrote {secs} s in {elapsed} s");
+    }
+}
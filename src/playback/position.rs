@@ -0,0 +1,188 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::app::LoopInfo;
+use crate::audio::SAMPLE_RATE;
+
+/// Playback position published by the mixer once per rendered block
+///
+/// The mixer renders 256 frames (about 5 ms) per lock of its state, so the
+/// published frame moves in steps of one block.
+///
+/// The audio thread writes, the main loop and TUI read. The fields are
+/// independent atomics, so a reader may briefly see a new clip ID with the
+/// old clip's span; that is harmless for display.
+#[derive(Debug, Default)]
+pub struct PlaybackPosition {
+    /// Master clock frame
+    frame: AtomicU64,
+    /// ID of the clip playing (0 when silent)
+    clip_id: AtomicU64,
+    /// Master clock frames where the playing clip started and hands over
+    clip_start: AtomicU64,
+    clip_end: AtomicU64,
+    /// The playing clip is held
+    held: AtomicBool,
+}
+
+impl PlaybackPosition {
+    pub(super) fn publish(&self, frame: u64, clip: Option<ClipPosition>) {
+        self.frame.store(frame, Ordering::Relaxed);
+        match clip {
+            Some(clip) => {
+                self.clip_start.store(clip.start, Ordering::Relaxed);
+                self.clip_end.store(clip.end, Ordering::Relaxed);
+                self.held.store(clip.held, Ordering::Relaxed);
+                self.clip_id.store(clip.id, Ordering::Release);
+            }
+            None => self.clip_id.store(0, Ordering::Release),
+        }
+    }
+
+    /// Read the latest published position
+    pub fn snapshot(&self) -> PositionSnapshot {
+        let clip_id = self.clip_id.load(Ordering::Acquire);
+        let clip = (clip_id != 0).then(|| ClipPosition {
+            id: clip_id,
+            start: self.clip_start.load(Ordering::Relaxed),
+            end: self.clip_end.load(Ordering::Relaxed),
+            held: self.held.load(Ordering::Relaxed),
+        });
+
+        PositionSnapshot {
+            frame: self.frame.load(Ordering::Relaxed),
+            clip,
+        }
+    }
+}
+
+/// Where the playing clip sits on the master clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipPosition {
+    /// `LoopBuffer::id` of the clip
+    pub id: u64,
+    /// Frame the clip started on
+    pub start: u64,
+    /// Frame the clip hands over on
+    pub end: u64,
+    pub held: bool,
+}
+
+/// Position read from [`PlaybackPosition`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PositionSnapshot {
+    /// Master clock frame
+    pub frame: u64,
+    /// Clip playing, if any
+    pub clip: Option<ClipPosition>,
+}
+
+impl PositionSnapshot {
+    /// ID of the clip playing
+    pub fn clip_id(&self) -> Option<u64> {
+        self.clip.map(|clip| clip.id)
+    }
+
+    /// Repeat progress of the playing clip
+    pub fn loop_progress(&self, info: &LoopInfo) -> Option<LoopProgress> {
+        let clip = self.clip?;
+        let loop_frames = info.duration_samples.max(1) as u64;
+        let elapsed = self.frame.saturating_sub(clip.start);
+        let total = clip.end.saturating_sub(clip.start);
+        let remaining = clip.end.saturating_sub(self.frame);
+
+        let pass = (elapsed / loop_frames + 1) as u32;
+        let passes = (total as f64 / loop_frames as f64).round() as u32;
+
+        Some(LoopProgress {
+            pass,
+            passes: passes.max(pass),
+            remaining_secs: remaining as f32 / SAMPLE_RATE as f32,
+            progress: if total == 0 { 1.0 } else { elapsed.min(total) as f32 / total as f32 },
+            held: clip.held,
+        })
+    }
+
+    /// Bar and beat (1-based) within the playing loop
+    pub fn beat_position(&self, info: &LoopInfo) -> Option<(u32, u32)> {
+        let clip = self.clip?;
+        if info.bpm <= 0.0 || info.beats_per_bar == 0 {
+            return None;
+        }
+
+        let loop_frames = info.duration_samples.max(1) as u64;
+        let frame_in_loop = self.frame.saturating_sub(clip.start) % loop_frames;
+        let beat = (frame_in_loop as f64 * info.bpm as f64 / 60.0 / SAMPLE_RATE as f64) as u32;
+        let beats_per_bar = info.beats_per_bar as u32;
+
+        Some((beat / beats_per_bar + 1, beat % beats_per_bar + 1))
+    }
+}
+
+/// Repeat progress of the clip that is playing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopProgress {
+    /// Current pass through the loop (1-based)
+    pub pass: u32,
+    /// Number of passes before moving on
+    pub passes: u32,
+    /// Time until the clip hands over (seconds)
+    pub remaining_secs: f32,
+    /// Fraction of all passes played (0.0 to 1.0)
+    pub progress: f32,
+    /// The loop is held and repeats until released
+    pub held: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loop_info() -> LoopInfo {
+        LoopInfo {
+            bars: 2,
+            ..LoopInfo::for_test(120.0, 192_000)
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let position = PlaybackPosition::default();
+        assert_eq!(position.snapshot().clip_id(), None);
+
+        let clip = ClipPosition {
+            id: 7,
+            start: 1_000,
+            end: 577_000,
+            held: false,
+        };
+        position.publish(5_000, Some(clip));
+        let snapshot = position.snapshot();
+        assert_eq!(snapshot.frame, 5_000);
+        assert_eq!(snapshot.clip, Some(clip));
+
+        position.publish(6_000, None);
+        assert_eq!(position.snapshot().clip_id(), None);
+    }
+
+    #[test]
+    fn test_progress_and_beat() {
+        let info = loop_info();
+        // Three passes of a 4-second loop, 5.25 seconds in
+        let snapshot = PositionSnapshot {
+            frame: 252_000,
+            clip: Some(ClipPosition {
+                id: 1,
+                start: 0,
+                end: 576_000,
+                held: false,
+            }),
+        };
+
+        let progress = snapshot.loop_progress(&info).unwrap();
+        assert_eq!((progress.pass, progress.passes), (2, 3));
+        assert!((progress.remaining_secs - 6.75).abs() < 1e-3);
+
+        // 1.25s into the second pass: beat 3 of bar 1
+        assert_eq!(snapshot.beat_position(&info), Some((1, 3)));
+    }
+}
@@ -0,0 +1,572 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use rodio::Source;
+use tracing::{debug, info, warn};
+
+use crate::app::StationInfo;
+use crate::audio::{CHANNELS, SAMPLE_RATE};
+use crate::error::PlaybackError;
+
+/// Samples handed to the writer at a time (~21 ms)
+const TAP_BLOCK_SAMPLES: usize = 1024 * CHANNELS as usize;
+
+/// Blocks that may wait for the writer before the tap starts dropping (~11 s)
+const TAP_QUEUE_BLOCKS: usize = 512;
+
+/// Empty blocks kept for reuse, so the audio thread rarely allocates
+const POOL_BLOCKS: usize = 64;
+
+/// Work for the writer thread
+enum WriterMessage {
+    /// Interleaved samples from the tap
+    Block(Vec<f32>),
+    /// CUE sheet replacing the one next to the recording
+    CueSheet(String),
+}
+
+/// Connection from the audio thread to a running recorder
+struct TapLink {
+    blocks: SyncSender<WriterMessage>,
+    pool: Arc<Mutex<Vec<Vec<f32>>>>,
+    frames: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// Recording slot of a playback engine (empty while not recording)
+#[derive(Clone, Default)]
+pub struct RecordTap {
+    link: Arc<Mutex<Option<TapLink>>>,
+}
+
+impl RecordTap {
+    /// Send everything passing the tap to a recorder
+    pub fn connect(&self, recorder: &Recorder) {
+        let Some(blocks) = recorder.blocks.clone() else {
+            return;
+        };
+        *self.link.lock() = Some(TapLink {
+            blocks,
+            pool: Arc::clone(&recorder.pool),
+            frames: Arc::clone(&recorder.frames),
+            dropped: Arc::clone(&recorder.dropped),
+        });
+        recorder.taps.lock().push(self.clone());
+    }
+
+    pub fn disconnect(&self) {
+        *self.link.lock() = None;
+    }
+
+    /// Disconnect if still linked to the recorder counting into `frames`
+    fn detach(&self, frames: &Arc<AtomicU64>) {
+        let mut link = self.link.lock();
+        if link.as_ref().is_some_and(|link| Arc::ptr_eq(&link.frames, frames)) {
+            *link = None;
+        }
+    }
+
+    /// Hand a full block to the recorder, getting an empty one back
+    ///
+    /// Runs on the audio thread, so it never waits: the block is dropped
+    /// while the link is being changed.
+    fn flush(&self, block: &mut Vec<f32>) {
+        let Some(link) = self.link.try_lock() else {
+            block.clear();
+            return;
+        };
+        let Some(link) = link.as_ref() else {
+            block.clear();
+            return;
+        };
+
+        // Take a pooled buffer if the writer isn't holding the lock
+        let empty = link
+            .pool
+            .try_lock()
+            .and_then(|mut pool| pool.pop())
+            .unwrap_or_else(|| Vec::with_capacity(TAP_BLOCK_SAMPLES));
+        let full = std::mem::replace(block, empty);
+        let frames = (full.len() / CHANNELS as usize) as u64;
+
+        match link.blocks.try_send(WriterMessage::Block(full)) {
+            Ok(()) => link.frames.fetch_add(frames, Ordering::Relaxed),
+            Err(_) => link.dropped.fetch_add(frames, Ordering::Relaxed),
+        };
+    }
+}
+
+/// Source wrapper copying the samples it passes on to a [`RecordTap`]
+pub struct Tap<S>
+where
+    S: Source<Item = f32>,
+{
+    input: S,
+    tap: RecordTap,
+    block: Vec<f32>,
+}
+
+impl<S> Tap<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, tap: RecordTap) -> Self {
+        Self {
+            input,
+            tap,
+            block: Vec::with_capacity(TAP_BLOCK_SAMPLES),
+        }
+    }
+}
+
+impl<S> Iterator for Tap<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        self.block.push(sample);
+        if self.block.len() >= TAP_BLOCK_SAMPLES {
+            self.tap.flush(&mut self.block);
+        }
+        Some(sample)
+    }
+}
+
+impl<S> Source for Tap<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// A station change in the recording
+struct Chapter {
+    title: String,
+    performer: String,
+    station_id: String,
+    /// Recording frame the station starts at
+    frame: u64,
+}
+
+/// Session recorder
+///
+/// Samples from the tap are written by a background thread, straight to a
+/// 32-bit float WAV or through ffmpeg for any other extension (FLAC, MP3,
+/// ...). Station changes are listed in a CUE sheet next to the recording.
+pub struct Recorder {
+    path: PathBuf,
+    /// Dropped on finish, which ends the writer thread
+    blocks: Option<SyncSender<WriterMessage>>,
+    pool: Arc<Mutex<Vec<Vec<f32>>>>,
+    /// Frames handed to the writer
+    frames: Arc<AtomicU64>,
+    /// Frames lost because the writer fell behind
+    dropped: Arc<AtomicU64>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+    /// Taps connected to this recorder (their senders keep the writer running)
+    taps: Mutex<Vec<RecordTap>>,
+    chapters: Vec<Chapter>,
+    /// The latest CUE sheet didn't fit in the writer's queue
+    cue_pending: bool,
+}
+
+impl Recorder {
+    /// Open the output and start the writer thread
+    pub fn start(path: &Path) -> Result<Self, PlaybackError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| PlaybackError::Recording(e.to_string()))?;
+        }
+
+        let output = MixWriter::create(path)?;
+
+        let (blocks, received) = mpsc::sync_channel(TAP_QUEUE_BLOCKS);
+        let pool = (0..POOL_BLOCKS).map(|_| Vec::with_capacity(TAP_BLOCK_SAMPLES)).collect();
+        let pool = Arc::new(Mutex::new(pool));
+        let writer_pool = Arc::clone(&pool);
+        let cue_path = path.with_extension("cue");
+        let writer = std::thread::Builder::new()
+            .name("recorder".into())
+            .spawn(move || run_writer(output, received, writer_pool, &cue_path))
+            .map_err(|e| PlaybackError::Recording(e.to_string()))?;
+
+        info!(path = ?path, "Recording started");
+        Ok(Self {
+            path: path.to_path_buf(),
+            blocks: Some(blocks),
+            pool,
+            frames: Arc::new(AtomicU64::new(0)),
+            dropped: Arc::new(AtomicU64::new(0)),
+            writer: Some(writer),
+            taps: Mutex::new(Vec::new()),
+            chapters: Vec::new(),
+            cue_pending: false,
+        })
+    }
+
+    /// Frames recorded so far
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Length of the recording so far
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / SAMPLE_RATE as f64)
+    }
+
+    /// Mark a station change at a recording frame and have the writer
+    /// thread rewrite the CUE sheet
+    ///
+    /// Consecutive clips from the same station share one chapter.
+    pub fn add_chapter(&mut self, station: &StationInfo, frame: u64) {
+        if self.chapters.last().is_some_and(|last| last.station_id == station.id) {
+            return;
+        }
+        self.chapters.push(Chapter {
+            title: station.name.clone(),
+            performer: format!("{}, {}", station.place_name, station.country),
+            station_id: station.id.clone(),
+            frame,
+        });
+
+        // Never waits: a sheet that doesn't fit is sent again on finish,
+        // unless a later chapter's sheet replaces it first
+        self.cue_pending = self
+            .blocks
+            .as_ref()
+            .is_some_and(|blocks| blocks.try_send(WriterMessage::CueSheet(self.cue_sheet())).is_err());
+    }
+
+    /// CUE sheet with one track per station
+    fn cue_sheet(&self) -> String {
+        let file_name = self.path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let file_type = match self.path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("mp3") => "MP3",
+            _ => "WAVE",
+        };
+
+        let mut sheet = format!(
+            "REM COMMENT \"Recorded with tappr\"\nTITLE \"tappr session\"\nFILE \"{}\" {}\n",
+            cue_text(&file_name),
+            file_type
+        );
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            sheet.push_str(&format!(
+                "  TRACK {:02} AUDIO\n    TITLE \"{}\"\n    PERFORMER \"{}\"\n    INDEX 01 {}\n",
+                index + 1,
+                cue_text(&chapter.title),
+                cue_text(&chapter.performer),
+                cue_time(chapter.frame)
+            ));
+        }
+        sheet
+    }
+
+    /// Close the block channel: detach the taps still linked, hand over a
+    /// CUE sheet still waiting, then drop our sender
+    fn close(&mut self) {
+        for tap in self.taps.lock().drain(..) {
+            tap.detach(&self.frames);
+        }
+        if let Some(blocks) = self.blocks.take() {
+            if std::mem::take(&mut self.cue_pending) {
+                let _ = blocks.send(WriterMessage::CueSheet(self.cue_sheet()));
+            }
+        }
+    }
+
+    /// Stop recording and wait for the writer to finish the file
+    pub fn finish(mut self) -> Result<PathBuf, PlaybackError> {
+        self.close();
+        let result = match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result.map_err(|e| PlaybackError::Recording(e.to_string())),
+            Some(Err(_)) => Err(PlaybackError::Recording("writer thread panicked".into())),
+            None => Ok(()),
+        };
+
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(dropped_frames = dropped, "Recorder fell behind, frames were dropped");
+        }
+        info!(path = ?self.path, secs = self.elapsed().as_secs_f32(), "Recording finished");
+        result.map(|()| self.path.clone())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.close();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes a stream of interleaved samples to a WAV file or through ffmpeg
+///
+/// WAV files are written as 32-bit float; any other extension is encoded by
+/// ffmpeg reading raw samples from a pipe.
+pub(super) enum MixWriter {
+    Wav(WavStream),
+    Ffmpeg(Child),
+}
+
+impl MixWriter {
+    pub(super) fn create(path: &Path) -> Result<Self, PlaybackError> {
+        if is_wav(path) {
+            WavStream::create(path)
+                .map(Self::Wav)
+                .map_err(|e| PlaybackError::Recording(e.to_string()))
+        } else {
+            spawn_ffmpeg(path).map(Self::Ffmpeg)
+        }
+    }
+
+    /// Append samples as little-endian floats
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Wav(wav) => wav.write(bytes),
+            Self::Ffmpeg(child) => match child.stdin.as_mut() {
+                Some(stdin) => stdin.write_all(bytes),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Append interleaved samples
+    pub(super) fn write_samples(&mut self, samples: &[f32], bytes: &mut Vec<u8>) -> io::Result<()> {
+        bytes.clear();
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.write(bytes)
+    }
+
+    /// Complete the file (WAV sizes, or wait for ffmpeg to flush)
+    pub(super) fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Wav(wav) => wav.finish(),
+            Self::Ffmpeg(mut child) => {
+                // Closing stdin lets ffmpeg flush and exit
+                drop(child.stdin.take());
+                let status = child.wait()?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("ffmpeg exited with {}", status)))
+                }
+            }
+        }
+    }
+}
+
+/// Write blocks and CUE sheets until every sender is gone, then close the output
+fn run_writer(
+    mut output: MixWriter,
+    messages: Receiver<WriterMessage>,
+    pool: Arc<Mutex<Vec<Vec<f32>>>>,
+    cue_path: &Path,
+) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(TAP_BLOCK_SAMPLES * 4);
+    for message in messages {
+        match message {
+            WriterMessage::Block(mut block) => {
+                output.write_samples(&block, &mut bytes)?;
+
+                block.clear();
+                let mut pool = pool.lock();
+                if pool.len() < POOL_BLOCKS {
+                    pool.push(block);
+                }
+            }
+            WriterMessage::CueSheet(sheet) => {
+                if let Err(e) = std::fs::write(cue_path, sheet) {
+                    warn!(path = ?cue_path, error = %e, "Failed to write cue sheet");
+                }
+            }
+        }
+    }
+    output.finish()
+}
+
+/// Start ffmpeg encoding raw float samples from stdin to `path`
+fn spawn_ffmpeg(path: &Path) -> Result<Child, PlaybackError> {
+    debug!(path = ?path, "Starting ffmpeg encoder");
+    Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "f32le"])
+        .args(["-ar", &SAMPLE_RATE.to_string(), "-ac", &CHANNELS.to_string(), "-i", "pipe:0"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| PlaybackError::Recording(format!("Failed to start ffmpeg: {}", e)))
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_none_or(|ext| ext.eq_ignore_ascii_case("wav"))
+}
+
+/// Streaming 32-bit float WAV whose sizes are filled in on finish
+pub(super) struct WavStream {
+    file: BufWriter<File>,
+    data_bytes: u64,
+}
+
+/// Header length up to the sample data (RIFF, 18-byte fmt, fact, data)
+const WAV_HEADER_BYTES: u64 = 58;
+
+impl WavStream {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let block_align = CHANNELS * 4;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&18u32.to_le_bytes())?;
+        file.write_all(&3u16.to_le_bytes())?;
+        file.write_all(&CHANNELS.to_le_bytes())?;
+        file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        file.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&32u16.to_le_bytes())?;
+        file.write_all(&0u16.to_le_bytes())?;
+        file.write_all(b"fact")?;
+        file.write_all(&4u32.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { file, data_bytes: 0 })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.data_bytes += bytes.len() as u64;
+        self.file.write_all(bytes)
+    }
+
+    /// Fill in the chunk sizes (capped at the 4 GiB RIFF limit)
+    fn finish(mut self) -> io::Result<()> {
+        let data_bytes = self.data_bytes.min(u32::MAX as u64 - WAV_HEADER_BYTES) as u32;
+        let frames = data_bytes / (CHANNELS as u32 * 4);
+
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(WAV_HEADER_BYTES as u32 - 8 + data_bytes).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(46))?;
+        self.file.write_all(&frames.to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(54))?;
+        self.file.write_all(&data_bytes.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// CUE sheet time (minutes:seconds:frames at 75 frames per second)
+fn cue_time(frame: u64) -> String {
+    let cue_frames = frame * 75 / SAMPLE_RATE as u64;
+    format!("{:02}:{:02}:{:02}", cue_frames / 75 / 60, cue_frames / 75 % 60, cue_frames % 75)
+}
+
+/// Text safe inside a quoted CUE field
+fn cue_text(text: &str) -> String {
+    text.replace('"', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cue_time() {
+        assert_eq!(cue_time(0), "00:00:00");
+        assert_eq!(cue_time(SAMPLE_RATE as u64 * 61 + SAMPLE_RATE as u64 / 2), "01:01:37");
+    }
+
+    #[test]
+    fn test_tap_records_passing_samples() {
+        let path = std::env::temp_dir().join(format!("tappr-record-test-{}.wav", std::process::id()));
+        let recorder = Recorder::start(&path).unwrap();
+        let tap = RecordTap::default();
+        tap.connect(&recorder);
+
+        let input = rodio::source::SineWave::new(440.0).take_duration(Duration::from_millis(100));
+        let tapped = Tap::new(rodio::source::ChannelVolume::new(input, vec![1.0, 1.0]), tap.clone());
+        let played: Vec<f32> = tapped.collect();
+        tap.disconnect();
+
+        // Only whole blocks reach the recorder
+        let frames = recorder.frames();
+        assert_eq!(frames, (played.len() / TAP_BLOCK_SAMPLES * 1024) as u64);
+        recorder.finish().unwrap();
+
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(wav.len() as u64, WAV_HEADER_BYTES + frames * 8);
+        assert_eq!(u32::from_le_bytes(wav[54..58].try_into().unwrap()) as u64, frames * 8);
+        let first = f32::from_le_bytes(wav[58..62].try_into().unwrap());
+        assert_eq!(first, played[0]);
+    }
+
+    #[test]
+    fn test_writer_writes_cue_sheet() {
+        let path = std::env::temp_dir().join(format!("tappr-record-cue-{}.wav", std::process::id()));
+        let mut recorder = Recorder::start(&path).unwrap();
+        let station = |id: &str, name: &str| StationInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..StationInfo::default()
+        };
+        recorder.add_chapter(&station("a", "First \"FM\""), 0);
+        recorder.add_chapter(&station("a", "First \"FM\""), SAMPLE_RATE as u64);
+        recorder.add_chapter(&station("b", "Second"), SAMPLE_RATE as u64 * 2);
+        recorder.finish().unwrap();
+
+        let cue_path = path.with_extension("cue");
+        let sheet = std::fs::read_to_string(&cue_path).unwrap();
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&cue_path).ok();
+        assert_eq!(sheet.matches("TRACK").count(), 2);
+        assert!(sheet.contains("TITLE \"First 'FM'\""));
+        assert!(sheet.contains("INDEX 01 00:02:00"));
+    }
+
+    #[test]
+    fn test_finish_detaches_linked_tap() {
+        let path = std::env::temp_dir().join(format!("tappr-record-detach-{}.wav", std::process::id()));
+        let recorder = Recorder::start(&path).unwrap();
+        let tap = RecordTap::default();
+        tap.connect(&recorder);
+
+        // Still linked: finishing must not wait for the tap's sender
+        recorder.finish().unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(tap.link.lock().is_none());
+    }
+}
@@ -29,10 +29,19 @@ pub struct OneShotSource {
 
 impl OneShotSource {
     /// Create a new one-shot source with beat-aligned crossfade
+    #[cfg(test)]
     pub fn new(buffer: LoopBuffer) -> Self {
         Self::with_fades(buffer, true, true)
     }
 
+    /// Create a one-shot source without fades
+    ///
+    /// Loops from the quantizer start on a zero crossing and their tail is
+    /// already crossfaded into the head, so no extra fade is needed.
+    pub fn seamless(buffer: LoopBuffer) -> Self {
+        Self::with_fades(buffer, false, false)
+    }
+
     /// Create a new one-shot source with configurable fades
     pub fn with_fades(buffer: LoopBuffer, fade_in: bool, fade_out: bool) -> Self {
         let total_samples = buffer.samples.len();
@@ -63,6 +72,11 @@ impl OneShotSource {
         }
     }
 
+    /// Number of frames left to play
+    pub fn remaining_frames(&self) -> usize {
+        self.total_samples.saturating_sub(self.position) / CHANNELS as usize
+    }
+
     /// Calculate equal-power fade-in gain for a position within the fade region
     /// Uses sine curve for perceptually constant loudness
     fn fade_in_gain(&self, position: usize) -> f32 {
@@ -141,16 +155,7 @@ mod tests {
         let duration_samples = samples.len() / CHANNELS as usize;
         LoopBuffer::new(
             samples,
-            LoopInfo {
-                bpm: 120.0,
-                source_bpm: 120.0,
-                bpm_confidence: 1.0,
-                time_stretched: false,
-                bars: 1,
-                beats_per_bar: 4,
-                duration_samples,
-                sample_rate: SAMPLE_RATE,
-            },
+            LoopInfo::for_test(120.0, duration_samples),
         )
     }
 
@@ -259,16 +264,7 @@ mod tests {
             let duration_samples = samples.len() / CHANNELS as usize;
             let buffer = LoopBuffer::new(
                 samples,
-                LoopInfo {
-                    bpm,
-                    source_bpm: bpm,
-                    bpm_confidence: 1.0,
-                    time_stretched: false,
-                    bars: 1,
-                    beats_per_bar: 4,
-                    duration_samples,
-                    sample_rate: SAMPLE_RATE,
-                },
+                LoopInfo::for_test(bpm, duration_samples),
             );
 
             let source = OneShotSource::new(buffer);
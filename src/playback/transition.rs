@@ -0,0 +1,654 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::sync::Arc;
+
+use crate::audio::{LoopBuffer, CHANNELS, SAMPLE_RATE};
+
+/// Interleaved frame width
+const FRAME: usize = CHANNELS as usize;
+
+/// Beat length used when the outgoing clip has no tempo (120 BPM)
+const DEFAULT_BEAT_SECS: f32 = 0.5;
+
+/// Echo-out: the last beat is sent into a dotted-eighth delay that rings on
+/// for a few beats over the incoming clip
+const ECHO_SEND_BEATS: f32 = 1.0;
+const ECHO_DELAY_BEATS: f32 = 0.75;
+const ECHO_TAIL_BEATS: f32 = 4.0;
+const ECHO_FEEDBACK: f32 = 0.55;
+
+/// Filter sweep: over the last bar the outgoing clip is low-passed down to
+/// a bass rumble while the incoming clip's high-pass opens up
+const FILTER_SWEEP_BARS: f32 = 1.0;
+const OUTGOING_LOW_PASS: (f32, f32) = (20_000.0, 200.0);
+const INCOMING_HIGH_PASS: (f32, f32) = (1_000.0, 20.0);
+
+/// Backspin: the last beat spins backwards, peaking at this speed, while
+/// the platter slows down
+const BACKSPIN_BEATS: f32 = 1.0;
+const BACKSPIN_SPEED: f64 = 3.0;
+/// Fraction of the backspin spent reversing the platter
+const BACKSPIN_GRAB: f64 = 0.08;
+
+/// Radio tuning: one bar of dial noise and heterodyne whistles, with a
+/// band-passed glimpse of the next station before it comes in
+const TUNING_BARS: f32 = 1.0;
+const TUNING_NOISE_LEVEL: f32 = 0.3;
+const TUNING_NOISE_CENTRE: f32 = 2_500.0;
+const TUNING_WHISTLE_LEVEL: f32 = 0.06;
+/// Pitch of a whistle far from its carrier (Hz); it falls to zero as the dial crosses it
+const TUNING_WHISTLE_MAX: f32 = 4_000.0;
+/// Points in the transition where the dial crosses a carrier
+const TUNING_CARRIERS: [f32; 2] = [0.3, 0.65];
+const TUNING_GLIMPSE_CENTRE: f32 = 1_200.0;
+const TUNING_GLIMPSE_DAMPING: f32 = 0.15;
+const TUNING_GLIMPSE_GAIN: f32 = 2.0;
+/// Part of the transition in which the next station can be heard through the noise
+const TUNING_GLIMPSE_WINDOW: (f32, f32) = (0.4, 0.85);
+
+/// How one clip hands over to the next
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionStyle {
+    /// Hard cut on the downbeat
+    Cut,
+    /// Equal-power crossfade over this many bars of the outgoing clip
+    Crossfade(u8),
+    /// Tempo-synced delay tail of the outgoing clip over the incoming one
+    EchoOut,
+    /// Low-pass sweep on the outgoing clip, high-pass sweep on the incoming one
+    FilterSweep,
+    /// The outgoing clip is spun backwards into the downbeat
+    Backspin,
+    /// Static and whistles of a radio dial turning to the next station
+    Tuning,
+}
+
+impl Default for TransitionStyle {
+    fn default() -> Self {
+        TransitionStyle::Crossfade(1)
+    }
+}
+
+impl TransitionStyle {
+    /// Next style in the TUI cycle
+    pub fn next(self) -> Self {
+        match self {
+            TransitionStyle::Cut => TransitionStyle::Crossfade(1),
+            TransitionStyle::Crossfade(bars) if bars < 4 => TransitionStyle::Crossfade((bars * 2).min(4)),
+            TransitionStyle::Crossfade(_) => TransitionStyle::EchoOut,
+            TransitionStyle::EchoOut => TransitionStyle::FilterSweep,
+            TransitionStyle::FilterSweep => TransitionStyle::Backspin,
+            TransitionStyle::Backspin => TransitionStyle::Tuning,
+            TransitionStyle::Tuning => TransitionStyle::Cut,
+        }
+    }
+
+    /// Beats before the end of the outgoing clip at which the transition starts
+    fn lead_beats(self, beats_per_bar: u8) -> f32 {
+        match self {
+            TransitionStyle::Cut => 0.0,
+            TransitionStyle::Crossfade(bars) => bars as f32 * beats_per_bar as f32,
+            TransitionStyle::EchoOut => ECHO_SEND_BEATS,
+            TransitionStyle::FilterSweep => FILTER_SWEEP_BARS * beats_per_bar as f32,
+            TransitionStyle::Backspin => BACKSPIN_BEATS,
+            TransitionStyle::Tuning => TUNING_BARS * beats_per_bar as f32,
+        }
+    }
+
+    /// Whether the incoming clip starts playing when the transition starts
+    /// (rather than when the outgoing clip ends)
+    fn incoming_starts_with_lead(self) -> bool {
+        matches!(self, TransitionStyle::Crossfade(_) | TransitionStyle::FilterSweep)
+    }
+}
+
+impl std::str::FromStr for TransitionStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "cut" => Ok(TransitionStyle::Cut),
+            "crossfade" | "fade" => Ok(TransitionStyle::Crossfade(1)),
+            "echo" | "echo-out" => Ok(TransitionStyle::EchoOut),
+            "filter" | "filter-sweep" => Ok(TransitionStyle::FilterSweep),
+            "backspin" | "spin" => Ok(TransitionStyle::Backspin),
+            "tuning" | "radio" => Ok(TransitionStyle::Tuning),
+            _ => {
+                let bars = s
+                    .strip_prefix("crossfade:")
+                    .and_then(|bars| bars.parse::<u8>().ok())
+                    .filter(|&bars| bars > 0)
+                    .ok_or_else(|| {
+                        format!(
+                            "Unknown transition '{}' (expected cut, crossfade[:bars], echo, filter, backspin or tuning)",
+                            s
+                        )
+                    })?;
+                Ok(TransitionStyle::Crossfade(bars))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for TransitionStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransitionStyle::Cut => write!(f, "Cut"),
+            TransitionStyle::Crossfade(1) => write!(f, "Crossfade 1 bar"),
+            TransitionStyle::Crossfade(bars) => write!(f, "Crossfade {} bars", bars),
+            TransitionStyle::EchoOut => write!(f, "Echo out"),
+            TransitionStyle::FilterSweep => write!(f, "Filter sweep"),
+            TransitionStyle::Backspin => write!(f, "Backspin"),
+            TransitionStyle::Tuning => write!(f, "Radio tuning"),
+        }
+    }
+}
+
+/// Frames per beat of a clip
+fn beat_frames(buffer: &LoopBuffer) -> f32 {
+    let bpm = buffer.loop_info.bpm;
+    let secs = if bpm > 0.0 { 60.0 / bpm } else { DEFAULT_BEAT_SECS };
+    secs * SAMPLE_RATE as f32
+}
+
+/// Frames before the end of `outgoing` at which a transition should start
+///
+/// Limited to half of either clip so overlaps never swallow a whole clip.
+/// A limited lead is rounded down to whole bars of the outgoing clip when
+/// the incoming clip starts with the transition, so it still starts on a
+/// downbeat of the bar grid, and to whole beats otherwise.
+pub fn lead_frames(style: TransitionStyle, outgoing: &LoopBuffer, next: &LoopBuffer) -> usize {
+    let beats_per_bar = outgoing.loop_info.beats_per_bar;
+    let beat = beat_frames(outgoing);
+    let lead = (style.lead_beats(beats_per_bar) * beat) as usize;
+    let limit = (outgoing.frame_count() / 2).min(next.frame_count() / 2);
+    if lead <= limit {
+        return lead;
+    }
+
+    let unit = if style.incoming_starts_with_lead() {
+        beat * beats_per_bar.max(1) as f32
+    } else {
+        beat
+    };
+    ((limit as f32 / unit).floor() * unit) as usize
+}
+
+/// Topology-preserving state variable filter (one channel)
+#[derive(Debug, Clone, Copy, Default)]
+struct StateVariableFilter {
+    ic1: f32,
+    ic2: f32,
+}
+
+/// Low-pass, band-pass and high-pass outputs of one filter step
+struct FilterOutput {
+    low: f32,
+    band: f32,
+    high: f32,
+}
+
+impl StateVariableFilter {
+    /// Butterworth damping (1/Q)
+    const BUTTERWORTH: f32 = std::f32::consts::SQRT_2;
+
+    /// Filter one sample at `cutoff` Hz with the given damping (1/Q)
+    fn process(&mut self, input: f32, cutoff: f32, damping: f32) -> FilterOutput {
+        let g = (PI * cutoff.min(SAMPLE_RATE as f32 * 0.45) / SAMPLE_RATE as f32).tan();
+        let a1 = 1.0 / (1.0 + g * (g + damping));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        let v3 = input - self.ic2;
+        let v1 = a1 * self.ic1 + a2 * v3;
+        let v2 = self.ic2 + a2 * self.ic1 + a3 * v3;
+        self.ic1 = 2.0 * v1 - self.ic1;
+        self.ic2 = 2.0 * v2 - self.ic2;
+
+        FilterOutput {
+            low: v2,
+            band: v1,
+            high: input - damping * v1 - v2,
+        }
+    }
+}
+
+/// Xorshift white noise generator
+struct Noise(u32);
+
+impl Noise {
+    /// Next sample in [-1, 1)
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+/// Half-sine bump over `start..end` of a transition, zero elsewhere
+fn bump(progress: f32, (start, end): (f32, f32)) -> f32 {
+    if progress <= start || progress >= end {
+        return 0.0;
+    }
+    (PI * (progress - start) / (end - start)).sin()
+}
+
+/// Exponential sweep between two frequencies
+fn sweep((from, to): (f32, f32), progress: f32) -> f32 {
+    from * (to / from).powf(progress)
+}
+
+/// Per-style processing state
+enum Effect {
+    Cut,
+    Crossfade,
+    Echo {
+        /// Interleaved delay line
+        delay: Vec<f32>,
+        position: usize,
+        /// Frames of the outgoing clip sent into the delay
+        send_frames: usize,
+    },
+    FilterSweep {
+        outgoing: [StateVariableFilter; FRAME],
+        incoming: [StateVariableFilter; FRAME],
+    },
+    Backspin {
+        samples: Arc<[f32]>,
+        /// Playhead in frames
+        position: f64,
+    },
+    Tuning {
+        /// Next clip, heard faintly through the noise before it starts
+        glimpse: Option<Arc<[f32]>>,
+        /// Frame of the next clip the glimpse starts from
+        glimpse_offset: usize,
+        glimpse_filters: [StateVariableFilter; FRAME],
+        noise: Noise,
+        noise_filter: StateVariableFilter,
+        /// Oscillator phase of each whistle (cycles)
+        whistle_phases: [f32; TUNING_CARRIERS.len()],
+    },
+}
+
+/// A transition in progress between the outgoing and the incoming deck
+pub struct Transition {
+    effect: Effect,
+    /// Frames since the transition started
+    elapsed: usize,
+    /// Frame (from the start) at which the incoming clip starts playing
+    incoming_start: usize,
+    /// Total length in frames
+    length: usize,
+}
+
+impl Transition {
+    /// Start a transition `lead` frames before the end of `outgoing`
+    ///
+    /// `position` is the outgoing clip's playhead in frames and `next` the
+    /// clip taking over, if any.
+    pub fn new(
+        style: TransitionStyle,
+        outgoing: &LoopBuffer,
+        position: usize,
+        next: Option<&LoopBuffer>,
+        lead: usize,
+    ) -> Self {
+        let beat = beat_frames(outgoing);
+
+        match style {
+            TransitionStyle::Cut => Self::with_effect(Effect::Cut, lead, lead),
+            TransitionStyle::Crossfade(_) => Self::crossfade(lead),
+            TransitionStyle::EchoOut => {
+                let delay_frames = ((ECHO_DELAY_BEATS * beat) as usize).max(1);
+                // Like the lead, ring on over at most half of the incoming clip
+                let tail = ((ECHO_TAIL_BEATS * beat) as usize)
+                    .min(next.map_or(usize::MAX, |next| next.frame_count() / 2));
+                let effect = Effect::Echo {
+                    delay: vec![0.0; delay_frames * FRAME],
+                    position: 0,
+                    send_frames: lead,
+                };
+                Self::with_effect(effect, lead, lead + tail)
+            }
+            TransitionStyle::FilterSweep => {
+                let effect = Effect::FilterSweep {
+                    outgoing: Default::default(),
+                    incoming: Default::default(),
+                };
+                Self::with_effect(effect, 0, lead)
+            }
+            TransitionStyle::Backspin => {
+                let effect = Effect::Backspin {
+                    samples: Arc::clone(&outgoing.samples),
+                    position: position as f64,
+                };
+                Self::with_effect(effect, lead, lead)
+            }
+            TransitionStyle::Tuning => {
+                let effect = Effect::Tuning {
+                    glimpse: next.map(|next| Arc::clone(&next.samples)),
+                    // From the middle of the next clip, so its downbeat is still fresh
+                    glimpse_offset: next.map_or(0, |next| next.frame_count() / 2),
+                    glimpse_filters: Default::default(),
+                    noise: Noise(0x9E37_79B9 ^ position as u32 | 1),
+                    noise_filter: StateVariableFilter::default(),
+                    whistle_phases: [0.0; TUNING_CARRIERS.len()],
+                };
+                Self::with_effect(effect, lead, lead)
+            }
+        }
+    }
+
+    /// Equal-power crossfade starting both clips together
+    pub fn crossfade(length: usize) -> Self {
+        Self::with_effect(Effect::Crossfade, 0, length)
+    }
+
+    fn with_effect(effect: Effect, incoming_start: usize, length: usize) -> Self {
+        Self {
+            effect,
+            elapsed: 0,
+            incoming_start,
+            length,
+        }
+    }
+
+    /// Whether the incoming clip should be playing yet
+    pub fn incoming_started(&self) -> bool {
+        self.elapsed >= self.incoming_start
+    }
+
+    /// Whether the transition has finished and the incoming clip plays alone
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.length
+    }
+
+    /// Mix one frame of each deck into `out`
+    pub fn mix_frame(&mut self, outgoing: &[f32], incoming: &[f32], out: &mut [f32]) {
+        let progress = if self.length == 0 {
+            1.0
+        } else {
+            (self.elapsed as f32 / self.length as f32).min(1.0)
+        };
+        let elapsed = self.elapsed;
+        let length = self.length;
+        self.elapsed += 1;
+
+        match &mut self.effect {
+            Effect::Cut => {
+                let source = if elapsed < self.incoming_start { outgoing } else { incoming };
+                out.copy_from_slice(source);
+            }
+            Effect::Crossfade => {
+                let (out_gain, in_gain) = equal_power(progress);
+                for ((sample, a), b) in out.iter_mut().zip(outgoing).zip(incoming) {
+                    *sample = a * out_gain + b * in_gain;
+                }
+            }
+            Effect::Echo {
+                delay,
+                position,
+                send_frames,
+            } => {
+                // Echoes fade away over the tail so the transition ends in silence
+                let tail_gain = if elapsed < *send_frames {
+                    1.0
+                } else {
+                    let tail = (length - *send_frames).max(1);
+                    1.0 - (elapsed - *send_frames) as f32 / tail as f32
+                };
+                let send = elapsed < *send_frames;
+
+                for ch in 0..FRAME {
+                    let index = *position + ch;
+                    let wet = delay[index];
+                    let input = if send { outgoing[ch] } else { 0.0 };
+                    delay[index] = input + wet * ECHO_FEEDBACK;
+                    out[ch] = outgoing[ch] + wet * tail_gain + incoming[ch];
+                }
+                *position = (*position + FRAME) % delay.len();
+            }
+            Effect::FilterSweep {
+                outgoing: out_filters,
+                incoming: in_filters,
+            } => {
+                let low_pass = sweep(OUTGOING_LOW_PASS, progress);
+                let high_pass = sweep(INCOMING_HIGH_PASS, progress);
+                let (out_gain, in_gain) = equal_power(progress);
+
+                for ch in 0..FRAME {
+                    let low = out_filters[ch].process(outgoing[ch], low_pass, StateVariableFilter::BUTTERWORTH).low;
+                    let high = in_filters[ch].process(incoming[ch], high_pass, StateVariableFilter::BUTTERWORTH).high;
+                    out[ch] = low * out_gain + high * in_gain;
+                }
+            }
+            Effect::Backspin { samples, position } => {
+                let frames = samples.len() / FRAME;
+                let t = progress as f64;
+
+                // Grab the platter and pull it backwards, then let it slow down
+                let speed = if t < BACKSPIN_GRAB {
+                    1.0 - (1.0 + BACKSPIN_SPEED) * t / BACKSPIN_GRAB
+                } else {
+                    let spin = (t - BACKSPIN_GRAB) / (1.0 - BACKSPIN_GRAB);
+                    -BACKSPIN_SPEED * (1.0 - spin).powi(2)
+                };
+                *position = (*position + speed).clamp(0.0, frames.saturating_sub(1) as f64);
+
+                let index = position.floor() as usize;
+                let frac = (*position - index as f64) as f32;
+                let next = (index + 1).min(frames.saturating_sub(1));
+                let gain = (progress * FRAC_PI_2).cos();
+
+                for ch in 0..FRAME {
+                    let a = samples.get(index * FRAME + ch).copied().unwrap_or(0.0);
+                    let b = samples.get(next * FRAME + ch).copied().unwrap_or(0.0);
+                    let spun = (a + (b - a) * frac) * gain;
+                    out[ch] = if elapsed < self.incoming_start { spun } else { incoming[ch] };
+                }
+            }
+            Effect::Tuning {
+                glimpse,
+                glimpse_offset,
+                glimpse_filters,
+                noise,
+                noise_filter,
+                whistle_phases,
+            } => {
+                if elapsed >= self.incoming_start {
+                    out.copy_from_slice(incoming);
+                    return;
+                }
+
+                // The outgoing station drops away over the first quarter
+                let out_gain = if progress < 0.25 { (progress * 4.0 * FRAC_PI_2).cos() } else { 0.0 };
+                let static_level = (progress * PI).sin();
+
+                let hiss = noise_filter
+                    .process(noise.next(), TUNING_NOISE_CENTRE, 1.0)
+                    .band
+                    * TUNING_NOISE_LEVEL
+                    * static_level;
+
+                // Each whistle is the beat between two carriers: its pitch falls to
+                // nothing as the dial crosses a station and climbs again after
+                let mut whistles = 0.0;
+                for (phase, carrier) in whistle_phases.iter_mut().zip(TUNING_CARRIERS) {
+                    let distance = progress - carrier;
+                    let pitch = TUNING_WHISTLE_MAX * (distance.abs() * 8.0).min(1.0);
+                    *phase = (*phase + pitch / SAMPLE_RATE as f32).fract();
+                    let level = (-(distance / 0.12).powi(2)).exp();
+                    whistles += (2.0 * PI * *phase).sin() * level * TUNING_WHISTLE_LEVEL;
+                }
+
+                let glimpse_gain = bump(progress, TUNING_GLIMPSE_WINDOW) * TUNING_GLIMPSE_GAIN;
+                for ch in 0..FRAME {
+                    let next = glimpse.as_ref().map_or(0.0, |samples| {
+                        let frames = (samples.len() / FRAME).max(1);
+                        let frame = (*glimpse_offset + elapsed) % frames;
+                        samples.get(frame * FRAME + ch).copied().unwrap_or(0.0)
+                    });
+                    let narrow = glimpse_filters[ch]
+                        .process(next, TUNING_GLIMPSE_CENTRE, TUNING_GLIMPSE_DAMPING)
+                        .band;
+                    out[ch] = outgoing[ch] * out_gain + hiss + whistles + narrow * glimpse_gain;
+                }
+            }
+        }
+    }
+}
+
+/// Equal-power gains (outgoing, incoming) at a point of a crossfade
+fn equal_power(progress: f32) -> (f32, f32) {
+    let angle = progress * FRAC_PI_2;
+    (angle.cos(), angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::LoopInfo;
+
+    /// Clip at 120 BPM in 4/4 built from a per-frame mono signal
+    fn clip(secs: f32, signal: impl Fn(usize) -> f32) -> LoopBuffer {
+        let frames = (secs * SAMPLE_RATE as f32) as usize;
+        let samples = (0..frames).flat_map(|i| [signal(i), signal(i)]).collect();
+        LoopBuffer::new(
+            samples,
+            LoopInfo {
+                bars: 2,
+                ..LoopInfo::for_test(120.0, frames)
+            },
+        )
+    }
+
+    /// Run a transition over the tail of `outgoing` with silence incoming
+    fn run(style: TransitionStyle, outgoing: &LoopBuffer) -> Vec<f32> {
+        let silence = clip(4.0, |_| 0.0);
+        let lead = lead_frames(style, outgoing, &silence);
+        let start = outgoing.frame_count() - lead;
+        let mut transition = Transition::new(style, outgoing, start, Some(&silence), lead);
+
+        let mut output = Vec::new();
+        let mut frame = [0.0; FRAME];
+        let mut position = start;
+        while !transition.is_done() {
+            let out_frame: &[f32] = outgoing.samples.get(position * FRAME..(position + 1) * FRAME).unwrap_or(&[0.0; FRAME]);
+            transition.mix_frame(out_frame, &[0.0; FRAME], &mut frame);
+            output.push(frame[0]);
+            position += 1;
+        }
+        output
+    }
+
+    #[test]
+    fn test_parse_styles() {
+        assert_eq!("cut".parse(), Ok(TransitionStyle::Cut));
+        assert_eq!("crossfade".parse(), Ok(TransitionStyle::Crossfade(1)));
+        assert_eq!("crossfade:4".parse(), Ok(TransitionStyle::Crossfade(4)));
+        assert_eq!("Echo".parse(), Ok(TransitionStyle::EchoOut));
+        assert_eq!("filter".parse(), Ok(TransitionStyle::FilterSweep));
+        assert_eq!("backspin".parse(), Ok(TransitionStyle::Backspin));
+        assert_eq!("tuning".parse(), Ok(TransitionStyle::Tuning));
+        assert!("crossfade:0".parse::<TransitionStyle>().is_err());
+        assert!("wobble".parse::<TransitionStyle>().is_err());
+    }
+
+    #[test]
+    fn test_lead_is_defined_in_beats() {
+        let outgoing = clip(4.0, |_| 0.0);
+        let next = clip(4.0, |_| 0.0);
+        // 120 BPM: one beat is 24000 frames, one 4/4 bar 96000
+        assert_eq!(lead_frames(TransitionStyle::Cut, &outgoing, &next), 0);
+        assert_eq!(lead_frames(TransitionStyle::Crossfade(1), &outgoing, &next), 96_000);
+        assert_eq!(lead_frames(TransitionStyle::EchoOut, &outgoing, &next), 24_000);
+        assert_eq!(lead_frames(TransitionStyle::Backspin, &outgoing, &next), 24_000);
+        // Capped at half a clip
+        assert_eq!(lead_frames(TransitionStyle::Crossfade(4), &outgoing, &next), 96_000);
+    }
+
+    #[test]
+    fn test_capped_lead_keeps_whole_bars() {
+        // One 4/4 bar: half of it is two beats
+        let outgoing = clip(2.0, |_| 0.0);
+        let next = clip(2.0, |_| 0.0);
+        // The incoming clip would start mid-bar, so the lead drops to a cut
+        assert_eq!(lead_frames(TransitionStyle::Crossfade(2), &outgoing, &next), 0);
+        assert_eq!(lead_frames(TransitionStyle::FilterSweep, &outgoing, &next), 0);
+        // The incoming clip starts when the outgoing one ends: whole beats are enough
+        assert_eq!(lead_frames(TransitionStyle::Tuning, &outgoing, &next), 48_000);
+
+        // Two bars: the two bar crossfade keeps one
+        let outgoing = clip(4.0, |_| 0.0);
+        let next = clip(2.0, |_| 0.0);
+        assert_eq!(lead_frames(TransitionStyle::Crossfade(2), &outgoing, &next), 0);
+        let next = clip(4.0, |_| 0.0);
+        assert_eq!(lead_frames(TransitionStyle::Crossfade(2), &outgoing, &next), 96_000);
+    }
+
+    #[test]
+    fn test_echo_tail_fits_short_incoming_clip() {
+        let outgoing = clip(4.0, |_| 0.5);
+        // Half a second incoming: the four beat tail would outlast it
+        let next = clip(0.5, |_| 0.0);
+        let lead = lead_frames(TransitionStyle::EchoOut, &outgoing, &next);
+        let start = outgoing.frame_count() - lead;
+        let transition = Transition::new(TransitionStyle::EchoOut, &outgoing, start, Some(&next), lead);
+
+        assert_eq!(transition.incoming_start, lead);
+        assert_eq!(transition.length, lead + next.frame_count() / 2);
+    }
+
+    #[test]
+    fn test_echo_rings_after_outgoing_ends() {
+        let outgoing = clip(4.0, |i| if i % 2400 < 100 { 0.8 } else { 0.0 });
+        let output = run(TransitionStyle::EchoOut, &outgoing);
+
+        // One beat of send, four beats of decaying tail
+        assert_eq!(output.len(), 5 * 24_000);
+        let tail = &output[24_000..];
+        let early = tail[..24_000].iter().fold(0.0f32, |a, b| a.max(b.abs()));
+        let late = tail[tail.len() - 2_400..].iter().fold(0.0f32, |a, b| a.max(b.abs()));
+        assert!(early > 0.2, "No echo after the downbeat: {}", early);
+        assert!(late < 0.05, "Echo did not die away: {}", late);
+    }
+
+    #[test]
+    fn test_filter_sweep_removes_highs() {
+        let tone = |i: usize| (2.0 * PI * 8_000.0 * i as f32 / SAMPLE_RATE as f32).sin() * 0.5;
+        let outgoing = clip(4.0, tone);
+        let output = run(TransitionStyle::FilterSweep, &outgoing);
+
+        let peak = |range: &[f32]| range.iter().fold(0.0f32, |a, b| a.max(b.abs()));
+        let start = peak(&output[1_000..5_000]);
+        let end = peak(&output[output.len() - 10_000..]);
+        assert!(start > 0.4, "Tone filtered too early: {}", start);
+        assert!(end < 0.02, "Tone survived the sweep: {}", end);
+    }
+
+    #[test]
+    fn test_backspin_fades_to_silence() {
+        let outgoing = clip(4.0, |i| (i as f32 * 0.01).sin() * 0.5);
+        let output = run(TransitionStyle::Backspin, &outgoing);
+
+        assert_eq!(output.len(), 24_000);
+        assert!(output.iter().all(|s| s.is_finite() && s.abs() <= 0.5 + 1e-3));
+        assert!(output[output.len() - 1].abs() < 0.01);
+    }
+
+    #[test]
+    fn test_tuning_fills_the_bar_with_static() {
+        // Silent clips: everything heard is synthesized
+        let outgoing = clip(4.0, |_| 0.0);
+        let output = run(TransitionStyle::Tuning, &outgoing);
+
+        assert_eq!(output.len(), 96_000);
+        let peak = |range: &[f32]| range.iter().fold(0.0f32, |a, b| a.max(b.abs()));
+        let middle = peak(&output[40_000..56_000]);
+        assert!(middle > 0.05, "No static mid-transition: {}", middle);
+        assert!(peak(&output) < 1.0);
+        assert!(peak(&output[output.len() - 100..]) < 0.02, "Static runs into the downbeat");
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Preferences file name inside the config directory
+const PREFS_FILE: &str = "prefs.json";
+
+/// Preferences kept between sessions
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Playback volume (0.0 to 1.0)
+    pub volume: f32,
+    /// Output muted
+    pub muted: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl Preferences {
+    /// Load saved preferences, falling back to defaults
+    pub fn load() -> Self {
+        let Some(path) = prefs_path() else {
+            return Self::default();
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(path = ?path, error = %e, "Ignoring invalid preferences file");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Save preferences, logging (not failing) on errors
+    pub fn save(&self) {
+        let Some(path) = prefs_path() else {
+            return;
+        };
+
+        if let Some(dir) = path.parent() {
+            if let Err(e) = std::fs::create_dir_all(dir) {
+                warn!(path = ?dir, error = %e, "Failed to create config directory");
+                return;
+            }
+        }
+
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => match std::fs::write(&path, json) {
+                Ok(()) => debug!(path = ?path, "Saved preferences"),
+                Err(e) => warn!(path = ?path, error = %e, "Failed to save preferences"),
+            },
+            Err(e) => warn!(error = %e, "Failed to serialize preferences"),
+        }
+    }
+}
+
+/// Path of the preferences file (None without a config directory)
+fn prefs_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tappr").join(PREFS_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let prefs: Preferences = serde_json::from_str(r#"{"volume": 0.4}"#).unwrap();
+        assert_eq!(prefs, Preferences { volume: 0.4, muted: false });
+
+        let json = serde_json::to_string(&prefs).unwrap();
+        assert_eq!(serde_json::from_str::<Preferences>(&json).unwrap(), prefs);
+    }
+}
@@ -1,15 +1,40 @@
+use std::path::PathBuf;
+
 use tokio::sync::mpsc;
 
-use crate::app::StationInfo;
+use crate::app::{MusicalKey, StationInfo};
 use crate::audio::LoopBuffer;
+use crate::playback::AudioDevice;
 
 /// Commands from TUI/input to producer task
 #[derive(Debug)]
 pub enum ProducerCommand {
     /// Skip to next station immediately
     NextStation,
-    /// Audio device changed - main loop should recreate playback engine
-    AudioDeviceChanged(usize),
+    /// Audio device chosen (by name) - main loop should move playback to it
+    AudioDeviceChanged(String),
+    /// Re-quantize the current clip with its tempo scaled by this factor
+    Requantize(f32),
+    /// Hold or release the current loop
+    ToggleHold,
+    /// Play a clip from the history again (by `LoopBuffer::id`)
+    Replay(u64),
+    /// Export the current loop as a WAV file
+    Export,
+    /// Start or stop recording the session mix
+    ToggleRecord,
+    /// Preview the next queued clip on the cue device (or stop previewing)
+    ToggleCue,
+    /// Drop the clip being previewed from the queue
+    DropCued,
+    /// Drop a clip from the queue before it plays
+    RemoveQueued(u64),
+    /// Move a queued clip this many places later (negative: earlier)
+    MoveQueued(u64, isize),
+    /// Play a queued clip next
+    PinQueued(u64),
+    /// A clip with this key started playing (harmonic sequencing follows it)
+    NowPlayingKey(Option<MusicalKey>),
     /// Shutdown the producer
     Quit,
 }
@@ -26,8 +51,37 @@ pub enum ProducerEvent {
     Error(String),
     /// Skip current station and advance to next
     SkipCurrent,
-    /// Audio device changed - main loop should recreate playback engine
-    AudioDeviceChanged(usize),
+    /// Audio device chosen (by name) - main loop should move playback to it
+    AudioDeviceChanged(String),
+    /// Output devices re-enumerated (picks up plugged and unplugged devices)
+    DevicesScanned {
+        devices: Vec<AudioDevice>,
+        default: Option<String>,
+    },
+    /// Re-quantize the current clip with its tempo scaled by this factor
+    Requantize(f32),
+    /// Re-quantized replacement for the current clip is ready
+    Requantized(LoopBuffer),
+    /// Hold or release the current loop
+    ToggleHold,
+    /// Play a clip from the history again (by `LoopBuffer::id`)
+    Replay(u64),
+    /// Export the current loop as a WAV file
+    Export,
+    /// Loop export finished, written to this path
+    Exported(PathBuf),
+    /// Start or stop recording the session mix
+    ToggleRecord,
+    /// Preview the next queued clip on the cue device (or stop previewing)
+    ToggleCue,
+    /// Drop the clip being previewed from the queue
+    DropCued,
+    /// Drop a clip from the queue before it plays
+    RemoveQueued(u64),
+    /// Move a queued clip this many places later (negative: earlier)
+    MoveQueued(u64, isize),
+    /// Play a queued clip next
+    PinQueued(u64),
     /// Producer is shutting down
     Shutdown,
 }
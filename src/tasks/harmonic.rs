@@ -0,0 +1,175 @@
+use std::time::{Duration, Instant};
+
+use crate::app::{MusicalKey, StationInfo};
+use crate::audio::LoopBuffer;
+
+/// Compatibility assumed for clips without a detected key
+const UNKNOWN_KEY_COMPATIBILITY: f32 = 0.5;
+
+/// Small pool of finished clips held back for harmonic sequencing
+///
+/// The producer coordinator parks clips here and releases the one that best
+/// follows the key of the clip now playing on the Camelot wheel (the last
+/// released key until playback reports one).
+pub struct HarmonicPool {
+    clips: Vec<PooledClip>,
+    capacity: usize,
+    max_hold: Duration,
+    /// Key of the clip now playing (the one the next clip follows)
+    playing_key: Option<MusicalKey>,
+    /// Key of the most recently released clip
+    last_key: Option<MusicalKey>,
+    /// Whether any clip has been released yet
+    started: bool,
+}
+
+struct PooledClip {
+    buffer: LoopBuffer,
+    station: StationInfo,
+    added: Instant,
+}
+
+impl HarmonicPool {
+    pub fn new(capacity: usize, max_hold: Duration) -> Self {
+        Self {
+            clips: Vec::new(),
+            capacity: capacity.max(1),
+            max_hold,
+            playing_key: None,
+            last_key: None,
+            started: false,
+        }
+    }
+
+    /// Follow the key of the clip that started playing (kept if it has none)
+    ///
+    /// Skips and replays change what is playing, so this is what the next clip
+    /// has to fit rather than whatever was released last.
+    pub fn set_playing_key(&mut self, key: Option<MusicalKey>) {
+        if key.is_some() {
+            self.playing_key = key;
+        }
+    }
+
+    /// Add a finished clip to the pool
+    pub fn push(&mut self, buffer: LoopBuffer, station: StationInfo) {
+        self.clips.push(PooledClip {
+            buffer,
+            station,
+            added: Instant::now(),
+        });
+    }
+
+    /// Release a clip if one is due
+    ///
+    /// The first clip goes out immediately so playback can start. After that
+    /// a clip is released once the pool is full or its oldest clip has waited
+    /// longer than the maximum hold time.
+    pub fn release(&mut self) -> Option<(LoopBuffer, StationInfo)> {
+        let oldest_expired = self
+            .clips
+            .iter()
+            .any(|clip| clip.added.elapsed() >= self.max_hold);
+
+        if !self.clips.is_empty() && (!self.started || self.clips.len() >= self.capacity || oldest_expired) {
+            self.take_best()
+        } else {
+            None
+        }
+    }
+
+    /// Remove and return the clip most compatible with the playing key
+    fn take_best(&mut self) -> Option<(LoopBuffer, StationInfo)> {
+        let follow_key = self.playing_key.or(self.last_key);
+        let score = |clip: &PooledClip| match (follow_key, clip.buffer.loop_info.key) {
+            (Some(from), Some(to)) => from.compatibility(&to),
+            _ => UNKNOWN_KEY_COMPATIBILITY,
+        };
+
+        // Ties go to the clip that has waited longest
+        let index = self
+            .clips
+            .iter()
+            .enumerate()
+            .max_by(|(ia, a), (ib, b)| score(a).total_cmp(&score(b)).then(ib.cmp(ia)))
+            .map(|(index, _)| index)?;
+
+        let clip = self.clips.remove(index);
+        if clip.buffer.loop_info.key.is_some() {
+            self.last_key = clip.buffer.loop_info.key;
+        }
+        self.started = true;
+
+        Some((clip.buffer, clip.station))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{LoopInfo, Mode};
+
+    fn clip(key: Option<MusicalKey>) -> (LoopBuffer, StationInfo) {
+        let loop_info = LoopInfo {
+            key,
+            ..LoopInfo::for_test(120.0, 100)
+        };
+        let station = StationInfo::default();
+        (LoopBuffer::new(vec![0.0; 200], loop_info), station)
+    }
+
+    #[test]
+    fn test_releases_most_compatible_clip() {
+        let mut pool = HarmonicPool::new(3, Duration::from_secs(60));
+        let c_major = MusicalKey::new(0, Mode::Major);
+
+        let (buffer, station) = clip(Some(c_major));
+        pool.push(buffer, station);
+        let first = pool.release().expect("First clip is released immediately");
+        assert_eq!(first.0.loop_info.key, Some(c_major));
+
+        for key in [MusicalKey::new(6, Mode::Major), MusicalKey::new(9, Mode::Minor)] {
+            let (buffer, station) = clip(Some(key));
+            pool.push(buffer, station);
+            assert!(pool.release().is_none(), "Pool released before filling up");
+        }
+
+        let (buffer, station) = clip(None);
+        pool.push(buffer, station);
+        let next = pool.release().expect("Full pool releases a clip");
+        assert_eq!(next.0.loop_info.key, Some(MusicalKey::new(9, Mode::Minor)));
+    }
+
+    #[test]
+    fn test_follows_playing_key_over_last_released() {
+        let mut pool = HarmonicPool::new(2, Duration::from_secs(60));
+        let c_major = MusicalKey::new(0, Mode::Major);
+        let a_minor = MusicalKey::new(9, Mode::Minor);
+
+        let (buffer, station) = clip(Some(c_major));
+        pool.push(buffer, station);
+        assert!(pool.release().is_some());
+
+        // A replayed clip far from C major is what the next clip has to follow
+        let f_sharp_major = MusicalKey::new(6, Mode::Major);
+        pool.set_playing_key(Some(f_sharp_major));
+        pool.set_playing_key(None);
+        for key in [a_minor, MusicalKey::new(3, Mode::Minor)] {
+            let (buffer, station) = clip(Some(key));
+            pool.push(buffer, station);
+        }
+        let next = pool.release().expect("Full pool releases a clip");
+        assert_eq!(next.0.loop_info.key, Some(MusicalKey::new(3, Mode::Minor)));
+    }
+
+    #[test]
+    fn test_releases_after_max_hold() {
+        let mut pool = HarmonicPool::new(3, Duration::ZERO);
+
+        for _ in 0..2 {
+            let (buffer, station) = clip(None);
+            pool.push(buffer, station);
+            assert!(pool.release().is_some());
+        }
+    }
+}
@@ -1,4 +1,5 @@
 mod channels;
+mod harmonic;
 mod producer;
 
 pub use channels::{Channels, ProducerCommand, ProducerEvent};
@@ -4,26 +4,39 @@ use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{debug, info, instrument, warn};
 
-use crate::app::{AppState, BpmMode};
-use crate::audio::AudioPipeline;
+use crate::app::{AppState, BpmMode, KeyMode, MeterMode};
+use crate::audio::{AudioPipeline, KeyLock};
 use crate::radio::{RadioCache, RadioService};
 
 use super::channels::{ProducerCommand, ProducerEvent};
+use super::harmonic::HarmonicPool;
 
 /// Number of parallel fetch workers
 const NUM_WORKERS: usize = 5;
 
+/// Workers stop fetching once playback has been paused this long
+const PAUSE_IDLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Number of finished clips held back in harmonic mode
+const HARMONIC_POOL_SIZE: usize = 3;
+
 /// Producer task configuration
 #[derive(Clone)]
 pub struct ProducerConfig {
     pub search: Option<String>,
     pub region: Option<String>,
     pub listen_seconds: u32,
-    #[allow(dead_code)]
     pub station_change_seconds: u32,
     pub bars: u8,
-    pub beats_per_bar: u8,
+    pub meter: MeterMode,
     pub bpm_mode: BpmMode,
+    pub key_mode: KeyMode,
+    /// Loudness normalization target (LUFS)
+    pub target_lufs: f32,
+    /// True-peak ceiling after normalization (dBTP)
+    pub true_peak_ceiling: f32,
+    /// Release clips in harmonically compatible order instead of completion order
+    pub harmonic: bool,
 }
 
 /// Producer coordinator that spawns parallel fetch workers
@@ -107,6 +120,9 @@ impl Producer {
         // Channel for workers to send completed clips (larger buffer for aggressive pre-fetch)
         let (clip_tx, mut clip_rx) = mpsc::channel::<(crate::audio::LoopBuffer, crate::app::StationInfo)>(NUM_WORKERS * 3);
 
+        // Workers share the target key in lock-to-first mode
+        let key_lock = KeyLock::default();
+
         // Spawn worker tasks with shared cache
         for worker_id in 0..NUM_WORKERS {
             let config = self.config.clone();
@@ -117,6 +133,7 @@ impl Producer {
             let shared_cache = Arc::clone(&shared_cache);
             let bpm_min = self.bpm_min;
             let bpm_max = self.bpm_max;
+            let key_lock = Arc::clone(&key_lock);
 
             tokio::spawn(async move {
                 run_worker(
@@ -129,6 +146,7 @@ impl Producer {
                     shared_cache,
                     bpm_min,
                     bpm_max,
+                    key_lock,
                 ).await;
             });
         }
@@ -136,12 +154,36 @@ impl Producer {
         // Drop our copy of clip_tx so channel closes when all workers finish
         drop(clip_tx);
 
+        // In harmonic mode clips wait in a small pool, at most one station change long
+        let mut harmonic_pool = self.config.harmonic.then(|| {
+            HarmonicPool::new(
+                HARMONIC_POOL_SIZE,
+                Duration::from_secs(self.config.station_change_seconds as u64),
+            )
+        });
+        let mut release_tick = tokio::time::interval(Duration::from_secs(1));
+
         // Main coordinator loop
         loop {
             tokio::select! {
                 // Receive completed clips from workers and forward to main
                 Some((buffer, station)) = clip_rx.recv() => {
-                    let _ = self.event_tx.send(ProducerEvent::LoopReady(buffer, station)).await;
+                    match harmonic_pool.as_mut() {
+                        Some(pool) => {
+                            pool.push(buffer, station);
+                            self.release_pooled(pool).await;
+                        }
+                        None => {
+                            let _ = self.event_tx.send(ProducerEvent::LoopReady(buffer, station)).await;
+                        }
+                    }
+                }
+
+                // Release clips that have waited too long for a better match
+                _ = release_tick.tick(), if harmonic_pool.is_some() => {
+                    if let Some(pool) = harmonic_pool.as_mut() {
+                        self.release_pooled(pool).await;
+                    }
                 }
 
                 // Handle commands from TUI
@@ -152,9 +194,54 @@ impl Producer {
                             // Signal main loop to skip current playback
                             let _ = self.event_tx.send(ProducerEvent::SkipCurrent).await;
                         }
-                        ProducerCommand::AudioDeviceChanged(device_index) => {
-                            debug!(device_index, "Received AudioDeviceChanged command");
-                            let _ = self.event_tx.send(ProducerEvent::AudioDeviceChanged(device_index)).await;
+                        ProducerCommand::AudioDeviceChanged(device) => {
+                            debug!(%device, "Received AudioDeviceChanged command");
+                            let _ = self.event_tx.send(ProducerEvent::AudioDeviceChanged(device)).await;
+                        }
+                        ProducerCommand::Requantize(factor) => {
+                            debug!(factor, "Received Requantize command");
+                            let _ = self.event_tx.send(ProducerEvent::Requantize(factor)).await;
+                        }
+                        ProducerCommand::ToggleHold => {
+                            debug!("Received ToggleHold command");
+                            let _ = self.event_tx.send(ProducerEvent::ToggleHold).await;
+                        }
+                        ProducerCommand::Replay(clip_id) => {
+                            debug!(clip_id, "Received Replay command");
+                            let _ = self.event_tx.send(ProducerEvent::Replay(clip_id)).await;
+                        }
+                        ProducerCommand::Export => {
+                            debug!("Received Export command");
+                            let _ = self.event_tx.send(ProducerEvent::Export).await;
+                        }
+                        ProducerCommand::ToggleRecord => {
+                            debug!("Received ToggleRecord command");
+                            let _ = self.event_tx.send(ProducerEvent::ToggleRecord).await;
+                        }
+                        ProducerCommand::ToggleCue => {
+                            debug!("Received ToggleCue command");
+                            let _ = self.event_tx.send(ProducerEvent::ToggleCue).await;
+                        }
+                        ProducerCommand::DropCued => {
+                            debug!("Received DropCued command");
+                            let _ = self.event_tx.send(ProducerEvent::DropCued).await;
+                        }
+                        ProducerCommand::RemoveQueued(clip_id) => {
+                            debug!(clip_id, "Received RemoveQueued command");
+                            let _ = self.event_tx.send(ProducerEvent::RemoveQueued(clip_id)).await;
+                        }
+                        ProducerCommand::MoveQueued(clip_id, offset) => {
+                            debug!(clip_id, offset, "Received MoveQueued command");
+                            let _ = self.event_tx.send(ProducerEvent::MoveQueued(clip_id, offset)).await;
+                        }
+                        ProducerCommand::PinQueued(clip_id) => {
+                            debug!(clip_id, "Received PinQueued command");
+                            let _ = self.event_tx.send(ProducerEvent::PinQueued(clip_id)).await;
+                        }
+                        ProducerCommand::NowPlayingKey(key) => {
+                            if let Some(pool) = harmonic_pool.as_mut() {
+                                pool.set_playing_key(key);
+                            }
                         }
                         ProducerCommand::Quit => {
                             info!("Received Quit command");
@@ -177,6 +264,18 @@ impl Producer {
         info!("Producer shutting down");
         let _ = self.event_tx.send(ProducerEvent::Shutdown).await;
     }
+
+    /// Forward any clips the harmonic pool is ready to release
+    async fn release_pooled(&self, pool: &mut HarmonicPool) {
+        while let Some((buffer, station)) = pool.release() {
+            debug!(
+                station = %station.name,
+                key = ?buffer.loop_info.key.map(|k| k.name()),
+                "Releasing clip from harmonic pool"
+            );
+            let _ = self.event_tx.send(ProducerEvent::LoopReady(buffer, station)).await;
+        }
+    }
 }
 
 /// Worker task that continuously fetches and processes stations
@@ -190,12 +289,15 @@ async fn run_worker(
     shared_cache: Arc<RadioCache>,
     bpm_min: f32,
     bpm_max: f32,
+    key_lock: KeyLock,
 ) {
     info!(worker_id, "Worker starting");
 
     // Each worker gets its own radio client (with shared cache) and audio pipeline
     let radio = RadioService::with_shared_cache(rate_limit_ms, shared_cache);
-    let audio = AudioPipeline::new(bpm_min, bpm_max);
+    let audio = AudioPipeline::new(bpm_min, bpm_max)
+        .with_key_lock(key_lock)
+        .with_loudness(config.target_lufs, config.true_peak_ceiling);
 
     // Worker 0 starts immediately for quick first clip
     // Other workers stagger slightly to avoid thundering herd (but not too much)
@@ -211,6 +313,12 @@ async fn run_worker(
             break;
         }
 
+        // Don't fill the queue (and burn bandwidth) during a long pause
+        if state.paused_for().is_some_and(|paused| paused >= PAUSE_IDLE_AFTER) {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            continue;
+        }
+
         // Fetch and process a station
         let result = if is_first_clip {
             // Quick-start mode for first clip: fast capture, no time-stretch
@@ -291,7 +399,7 @@ async fn fetch_and_process_quick(
 
     // Quick process audio (shorter capture, no time-stretch)
     let buffer = audio
-        .process_station_quick(&station, config.beats_per_bar)
+        .process_station_quick(&station, config.meter, config.key_mode)
         .await?;
 
     info!(
@@ -337,7 +445,8 @@ async fn fetch_and_process(
             config.listen_seconds,
             config.bpm_mode,
             config.bars,
-            config.beats_per_bar,
+            config.meter,
+            config.key_mode,
         )
         .await?;
 
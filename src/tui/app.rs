@@ -1,7 +1,6 @@
-use std::collections::VecDeque;
 use std::io::{self, Stdout};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use crossterm::execute;
@@ -13,11 +12,12 @@ use tracing::{debug, error, info};
 
 use crate::app::{AppState, LoopInfo, Settings, StationInfo};
 use crate::error::TuiError;
+use crate::playback::{LimiterMeter, PositionSnapshot, HISTORY_CLIPS};
 use crate::tasks::ProducerCommand;
 
-use super::widgets::{now_playing, settings, up_next, world_map};
+use super::widgets::{history, now_playing, settings, up_next, world_map};
 use now_playing::PlayStatus;
-use up_next::QueuedStation;
+use up_next::{QueuedStation, UpNextQueue};
 
 /// TUI application state
 pub struct TuiApp {
@@ -28,17 +28,50 @@ pub struct TuiApp {
     // Now playing state (what's actually playing right now)
     now_playing_station: Option<StationInfo>,
     now_playing_loop: Option<LoopInfo>,
-    now_playing_started: Option<Instant>,
+    /// ID of the clip shown as now playing
+    now_playing_clip: Option<u64>,
+    /// Playback position reported by the playback engine
+    position: PositionSnapshot,
 
     // Queue of upcoming stations
-    up_next: VecDeque<QueuedStation>,
+    up_next: UpNextQueue,
+    /// Panel the arrow keys and Enter act on
+    focus: Panel,
 
     // Display state
-    station_history: Vec<StationInfo>,
+    /// Clips that have played, oldest first
+    station_history: Vec<QueuedStation>,
+    /// History entry picked for replay (0 = most recent)
+    history_selected: Option<usize>,
     play_status: PlayStatus,
     last_error: Option<String>,
+    /// Confirmation shown in the footer (e.g. where a loop was exported)
+    last_notice: Option<String>,
+
+    /// Length of the running session recording
+    recording: Option<Duration>,
+    /// Queued clip playing on the cue device
+    cued_clip: Option<u64>,
+
+    // Master limiter gain reduction (falls back slowly after peaks)
+    limiter_meter: Option<Arc<LimiterMeter>>,
+    gain_reduction_db: f32,
 }
 
+/// Left panel taking the cursor keys (Tab switches)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Panel {
+    #[default]
+    History,
+    UpNext,
+}
+
+/// Gain reduction meter fall-back per drawn frame (dB, ~20 dB/s at 60 FPS)
+const GAIN_REDUCTION_FALLBACK_DB: f32 = 0.35;
+
+/// Gain reduction shown by a full meter (dB)
+const GAIN_REDUCTION_FULL_SCALE_DB: f32 = 12.0;
+
 impl TuiApp {
     /// Create a new TUI application
     pub fn new(
@@ -58,11 +91,19 @@ impl TuiApp {
             terminal,
             now_playing_station: None,
             now_playing_loop: None,
-            now_playing_started: None,
-            up_next: VecDeque::new(),
+            now_playing_clip: None,
+            position: PositionSnapshot::default(),
+            up_next: UpNextQueue::default(),
+            focus: Panel::default(),
             station_history: Vec::new(),
+            history_selected: None,
             play_status: PlayStatus::Idle,
             last_error: None,
+            last_notice: None,
+            recording: None,
+            cued_clip: None,
+            limiter_meter: None,
+            gain_reduction_db: 0.0,
         })
     }
 
@@ -74,80 +115,165 @@ impl TuiApp {
         Ok(())
     }
 
+    /// Show gain reduction from this limiter meter (replaced when the engine is recreated)
+    pub fn set_limiter_meter(&mut self, meter: Arc<LimiterMeter>) {
+        self.limiter_meter = Some(meter);
+        self.gain_reduction_db = 0.0;
+    }
+
+    /// Follow the clip the playback engine is actually playing
+    ///
+    /// When a queued clip starts playing it becomes now playing; clips queued
+    /// before it were dropped by the engine and leave the queue too.
+    /// `queued` lists the engine's queue (clip IDs, next first) and is only
+    /// asked for when the playing clip changes.
+    pub fn sync_position(&mut self, position: PositionSnapshot, queued: impl FnOnce() -> Vec<u64>) {
+        self.position = position;
+
+        let Some(clip_id) = position.clip_id() else {
+            return;
+        };
+        if self.now_playing_clip == Some(clip_id) {
+            return;
+        }
+        if let Some(next) = self.up_next.take_playing(clip_id) {
+            self.set_now_playing(next, &queued());
+        }
+    }
+
     /// Update display with new station (loading state)
     /// This is called when a worker starts processing a station
     pub fn set_loading(&mut self, _station: StationInfo) {
         // Loading state is now shown implicitly when we have items in queue
         // but nothing playing yet, or when queue is empty
-        if self.now_playing_station.is_none() && self.up_next.is_empty() {
+        if self.now_playing_station.is_none() && self.up_next.entries.is_empty() {
             self.play_status = PlayStatus::Loading;
         }
     }
 
-    /// Set a station as now playing immediately (first clip)
-    pub fn set_now_playing(&mut self, station: StationInfo, loop_info: LoopInfo) {
-        // Add previous station to history
-        if let Some(prev) = self.now_playing_station.take() {
+    /// Show a clip as now playing, moving the previous station to history
+    ///
+    /// A previous clip the engine queued again (skipped for a replay) goes
+    /// back to up next instead.
+    fn set_now_playing(&mut self, next: QueuedStation, queued: &[u64]) {
+        let prev_station = self.now_playing_station.take();
+        let prev_loop = self.now_playing_loop.take();
+        let prev = match (prev_station, prev_loop, self.now_playing_clip) {
+            (Some(station), Some(loop_info), Some(clip_id)) => {
+                self.up_next.requeue(QueuedStation { station, loop_info, clip_id }, queued)
+            }
+            _ => None,
+        };
+        if let Some(prev) = prev {
+            self.station_history.retain(|entry| entry.clip_id != prev.clip_id);
             self.station_history.push(prev);
-            // Keep last 10 stations
-            if self.station_history.len() > 10 {
+            if self.station_history.len() > HISTORY_CLIPS {
                 self.station_history.remove(0);
             }
         }
 
-        self.now_playing_station = Some(station);
-        self.now_playing_loop = Some(loop_info);
-        self.now_playing_started = Some(Instant::now());
+        self.now_playing_station = Some(next.station);
+        self.now_playing_loop = Some(next.loop_info);
+        self.now_playing_clip = Some(next.clip_id);
         self.play_status = PlayStatus::Playing;
         self.last_error = None;
+        self.last_notice = None;
     }
 
-    /// Add a station to the up next queue (subsequent clips)
-    pub fn add_to_queue(&mut self, station: StationInfo, loop_info: LoopInfo) {
-        self.up_next.push_back(QueuedStation { station, loop_info });
-        self.play_status = PlayStatus::Playing;
+    /// Swap in re-quantized loop info for the now-playing station
+    pub fn replace_now_playing(&mut self, loop_info: LoopInfo, clip_id: u64) {
+        if self.now_playing_station.is_some() {
+            self.now_playing_loop = Some(loop_info);
+            self.now_playing_clip = Some(clip_id);
+        }
+    }
+
+    /// Add a clip handed to the playback engine to the up next queue
+    ///
+    /// It moves to now playing once the engine reports it playing.
+    pub fn add_to_queue(&mut self, station: StationInfo, loop_info: LoopInfo, clip_id: u64) {
+        self.up_next.entries.push_back(QueuedStation { station, loop_info, clip_id });
         self.last_error = None;
     }
 
-    /// Advance to the next station in queue (called when playback actually advances)
-    /// Returns (duration_secs, bpm) of the new now-playing clip, if any
-    pub fn advance_queue(&mut self) -> Option<(f32, f32)> {
-        if let Some(next) = self.up_next.pop_front() {
-            // Add current to history
-            if let Some(prev) = self.now_playing_station.take() {
-                self.station_history.push(prev);
-                if self.station_history.len() > 10 {
-                    self.station_history.remove(0);
-                }
-            }
+    /// Move a clip the engine is replaying from the history to the front of up next
+    pub fn requeue_from_history(&mut self, clip_id: u64) {
+        if let Some(index) = self.station_history.iter().position(|entry| entry.clip_id == clip_id) {
+            let entry = self.station_history.remove(index);
+            self.up_next.entries.push_front(entry);
+        }
+        self.history_selected = None;
+    }
 
-            let duration_secs = next.loop_info.duration_samples as f32 / next.loop_info.sample_rate as f32;
-            let bpm = next.loop_info.bpm;
-            self.now_playing_station = Some(next.station);
-            self.now_playing_loop = Some(next.loop_info);
-            self.now_playing_started = Some(Instant::now());
-            Some((duration_secs, bpm))
-        } else {
-            None
+    /// Ask for a history entry (0 = most recent) to be played again
+    async fn replay_history(&mut self, index: usize) {
+        let Some(entry) = self.station_history.iter().rev().nth(index) else {
+            return;
+        };
+        debug!(station = %entry.station.name, "Replay requested");
+        let _ = self.cmd_tx.send(ProducerCommand::Replay(entry.clip_id)).await;
+    }
+
+    /// Move the history selection, newest entry first
+    fn select_history(&mut self, step: isize) {
+        if self.station_history.is_empty() {
+            self.history_selected = None;
+            return;
         }
+        let last = self.station_history.len() - 1;
+        self.history_selected = Some(match self.history_selected {
+            None => 0,
+            Some(index) => index.saturating_add_signed(step).min(last),
+        });
     }
 
+    /// Station a clip came from, if it is playing, queued or in the history
+    pub fn station_for_clip(&self, clip_id: u64) -> Option<StationInfo> {
+        if self.now_playing_clip == Some(clip_id) {
+            return self.now_playing_station.clone();
+        }
+        self.up_next
+            .entries
+            .iter()
+            .chain(&self.station_history)
+            .find(|entry| entry.clip_id == clip_id)
+            .map(|entry| entry.station.clone())
+    }
 
-    /// Get the number of items in the up next queue
-    pub fn queue_len(&self) -> usize {
-        self.up_next.len()
+    /// Mark the queued clip being previewed on the cue device
+    pub fn set_cued(&mut self, clip_id: Option<u64>) {
+        self.cued_clip = clip_id;
     }
 
-    /// Legacy method for compatibility - routes to appropriate method
-    #[allow(dead_code)]
-    pub fn set_playing(&mut self, station: StationInfo, loop_info: LoopInfo) {
-        // This is kept for backwards compatibility but main.rs should use
-        // set_now_playing() or add_to_queue() directly
-        if self.now_playing_station.is_none() {
-            self.set_now_playing(station, loop_info);
-        } else {
-            self.add_to_queue(station, loop_info);
+    /// Drop a clip from up next (removed from the engine's queue)
+    pub fn remove_from_queue(&mut self, clip_id: u64) {
+        self.up_next.remove(clip_id);
+    }
+
+    /// Put up next in the order of the engine's queue (clip IDs, next first)
+    pub fn reorder_queue(&mut self, queued: &[u64]) {
+        self.up_next.reorder(queued);
+    }
+
+    /// Send a queue edit for the clip under the up next cursor
+    async fn edit_selected(&mut self, command: impl FnOnce(u64) -> ProducerCommand) {
+        if self.focus != Panel::UpNext {
+            return;
         }
+        if let Some(clip_id) = self.up_next.selected() {
+            let _ = self.cmd_tx.send(command(clip_id)).await;
+        }
+    }
+
+    /// Show the length of the running session recording (`None` when not recording)
+    pub fn set_recording(&mut self, elapsed: Option<Duration>) {
+        self.recording = elapsed;
+    }
+
+    /// Show a confirmation in the footer
+    pub fn set_notice(&mut self, message: String) {
+        self.last_error = None;
+        self.last_notice = Some(message);
     }
 
     /// Update display with error
@@ -160,19 +286,25 @@ impl TuiApp {
         }
         // Always store the error for display in footer
         self.last_error = Some(message);
+        self.last_notice = None;
     }
 
     /// Draw the TUI
     pub fn draw(&mut self, settings: &Settings) -> Result<(), TuiError> {
-        // Queue advancement is now handled by main.rs based on actual sink state
+        // Queue advancement follows the playback position (see sync_position)
 
         let now_playing_station = self.now_playing_station.clone();
         let now_playing_loop = self.now_playing_loop.clone();
-        let up_next_queue: Vec<QueuedStation> = self.up_next.iter().map(|q| QueuedStation {
+        let up_next_queue: Vec<QueuedStation> = self.up_next.entries.iter().map(|q| QueuedStation {
             station: q.station.clone(),
             loop_info: q.loop_info.clone(),
+            clip_id: q.clip_id,
         }).collect();
         let station_history = self.station_history.clone();
+        let history_focused = self.focus == Panel::History;
+        let history_selected = self.history_selected.filter(|_| history_focused);
+        let queue_selected = self.up_next.selected().filter(|_| !history_focused);
+        let history_stations: Vec<StationInfo> = station_history.iter().map(|entry| entry.station.clone()).collect();
         let play_status = match &self.play_status {
             PlayStatus::Idle => PlayStatus::Idle,
             PlayStatus::Loading => PlayStatus::Loading,
@@ -180,6 +312,18 @@ impl TuiApp {
             PlayStatus::Error(msg) => PlayStatus::Error(msg.clone()),
         };
         let last_error = self.last_error.clone();
+        let last_notice = self.last_notice.clone();
+        let paused = self.state.is_paused();
+        let recording = self.recording;
+        let cued_clip = self.cued_clip;
+        // Position only describes the now-playing clip once the engine has caught up
+        let position = Some(self.position)
+            .filter(|position| position.clip_id().is_some_and(|id| self.now_playing_clip == Some(id)));
+
+        // Peak gain reduction since the last frame, falling back slowly
+        let latest_reduction = self.limiter_meter.as_ref().map_or(0.0, |meter| meter.take());
+        self.gain_reduction_db = latest_reduction.max(self.gain_reduction_db - GAIN_REDUCTION_FALLBACK_DB);
+        let gain_reduction_db = self.gain_reduction_db;
 
         self.terminal.draw(|frame| {
             let area = frame.area();
@@ -195,7 +339,7 @@ impl TuiApp {
                 .split(area);
 
             // Render header
-            render_header(frame, main_chunks[0], &play_status);
+            render_header(frame, main_chunks[0], &play_status, paused, recording, gain_reduction_db);
 
             // Body layout: left panel (30%) + world map (70%)
             let body_chunks = Layout::default()
@@ -207,25 +351,13 @@ impl TuiApp {
             let left_chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
-                    Constraint::Length(9),  // Settings
-                    Constraint::Length(13), // Now Playing
+                    Constraint::Length(11), // Settings
+                    Constraint::Length(15), // Now Playing
                     Constraint::Min(5),     // Up Next
+                    Constraint::Min(5),     // History
                 ])
                 .split(body_chunks[0]);
 
-            // Calculate playback progress for countdown visual
-            let playback_progress = if let (Some(started), Some(loop_info)) = (self.now_playing_started, &now_playing_loop) {
-                let elapsed = started.elapsed().as_secs_f32();
-                let duration = loop_info.duration_samples as f32 / loop_info.sample_rate as f32;
-                if duration > 0.0 {
-                    Some((elapsed / duration).min(1.0))
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
             // Render panels
             settings::render(frame, left_chunks[0], settings);
             now_playing::render(
@@ -234,18 +366,19 @@ impl TuiApp {
                 now_playing_station.as_ref(),
                 now_playing_loop.as_ref(),
                 &play_status,
-                playback_progress,
+                position,
             );
-            up_next::render(frame, left_chunks[2], &up_next_queue);
+            up_next::render(frame, left_chunks[2], &up_next_queue, cued_clip, queue_selected, !history_focused);
+            history::render(frame, left_chunks[3], &station_history, history_selected, history_focused);
             world_map::render(
                 frame,
                 body_chunks[1],
                 now_playing_station.as_ref(),
-                &station_history,
+                &history_stations,
             );
 
             // Render footer with controls and any error
-            render_footer(frame, main_chunks[2], last_error.as_deref());
+            render_footer(frame, main_chunks[2], last_error.as_deref(), last_notice.as_deref());
         })?;
 
         Ok(())
@@ -283,13 +416,107 @@ impl TuiApp {
                             let mut settings = self.state.settings.write().await;
                             settings.cycle_bars_down();
                         }
+                        KeyCode::Char(' ') => {
+                            let paused = self.state.toggle_pause();
+                            info!(paused, "Toggled pause");
+                        }
+                        KeyCode::Char(']') => {
+                            debug!("Volume up");
+                            let mut settings = self.state.settings.write().await;
+                            settings.volume_up();
+                        }
+                        KeyCode::Char('[') => {
+                            debug!("Volume down");
+                            let mut settings = self.state.settings.write().await;
+                            settings.volume_down();
+                        }
+                        KeyCode::Char('m') => {
+                            debug!("Toggle mute");
+                            let mut settings = self.state.settings.write().await;
+                            settings.toggle_mute();
+                        }
+                        KeyCode::Char('p') => {
+                            debug!("Previous clip requested");
+                            self.replay_history(0).await;
+                        }
+                        KeyCode::Tab => {
+                            self.focus = match self.focus {
+                                Panel::History => Panel::UpNext,
+                                Panel::UpNext => Panel::History,
+                            };
+                            if self.focus == Panel::UpNext && self.up_next.selected().is_none() {
+                                self.up_next.select(0);
+                            }
+                        }
+                        KeyCode::Up => match self.focus {
+                            Panel::History => self.select_history(-1),
+                            Panel::UpNext => self.up_next.select(-1),
+                        },
+                        KeyCode::Down => match self.focus {
+                            Panel::History => self.select_history(1),
+                            Panel::UpNext => self.up_next.select(1),
+                        },
+                        KeyCode::Enter => match self.focus {
+                            Panel::History => {
+                                if let Some(index) = self.history_selected {
+                                    self.replay_history(index).await;
+                                }
+                            }
+                            Panel::UpNext => {
+                                debug!("Pin queued clip");
+                                self.edit_selected(ProducerCommand::PinQueued).await;
+                            }
+                        },
+                        KeyCode::Char('x') | KeyCode::Delete => {
+                            debug!("Remove queued clip");
+                            self.edit_selected(ProducerCommand::RemoveQueued).await;
+                        }
+                        KeyCode::Char('K') => {
+                            self.edit_selected(|clip_id| ProducerCommand::MoveQueued(clip_id, -1)).await;
+                        }
+                        KeyCode::Char('J') => {
+                            self.edit_selected(|clip_id| ProducerCommand::MoveQueued(clip_id, 1)).await;
+                        }
+                        KeyCode::Char('e') => {
+                            debug!("Export current loop");
+                            let _ = self.cmd_tx.send(ProducerCommand::Export).await;
+                        }
+                        KeyCode::Char('c') => {
+                            debug!("Toggle cue preview");
+                            let _ = self.cmd_tx.send(ProducerCommand::ToggleCue).await;
+                        }
+                        KeyCode::Char('C') => {
+                            debug!("Drop cued clip");
+                            let _ = self.cmd_tx.send(ProducerCommand::DropCued).await;
+                        }
+                        KeyCode::Char('r') => {
+                            debug!("Toggle session recording");
+                            let _ = self.cmd_tx.send(ProducerCommand::ToggleRecord).await;
+                        }
+                        KeyCode::Char('l') => {
+                            debug!("Toggle loop hold");
+                            let _ = self.cmd_tx.send(ProducerCommand::ToggleHold).await;
+                        }
+                        KeyCode::Char('t') => {
+                            debug!("Cycle transition style");
+                            let mut settings = self.state.settings.write().await;
+                            settings.cycle_transition();
+                        }
+                        KeyCode::Char('<') => {
+                            debug!("Re-quantize at half tempo");
+                            let _ = self.cmd_tx.send(ProducerCommand::Requantize(0.5)).await;
+                        }
+                        KeyCode::Char('>') => {
+                            debug!("Re-quantize at double tempo");
+                            let _ = self.cmd_tx.send(ProducerCommand::Requantize(2.0)).await;
+                        }
                         KeyCode::Char('d') => {
                             debug!("Cycle audio device");
                             let mut settings = self.state.settings.write().await;
                             if settings.next_audio_device() {
-                                let device_index = settings.audio_device_index;
+                                let device = settings.audio_device.clone().unwrap_or_default();
                                 drop(settings); // Release lock before async send
-                                let _ = self.cmd_tx.send(ProducerCommand::AudioDeviceChanged(device_index)).await;
+                                let _ = self.cmd_tx.send(ProducerCommand::AudioDeviceChanged(device)).await;
                             }
                         }
                         _ => {}
@@ -316,20 +543,45 @@ impl Drop for TuiApp {
 }
 
 /// Render the header bar
-fn render_header(frame: &mut Frame, area: Rect, status: &PlayStatus) {
+fn render_header(
+    frame: &mut Frame,
+    area: Rect,
+    status: &PlayStatus,
+    paused: bool,
+    recording: Option<Duration>,
+    gain_reduction_db: f32,
+) {
     let status_text = match status {
         PlayStatus::Idle => ("IDLE", Color::Gray),
         PlayStatus::Loading => ("LOADING", Color::Yellow),
+        PlayStatus::Playing if paused => ("PAUSED", Color::Yellow),
         PlayStatus::Playing => ("PLAYING", Color::Green),
         PlayStatus::Error(_) => ("ERROR", Color::Red),
     };
 
-    let title = Line::from(vec![
+    let mut spans = vec![
         Span::styled(" tappr ", Style::default().bold().fg(Color::Cyan)),
         Span::raw("| "),
         Span::styled(status_text.0, Style::default().fg(status_text.1)),
-        Span::raw(" | Ride the beat of the world's airwaves"),
+    ];
+    if let Some(elapsed) = recording {
+        let secs = elapsed.as_secs();
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(
+            format!("● REC {:02}:{:02}", secs / 60, secs % 60),
+            Style::default().fg(Color::Red).bold(),
+        ));
+    }
+    spans.extend([
+        Span::raw(" | Ride the beat of the world's airwaves | "),
+        Span::styled("GR ", Style::default().fg(Color::Gray)),
+        gain_reduction_meter(gain_reduction_db),
+        Span::styled(
+            format!(" -{:.1} dB", gain_reduction_db),
+            Style::default().fg(Color::DarkGray),
+        ),
     ]);
+    let title = Line::from(spans);
 
     let block = Block::default()
         .borders(Borders::BOTTOM)
@@ -339,23 +591,66 @@ fn render_header(frame: &mut Frame, area: Rect, status: &PlayStatus) {
     frame.render_widget(paragraph, area);
 }
 
+/// Bar meter for limiter gain reduction
+fn gain_reduction_meter(reduction_db: f32) -> Span<'static> {
+    const WIDTH: usize = 8;
+    let filled = ((reduction_db / GAIN_REDUCTION_FULL_SCALE_DB) * WIDTH as f32)
+        .ceil()
+        .clamp(0.0, WIDTH as f32) as usize;
+
+    let color = match reduction_db {
+        db if db >= 6.0 => Color::Red,
+        db if db >= 3.0 => Color::Yellow,
+        _ => Color::Green,
+    };
+
+    Span::styled(
+        format!("{}{}", "▮".repeat(filled), "▯".repeat(WIDTH - filled)),
+        Style::default().fg(color),
+    )
+}
+
 /// Render the footer with controls
-fn render_footer(frame: &mut Frame, area: Rect, error: Option<&str>) {
+fn render_footer(frame: &mut Frame, area: Rect, error: Option<&str>, notice: Option<&str>) {
     let controls = if let Some(err) = error {
         Line::from(vec![
             Span::styled("Error: ", Style::default().fg(Color::Red)),
             Span::styled(err, Style::default().fg(Color::Red)),
         ])
+    } else if let Some(notice) = notice {
+        Line::from(Span::styled(notice, Style::default().fg(Color::Green)))
     } else {
         Line::from(vec![
             Span::styled("q", Style::default().fg(Color::Yellow)),
             Span::raw(":quit  "),
             Span::styled("n", Style::default().fg(Color::Yellow)),
             Span::raw(":next  "),
+            Span::styled("space", Style::default().fg(Color::Yellow)),
+            Span::raw(":pause  "),
+            Span::styled("[/]", Style::default().fg(Color::Yellow)),
+            Span::raw(":volume  "),
+            Span::styled("m", Style::default().fg(Color::Yellow)),
+            Span::raw(":mute  "),
             Span::styled("b", Style::default().fg(Color::Yellow)),
             Span::raw(":bpm  "),
             Span::styled("+/-", Style::default().fg(Color::Yellow)),
             Span::raw(":bars  "),
+            Span::styled("</>", Style::default().fg(Color::Yellow)),
+            Span::raw(":half/double  "),
+            Span::styled("p", Style::default().fg(Color::Yellow)),
+            Span::raw(":previous  "),
+            Span::styled("l", Style::default().fg(Color::Yellow)),
+            Span::raw(":hold  "),
+            Span::styled("t", Style::default().fg(Color::Yellow)),
+            Span::raw(":transition  "),
+            Span::styled("e", Style::default().fg(Color::Yellow)),
+            Span::raw(":export  "),
+            Span::styled("c/C", Style::default().fg(Color::Yellow)),
+            Span::raw(":cue/drop  "),
+            Span::styled("tab", Style::default().fg(Color::Yellow)),
+            Span::raw(":history/queue  "),
+            Span::styled("r", Style::default().fg(Color::Yellow)),
+            Span::raw(":record  "),
             Span::styled("d", Style::default().fg(Color::Yellow)),
             Span::raw(":device"),
         ])
@@ -0,0 +1,47 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+use super::up_next::QueuedStation;
+
+/// Render the history panel, newest clip first
+/// selected: index (newest = 0) of the clip Enter would replay
+/// focused: the cursor keys act on this panel
+pub fn render(frame: &mut Frame, area: Rect, history: &[QueuedStation], selected: Option<usize>, focused: bool) {
+    let border = if focused { Color::Yellow } else { Color::Blue };
+    let block = Block::default()
+        .title(format!(" History ({}) ", history.len()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(border));
+
+    let lines: Vec<Line> = if history.is_empty() {
+        vec![Line::from(Span::styled(
+            "Played clips show up here",
+            Style::default().fg(Color::DarkGray).italic(),
+        ))]
+    } else {
+        history
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, entry)| {
+                let (marker, name_style) = if selected == Some(i) {
+                    ("> ", Style::default().fg(Color::Yellow).bold())
+                } else {
+                    ("  ", Style::default().fg(Color::Gray))
+                };
+
+                Line::from(vec![
+                    Span::styled(marker, Style::default().fg(Color::Yellow)),
+                    Span::styled(&entry.station.name, name_style),
+                    Span::styled(
+                        format!(" | {:.0} BPM", entry.loop_info.bpm),
+                        Style::default().fg(Color::Magenta),
+                    ),
+                ])
+            })
+            .collect()
+    };
+
+    let paragraph = Paragraph::new(lines).block(block);
+    frame.render_widget(paragraph, area);
+}
@@ -1,3 +1,4 @@
+pub mod history;
 pub mod now_playing;
 pub mod settings;
 pub mod up_next;
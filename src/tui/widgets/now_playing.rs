@@ -2,6 +2,7 @@ use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
 
 use crate::app::{LoopInfo, StationInfo};
+use crate::playback::PositionSnapshot;
 
 /// Current playback status
 pub enum PlayStatus {
@@ -57,14 +58,14 @@ fn progress_bar(progress: f32, width: usize) -> String {
 }
 
 /// Render the now playing panel
-/// progress: 0.0 = just started, 1.0 = finished (used for countdown visual)
+/// position: playback position of this clip (drives the beat counter and countdown visual)
 pub fn render(
     frame: &mut Frame,
     area: Rect,
     station: Option<&StationInfo>,
     loop_info: Option<&LoopInfo>,
     status: &PlayStatus,
-    progress: Option<f32>,
+    position: Option<PositionSnapshot>,
 ) {
     let (title_color, status_text) = match status {
         PlayStatus::Idle => (Color::Gray, "IDLE"),
@@ -148,10 +149,39 @@ pub fn render(
                 ]));
             }
 
+            // Show alternative tempo interpretations (octave errors etc.)
+            let alternatives: Vec<String> = info
+                .alternative_tempos()
+                .map(|bpm| format!("{:.0}", bpm))
+                .collect();
+            if !alternatives.is_empty() {
+                lines.push(Line::from(vec![
+                    Span::styled("Alt: ", Style::default().fg(Color::Gray)),
+                    Span::styled(
+                        alternatives.join(" / "),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]));
+            }
+
+            if let Some(key) = info.key {
+                let mut spans = vec![
+                    Span::styled("Key: ", Style::default().fg(Color::Gray)),
+                    Span::styled(key.to_string(), Style::default().fg(Color::Cyan)),
+                ];
+                if info.transpose_semitones != 0 {
+                    spans.push(Span::styled(
+                        format!(" [{:+} st]", info.transpose_semitones),
+                        Style::default().fg(Color::Yellow).italic(),
+                    ));
+                }
+                lines.push(Line::from(spans));
+            }
+
             lines.push(Line::from(vec![
                 Span::styled("Loop: ", Style::default().fg(Color::Gray)),
                 Span::styled(
-                    format!("{} bars", info.bars),
+                    format!("{} bars of {}", info.bars, info.time_signature()),
                     Style::default().fg(Color::Cyan),
                 ),
             ]));
@@ -174,10 +204,45 @@ pub fn render(
                 ]));
             }
 
-            // Add progress bar at bottom with spacing above
+            // Add beat counter and progress bar at bottom with spacing above
+            let progress = position.and_then(|position| position.loop_progress(info));
+            let beat_position = position.and_then(|position| position.beat_position(info));
             if let Some(p) = progress {
                 lines.push(Line::from("")); // Space above progress bar
-                let bar = progress_bar(p, 15);
+                if let Some((bar, beat)) = beat_position {
+                    lines.push(Line::from(vec![
+                        Span::styled("Beat: ", Style::default().fg(Color::Gray)),
+                        Span::styled(
+                            format!("{}.{}", bar, beat),
+                            Style::default().fg(Color::Magenta).bold(),
+                        ),
+                        Span::styled(
+                            format!(" of {} bars", info.bars),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ]));
+                }
+                let repeat = if p.held {
+                    vec![
+                        Span::styled("Repeat: ", Style::default().fg(Color::Gray)),
+                        Span::styled(p.pass.to_string(), Style::default().fg(Color::Cyan)),
+                        Span::styled("  [HELD]", Style::default().fg(Color::Yellow).bold()),
+                    ]
+                } else {
+                    vec![
+                        Span::styled("Repeat: ", Style::default().fg(Color::Gray)),
+                        Span::styled(
+                            format!("{}/{}", p.pass, p.passes),
+                            Style::default().fg(Color::Cyan),
+                        ),
+                        Span::styled(
+                            format!("  {:.0}s left", p.remaining_secs.ceil()),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ]
+                };
+                lines.push(Line::from(repeat));
+                let bar = progress_bar(p.progress, 15);
                 lines.push(Line::from(Span::styled(
                     bar,
                     Style::default().fg(Color::Green),
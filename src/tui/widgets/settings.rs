@@ -15,7 +15,21 @@ pub fn render(frame: &mut Frame, area: Rect, settings: &Settings) {
         BpmMode::Fixed(bpm) => format!("Fixed ({:.0})", bpm),
     };
 
-    let device_name = settings.current_audio_device_name();
+    let device_name = settings.output_name();
+
+    let volume_text = format!("{:.0}%", settings.volume * 100.0);
+    let volume = if settings.muted {
+        vec![
+            Span::styled("Volume: ", Style::default().fg(Color::Gray)),
+            Span::styled(volume_text, Style::default().fg(Color::DarkGray)),
+            Span::styled(" [MUTED]", Style::default().fg(Color::Red).bold()),
+        ]
+    } else {
+        vec![
+            Span::styled("Volume: ", Style::default().fg(Color::Gray)),
+            Span::styled(volume_text, Style::default().fg(Color::Yellow)),
+        ]
+    };
 
     let lines = vec![
         Line::from(vec![
@@ -32,13 +46,28 @@ pub fn render(frame: &mut Frame, area: Rect, settings: &Settings) {
         Line::from(vec![
             Span::styled("Meter: ", Style::default().fg(Color::Gray)),
             Span::styled(
-                format!("{}/4", settings.beats_per_bar),
+                settings.meter.to_string(),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled("Key: ", Style::default().fg(Color::Gray)),
+            Span::styled(
+                settings.key_mode.to_string(),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled("Transition: ", Style::default().fg(Color::Gray)),
+            Span::styled(
+                settings.transition.to_string(),
                 Style::default().fg(Color::Yellow),
             ),
         ]),
         Line::from(""),
+        Line::from(volume),
         Line::from(vec![
-            Span::styled("Device: ", Style::default().fg(Color::Gray)),
+            Span::styled("Output: ", Style::default().fg(Color::Gray)),
             Span::styled(device_name, Style::default().fg(Color::Magenta)),
         ]),
         Line::from(vec![
@@ -1,20 +1,124 @@
+use std::collections::VecDeque;
+
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
 
 use crate::app::{LoopInfo, StationInfo};
 
 /// Queued station info
+#[derive(Clone)]
 pub struct QueuedStation {
     pub station: StationInfo,
     pub loop_info: LoopInfo,
+    /// `LoopBuffer::id` of the queued clip
+    pub clip_id: u64,
+}
+
+/// Clips handed to the playback engine that haven't started playing, with
+/// the panel's cursor
+#[derive(Default)]
+pub struct UpNextQueue {
+    /// Queued clips, next first
+    pub entries: VecDeque<QueuedStation>,
+    /// Entry under the cursor (by clip ID, so it survives reordering)
+    selected: Option<u64>,
+}
+
+impl UpNextQueue {
+    /// Clip under the cursor, if it is still queued
+    pub fn selected(&self) -> Option<u64> {
+        self.selected_index().map(|index| self.entries[index].clip_id)
+    }
+
+    /// Take out a clip that started playing, with the clips queued before it
+    /// (the engine dropped those)
+    pub fn take_playing(&mut self, clip_id: u64) -> Option<QueuedStation> {
+        let index = self.entries.iter().position(|queued| queued.clip_id == clip_id)?;
+        self.entries.drain(..index);
+        self.entries.pop_front()
+    }
+
+    /// Put a clip that stopped playing back in line if the engine queued it
+    /// again (`queued`: the engine's clip IDs, next first)
+    ///
+    /// Returns the clip if it isn't queued, as it has played.
+    pub fn requeue(&mut self, entry: QueuedStation, queued: &[u64]) -> Option<QueuedStation> {
+        if !queued.contains(&entry.clip_id) {
+            return Some(entry);
+        }
+        self.entries.push_back(entry);
+        self.reorder(queued);
+        None
+    }
+
+    /// Drop a clip (removed from the engine's queue)
+    ///
+    /// A cursor on it moves to the entry taking its place.
+    pub fn remove(&mut self, clip_id: u64) {
+        let Some(index) = self.entries.iter().position(|queued| queued.clip_id == clip_id) else {
+            return;
+        };
+        self.entries.remove(index);
+        if self.selected == Some(clip_id) {
+            self.selected = self
+                .entries
+                .get(index)
+                .or(self.entries.back())
+                .map(|queued| queued.clip_id);
+        }
+    }
+
+    /// Put the entries in the order of the engine's queue (clip IDs, next first)
+    ///
+    /// Entries the engine no longer holds as waiting, like the incoming clip
+    /// of a running transition, stay in front.
+    pub fn reorder(&mut self, queued: &[u64]) {
+        self.entries
+            .make_contiguous()
+            .sort_by_key(|entry| queued.iter().position(|&id| id == entry.clip_id).map_or(0, |index| index + 1));
+    }
+
+    /// Move the cursor, next clip first
+    pub fn select(&mut self, step: isize) {
+        if self.entries.is_empty() {
+            self.selected = None;
+            return;
+        }
+        let last = self.entries.len() - 1;
+        let index = match self.selected_index() {
+            None => 0,
+            Some(index) => index.saturating_add_signed(step).min(last),
+        };
+        self.selected = Some(self.entries[index].clip_id);
+    }
+
+    /// Position of the cursor, if its clip is still queued
+    fn selected_index(&self) -> Option<usize> {
+        let clip_id = self.selected?;
+        self.entries.iter().position(|queued| queued.clip_id == clip_id)
+    }
 }
 
 /// Render the up next panel showing queued stations
-pub fn render(frame: &mut Frame, area: Rect, queue: &[QueuedStation]) {
+/// cued: clip being previewed on the cue device
+/// selected: clip under the cursor, focused: the cursor keys act on this panel
+pub fn render(
+    frame: &mut Frame,
+    area: Rect,
+    queue: &[QueuedStation],
+    cued: Option<u64>,
+    selected: Option<u64>,
+    focused: bool,
+) {
+    let (title, border) = if focused {
+        (format!(" Up Next ({}) x:remove J/K:move Enter:play next ", queue.len()), Color::Yellow)
+    } else {
+        (format!(" Up Next ({}) ", queue.len()), Color::Blue)
+    };
     let block = Block::default()
-        .title(format!(" Up Next ({}) ", queue.len()))
+        .title(title)
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Blue));
+        .border_style(Style::default().fg(border));
 
     let lines: Vec<Line> = if queue.is_empty() {
         vec![
@@ -30,7 +134,10 @@ pub fn render(frame: &mut Frame, area: Rect, queue: &[QueuedStation]) {
             .enumerate()
             .flat_map(|(i, q)| {
                 let num_style = Style::default().fg(Color::DarkGray);
-                let name_style = if i == 0 {
+                let is_selected = selected == Some(q.clip_id);
+                let name_style = if is_selected {
+                    Style::default().fg(Color::Yellow).bold()
+                } else if i == 0 {
                     Style::default().fg(Color::White).bold()
                 } else {
                     Style::default().fg(Color::Gray)
@@ -48,11 +155,18 @@ pub fn render(frame: &mut Frame, area: Rect, queue: &[QueuedStation]) {
                     vec![Span::styled(format!("{:.0} BPM", q.loop_info.bpm), bpm_style)]
                 };
 
+                let marker = if is_selected { "> " } else { "" };
+                let mut title = vec![
+                    Span::styled(marker, Style::default().fg(Color::Yellow)),
+                    Span::styled(format!("{}. ", i + 1), num_style),
+                    Span::styled(&q.station.name, name_style),
+                ];
+                if cued == Some(q.clip_id) {
+                    title.push(Span::styled(" [CUE]", Style::default().fg(Color::Green).bold()));
+                }
+
                 vec![
-                    Line::from(vec![
-                        Span::styled(format!("{}. ", i + 1), num_style),
-                        Span::styled(&q.station.name, name_style),
-                    ]),
+                    Line::from(title),
                     Line::from(
                         vec![
                             Span::raw("   "),
@@ -74,3 +188,132 @@ pub fn render(frame: &mut Frame, area: Rect, queue: &[QueuedStation]) {
     let paragraph = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
     frame.render_widget(paragraph, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::LoopBuffer;
+    use crate::playback::{deck_mixer, TransitionStyle};
+
+    fn queue(clip_ids: &[u64]) -> UpNextQueue {
+        let entries = clip_ids
+            .iter()
+            .map(|&clip_id| QueuedStation {
+                station: StationInfo::default(),
+                loop_info: LoopInfo::for_test(120.0, 48_000),
+                clip_id,
+            })
+            .collect();
+        UpNextQueue { entries, selected: None }
+    }
+
+    fn clip_ids(queue: &UpNextQueue) -> Vec<u64> {
+        queue.entries.iter().map(|queued| queued.clip_id).collect()
+    }
+
+    #[test]
+    fn test_replay_during_skip_follows_mixer_order() {
+        let (handle, mut mixer) = deck_mixer();
+        handle.set_transition(TransitionStyle::Cut);
+        // Silent one bar clips of two seconds
+        let clips: Vec<LoopBuffer> = (0..4)
+            .map(|_| LoopBuffer::new(vec![0.0; 2 * 96_000], LoopInfo::for_test(120.0, 96_000)))
+            .collect();
+        let ids: Vec<u64> = clips.iter().map(|buffer| buffer.id).collect();
+        let mut up_next = queue(&ids);
+        clips.into_iter().for_each(|buffer| handle.append(buffer));
+
+        let mut playing: Option<QueuedStation> = None;
+        let mut history: Vec<QueuedStation> = Vec::new();
+        // What the TUI does each frame (see TuiApp::sync_position)
+        let mut play = |frames: usize,
+                        up_next: &mut UpNextQueue,
+                        playing: &mut Option<QueuedStation>,
+                        history: &mut Vec<QueuedStation>| {
+            for _ in 0..frames / 1_000 {
+                mixer.by_ref().take(2_000).for_each(drop);
+                let Some(clip_id) = handle.position().clip_id() else {
+                    continue;
+                };
+                if playing.as_ref().is_some_and(|entry| entry.clip_id == clip_id) {
+                    continue;
+                }
+                let Some(next) = up_next.take_playing(clip_id) else {
+                    continue;
+                };
+                let queued: Vec<u64> = handle.queued().iter().map(|buffer| buffer.id).collect();
+                if let Some(prev) = playing.replace(next).and_then(|prev| up_next.requeue(prev, &queued)) {
+                    history.push(prev);
+                }
+            }
+        };
+        let playing_id = |playing: &Option<QueuedStation>| playing.as_ref().map(|entry| entry.clip_id);
+
+        play(100_000, &mut up_next, &mut playing, &mut history);
+        assert_eq!(playing_id(&playing), Some(ids[1]));
+
+        // Replay the first clip while skipping the second: the skip fade
+        // finishes on the third clip, which then makes way for the replay
+        handle.skip();
+        play(1_000, &mut up_next, &mut playing, &mut history);
+        assert_eq!(playing_id(&playing), Some(ids[2]));
+        assert!(handle.replay(ids[0]));
+        let index = history.iter().position(|entry| entry.clip_id == ids[0]).unwrap();
+        up_next.entries.push_front(history.remove(index));
+
+        play(10_000, &mut up_next, &mut playing, &mut history);
+        assert_eq!(playing_id(&playing), Some(ids[0]));
+        let queued: Vec<u64> = handle.queued().iter().map(|buffer| buffer.id).collect();
+        assert_eq!(queued, vec![ids[2], ids[3]]);
+        assert_eq!(clip_ids(&up_next), queued);
+
+        // The skipped clip comes back as now playing after the replay
+        play(100_000, &mut up_next, &mut playing, &mut history);
+        assert_eq!(playing_id(&playing), Some(ids[2]));
+        assert_eq!(clip_ids(&up_next), vec![ids[3]]);
+    }
+
+    #[test]
+    fn test_cursor_follows_removed_entry() {
+        let mut queue = queue(&[1, 2, 3]);
+        queue.select(0);
+        queue.select(1);
+        assert_eq!(queue.selected(), Some(2));
+
+        // The entry taking its place
+        queue.remove(2);
+        assert_eq!(queue.selected(), Some(3));
+
+        // Removing the last entry moves the cursor up
+        queue.remove(3);
+        assert_eq!(queue.selected(), Some(1));
+
+        queue.remove(1);
+        assert_eq!(queue.selected(), None);
+        queue.select(1);
+        assert_eq!(queue.selected(), None);
+    }
+
+    #[test]
+    fn test_cursor_stays_when_other_entry_removed() {
+        let mut queue = queue(&[1, 2, 3]);
+        queue.select(0);
+        queue.remove(3);
+        assert_eq!(queue.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_reorder_keeps_incoming_clip_in_front() {
+        // Clip 1 is coming in on a transition, so the engine no longer queues it
+        let mut queue = queue(&[1, 2, 3, 4]);
+        queue.select(0);
+        queue.select(2);
+        queue.reorder(&[4, 2, 3]);
+        assert_eq!(clip_ids(&queue), vec![1, 4, 2, 3]);
+
+        // The cursor stays on its clip
+        assert_eq!(queue.selected(), Some(3));
+        queue.select(-1);
+        assert_eq!(queue.selected(), Some(2));
+    }
+}